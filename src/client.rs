@@ -0,0 +1,825 @@
+//! Typed client of the HTTP API, for driving forks from Rust test suites.
+//! Every route has a method, except the account watch, transaction events
+//! and WebSocket streams.
+
+use anyhow::Context;
+use base64::{Engine, engine};
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use solana_sdk::{hash::Hash, pubkey::Pubkey, transaction::VersionedTransaction};
+use uuid::Uuid;
+
+use crate::types::{
+    AccountExistsQuery, AccountReadQuery, ActiveFeature, ApiResponse,
+    AssociatedTokenAddressRequest, BatchDeleteResult, BlockhashResponse, ClonedProgram, ComputeFit,
+    ComputeFitRequest, CreateLookupTableRequest, CreateMintRequest, DiffForksRequest, EpochInfo,
+    ExecuteRequest, ExecuteResponse, ExtendLookupTableRequest, FeeEstimate, FeeSchedule,
+    FindPdaRequest, ForkDiff, ForkStats, ForkSummary, FreezeTokenRequest, GetAccountRequest,
+    HealthResponse, ListForksQuery, LogMatch, MinimumBalanceQuery, ProgramAccount, ProgramAddress,
+    ReplayHistoryRequest, ReplayResult, SendResult, SetBlockhashRequest, SetEpochRequest,
+    SetExecutableRequest, SetLamportsRequest, SetOwnerRequest, SetRentRequest,
+    SetTokenBalanceRequest, SetTokenDelegateRequest, SetupResult, SimulateMatchRequest,
+    SimulateRequest, SimulateResponse, SlotHashEntry, SlotHashesQuery, TokenBalance,
+    TouchedAccount, TransactionBreakdown, TransactionLookupQuery, TransactionPage,
+    TransactionRecord, UiAccount,
+};
+
+/// Client of a simulation engine server. Every method unwraps the
+/// [`ApiResponse`] of its route, failing with the server's error.
+///
+/// Bodies whose types only live in the server (fork options, setups,
+/// program account filters, history queries) are taken as any
+/// [`Serialize`] value, e.g. a `json!` literal.
+#[derive(Clone)]
+pub struct ForkClient {
+    http: reqwest::Client,
+    base_url: String,
+}
+
+impl ForkClient {
+    /// Client of the server listening at `base_url`, e.g.
+    /// `http://localhost:8080`
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_http_client(reqwest::Client::new(), base_url)
+    }
+
+    /// Client sending its requests with `http`, to configure timeouts or
+    /// proxies
+    pub fn with_http_client(http: reqwest::Client, base_url: impl Into<String>) -> Self {
+        ForkClient {
+            http,
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+        }
+    }
+
+    pub async fn health(&self) -> anyhow::Result<HealthResponse> {
+        self.send(self.http.get(self.url("/health"))).await
+    }
+
+    /// OpenAPI document describing the API
+    pub async fn openapi_document(&self) -> anyhow::Result<Value> {
+        let response = self.http.get(self.url("/openapi.json")).send().await?;
+        Ok(response.error_for_status()?.json().await?)
+    }
+
+    /// Lists the live forks, only the ones with `label` (`key` or
+    /// `key=value`) if given
+    pub async fn list_forks(&self, label: Option<&str>) -> anyhow::Result<Vec<ForkSummary>> {
+        let query = ListForksQuery {
+            label: label.map(str::to_string),
+        };
+        self.send(self.http.get(self.url("/forks")).query(&query))
+            .await
+    }
+
+    /// Creates a fork. `options` is serialized as the body of `POST /forks`,
+    /// e.g. `json!({ "offline": true })`.
+    pub async fn create_fork(&self, options: &impl Serialize) -> anyhow::Result<Uuid> {
+        self.send(self.http.post(self.url("/forks")).json(options))
+            .await
+    }
+
+    pub async fn delete_fork(&self, fork_id: &Uuid) -> anyhow::Result<()> {
+        self.send::<String>(self.http.delete(self.url(&format!("/forks/{}", fork_id))))
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn delete_forks(&self, fork_ids: &[Uuid]) -> anyhow::Result<BatchDeleteResult> {
+        self.send(
+            self.http
+                .post(self.url("/forks/delete_batch"))
+                .json(fork_ids),
+        )
+        .await
+    }
+
+    /// A page of the accounts differing between two forks
+    pub async fn diff_forks(&self, req: &DiffForksRequest) -> anyhow::Result<ForkDiff> {
+        self.send(self.http.post(self.url("/forks/diff")).json(req))
+            .await
+    }
+
+    /// Executes a transaction, failing if it fails
+    pub async fn execute(
+        &self,
+        fork_id: &Uuid,
+        tx: &VersionedTransaction,
+    ) -> anyhow::Result<ExecuteResponse> {
+        self.execute_with(fork_id, &transaction_request(tx)?).await
+    }
+
+    /// Executes the transaction of a request, to override its timeout or
+    /// log limits
+    pub async fn execute_with(
+        &self,
+        fork_id: &Uuid,
+        req: &ExecuteRequest,
+    ) -> anyhow::Result<ExecuteResponse> {
+        self.post(fork_id, "execute", req).await
+    }
+
+    /// Simulates a transaction then executes it if the simulation succeeded
+    pub async fn send_transaction(
+        &self,
+        fork_id: &Uuid,
+        tx: &VersionedTransaction,
+    ) -> anyhow::Result<SendResult> {
+        self.send_transaction_with(fork_id, &transaction_request(tx)?)
+            .await
+    }
+
+    pub async fn send_transaction_with(
+        &self,
+        fork_id: &Uuid,
+        req: &ExecuteRequest,
+    ) -> anyhow::Result<SendResult> {
+        self.post(fork_id, "send", req).await
+    }
+
+    /// Simulates a transaction, failing if it fails
+    pub async fn simulate(
+        &self,
+        fork_id: &Uuid,
+        tx: &VersionedTransaction,
+    ) -> anyhow::Result<SimulateResponse> {
+        self.simulate_with(
+            fork_id,
+            &SimulateRequest {
+                tx_base64: encode_transaction(tx)?,
+                ..Default::default()
+            },
+        )
+        .await
+    }
+
+    /// Simulates the transaction of a request, e.g. to allow it unsigned
+    pub async fn simulate_with(
+        &self,
+        fork_id: &Uuid,
+        req: &SimulateRequest,
+    ) -> anyhow::Result<SimulateResponse> {
+        self.post(fork_id, "simulate", req).await
+    }
+
+    /// Simulates a transaction and matches its logs against a pattern
+    pub async fn simulate_and_match(
+        &self,
+        fork_id: &Uuid,
+        req: &SimulateMatchRequest,
+    ) -> anyhow::Result<LogMatch> {
+        self.post(fork_id, "simulate_and_match", req).await
+    }
+
+    /// Compute units a simulated transaction consumed against its budget
+    pub async fn simulate_compute(
+        &self,
+        fork_id: &Uuid,
+        req: &ComputeFitRequest,
+    ) -> anyhow::Result<ComputeFit> {
+        self.post(fork_id, "simulate_compute", req).await
+    }
+
+    pub async fn estimate_fee(
+        &self,
+        fork_id: &Uuid,
+        tx: &VersionedTransaction,
+    ) -> anyhow::Result<FeeEstimate> {
+        self.post(fork_id, "estimate_fee", &transaction_request(tx)?)
+            .await
+    }
+
+    /// Breaks a transaction down without executing it
+    pub async fn decode_transaction(
+        &self,
+        fork_id: &Uuid,
+        tx: &VersionedTransaction,
+    ) -> anyhow::Result<TransactionBreakdown> {
+        self.post(fork_id, "decode", &transaction_request(tx)?)
+            .await
+    }
+
+    /// Accounts a transaction reads or writes, lookup tables resolved
+    pub async fn touched_accounts(
+        &self,
+        fork_id: &Uuid,
+        tx: &VersionedTransaction,
+    ) -> anyhow::Result<Vec<TouchedAccount>> {
+        self.post(fork_id, "touched_accounts", &transaction_request(tx)?)
+            .await
+    }
+
+    pub async fn set_lamports(
+        &self,
+        fork_id: &Uuid,
+        pubkey: &Pubkey,
+        lamports: u64,
+    ) -> anyhow::Result<()> {
+        let req = SetLamportsRequest {
+            pubkey: pubkey.to_string(),
+            lamports,
+            allow_rent_paying: false,
+        };
+        self.post::<String>(fork_id, "set_lamports", &req)
+            .await
+            .map(|_| ())
+    }
+
+    /// Sets the balance of a token account, creating it and its mint when
+    /// missing
+    pub async fn set_token_balance(
+        &self,
+        fork_id: &Uuid,
+        req: &SetTokenBalanceRequest,
+    ) -> anyhow::Result<()> {
+        self.post::<String>(fork_id, "set_token_balance", req)
+            .await
+            .map(|_| ())
+    }
+
+    /// Sets the balances of several token accounts at once, returning
+    /// their addresses. `entries` is a list of token balances, each
+    /// addressed by its token account or by its owner's associated account.
+    pub async fn set_token_balances(
+        &self,
+        fork_id: &Uuid,
+        entries: &impl Serialize,
+    ) -> anyhow::Result<Vec<String>> {
+        self.post(fork_id, "set_token_balances", entries).await
+    }
+
+    pub async fn freeze_token(
+        &self,
+        fork_id: &Uuid,
+        req: &FreezeTokenRequest,
+    ) -> anyhow::Result<()> {
+        self.post::<String>(fork_id, "freeze_token", req)
+            .await
+            .map(|_| ())
+    }
+
+    /// Applies a declarative setup of the fork's state, atomically
+    pub async fn setup(
+        &self,
+        fork_id: &Uuid,
+        setup: &impl Serialize,
+    ) -> anyhow::Result<SetupResult> {
+        self.post(fork_id, "setup", setup).await
+    }
+
+    pub async fn set_token_delegate(
+        &self,
+        fork_id: &Uuid,
+        req: &SetTokenDelegateRequest,
+    ) -> anyhow::Result<()> {
+        self.post::<String>(fork_id, "set_token_delegate", req)
+            .await
+            .map(|_| ())
+    }
+
+    /// Creates a mint, returning its address
+    pub async fn create_mint(
+        &self,
+        fork_id: &Uuid,
+        req: &CreateMintRequest,
+    ) -> anyhow::Result<Pubkey> {
+        let mint: String = self.post(fork_id, "create_mint", req).await?;
+        Ok(mint.parse()?)
+    }
+
+    /// Creates an address lookup table, returning its address
+    pub async fn create_lookup_table(
+        &self,
+        fork_id: &Uuid,
+        req: &CreateLookupTableRequest,
+    ) -> anyhow::Result<Pubkey> {
+        let address: String = self.post(fork_id, "create_lookup_table", req).await?;
+        Ok(address.parse()?)
+    }
+
+    /// Appends addresses to a lookup table, returning all of its addresses
+    pub async fn extend_lookup_table(
+        &self,
+        fork_id: &Uuid,
+        req: &ExtendLookupTableRequest,
+    ) -> anyhow::Result<Vec<Pubkey>> {
+        let addresses: Vec<String> = self.post(fork_id, "extend_lookup_table", req).await?;
+        addresses
+            .iter()
+            .map(|address| Ok(address.parse()?))
+            .collect()
+    }
+
+    pub async fn find_pda(
+        &self,
+        fork_id: &Uuid,
+        req: &FindPdaRequest,
+    ) -> anyhow::Result<ProgramAddress> {
+        self.post(fork_id, "find_pda", req).await
+    }
+
+    pub async fn associated_token_address(
+        &self,
+        fork_id: &Uuid,
+        req: &AssociatedTokenAddressRequest,
+    ) -> anyhow::Result<ProgramAddress> {
+        self.post(fork_id, "associated_token_address", req).await
+    }
+
+    /// Reassigns an account to the `owner` program
+    pub async fn set_owner(
+        &self,
+        fork_id: &Uuid,
+        pubkey: &Pubkey,
+        owner: &Pubkey,
+    ) -> anyhow::Result<()> {
+        let req = SetOwnerRequest {
+            pubkey: pubkey.to_string(),
+            owner: owner.to_string(),
+        };
+        self.post::<String>(fork_id, "set_owner", &req)
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn set_executable(
+        &self,
+        fork_id: &Uuid,
+        pubkey: &Pubkey,
+        executable: bool,
+    ) -> anyhow::Result<()> {
+        let req = SetExecutableRequest {
+            pubkey: pubkey.to_string(),
+            executable,
+        };
+        self.post::<String>(fork_id, "set_executable", &req)
+            .await
+            .map(|_| ())
+    }
+
+    /// Blockhash to sign the fork's transactions with, and the fork's slot
+    pub async fn get_blockhash(&self, fork_id: &Uuid) -> anyhow::Result<(Hash, u64)> {
+        let response: BlockhashResponse = self.get(fork_id, "blockhash").await?;
+        Ok((response.blockhash.parse()?, response.slot))
+    }
+
+    pub async fn set_blockhash(&self, fork_id: &Uuid, blockhash: &Hash) -> anyhow::Result<()> {
+        let req = SetBlockhashRequest {
+            blockhash: blockhash.to_string(),
+        };
+        self.post::<String>(fork_id, "set_blockhash", &req)
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn get_stats(&self, fork_id: &Uuid) -> anyhow::Result<ForkStats> {
+        self.get(fork_id, "stats").await
+    }
+
+    pub async fn get_fees(&self, fork_id: &Uuid) -> anyhow::Result<FeeSchedule> {
+        self.get(fork_id, "fees").await
+    }
+
+    pub async fn get_features(&self, fork_id: &Uuid) -> anyhow::Result<Vec<ActiveFeature>> {
+        self.get(fork_id, "features").await
+    }
+
+    /// Exports the accounts of a fork, as a list of `{ pubkey, lamports,
+    /// owner, data_base64, executable, rent_epoch }` objects which
+    /// [`import_accounts`](Self::import_accounts) takes back
+    pub async fn export_accounts(&self, fork_id: &Uuid) -> anyhow::Result<Vec<Value>> {
+        let response = self
+            .http
+            .get(self.fork_url(fork_id, "export"))
+            .send()
+            .await?;
+        // Accounts come as a bare array, only errors in an `ApiResponse`
+        match response.json::<Value>().await? {
+            Value::Array(accounts) => Ok(accounts),
+            error => {
+                serde_json::from_value::<ApiResponse<()>>(error)?.into_result()?;
+                anyhow::bail!("unexpected export response")
+            }
+        }
+    }
+
+    /// Imports exported accounts, returning the number imported
+    pub async fn import_accounts(
+        &self,
+        fork_id: &Uuid,
+        accounts: &impl Serialize,
+    ) -> anyhow::Result<usize> {
+        self.post(fork_id, "import", accounts).await
+    }
+
+    /// Replays transactions on a new fork, the fork's executed
+    /// transactions by default
+    pub async fn replay_history(
+        &self,
+        fork_id: &Uuid,
+        req: &ReplayHistoryRequest,
+    ) -> anyhow::Result<ReplayResult> {
+        self.post(fork_id, "replay_history", req).await
+    }
+
+    pub async fn set_rent(&self, fork_id: &Uuid, req: &SetRentRequest) -> anyhow::Result<()> {
+        self.post::<String>(fork_id, "rent", req).await.map(|_| ())
+    }
+
+    /// Warps the fork to the first slot of `epoch`
+    pub async fn set_epoch(&self, fork_id: &Uuid, epoch: u64) -> anyhow::Result<EpochInfo> {
+        self.post(fork_id, "set_epoch", &SetEpochRequest { epoch })
+            .await
+    }
+
+    /// Most recent entries of the `SlotHashes` sysvar, 32 by default
+    pub async fn get_slot_hashes(
+        &self,
+        fork_id: &Uuid,
+        limit: Option<usize>,
+    ) -> anyhow::Result<Vec<SlotHashEntry>> {
+        self.get_with_query(fork_id, "slot_hashes", &SlotHashesQuery { limit })
+            .await
+    }
+
+    /// Rent-exempt minimum balance of an account of `data_len` bytes
+    pub async fn minimum_balance(&self, fork_id: &Uuid, data_len: usize) -> anyhow::Result<u64> {
+        self.get_with_query(
+            fork_id,
+            "minimum_balance",
+            &MinimumBalanceQuery { data_len },
+        )
+        .await
+    }
+
+    pub async fn token_account_minimum_balance(&self, fork_id: &Uuid) -> anyhow::Result<u64> {
+        self.get(fork_id, "minimum_balance/token_account").await
+    }
+
+    pub async fn mint_minimum_balance(&self, fork_id: &Uuid) -> anyhow::Result<u64> {
+        self.get(fork_id, "minimum_balance/mint").await
+    }
+
+    pub async fn get_account(
+        &self,
+        fork_id: &Uuid,
+        req: &GetAccountRequest,
+        query: &AccountReadQuery,
+    ) -> anyhow::Result<UiAccount> {
+        let url = self.fork_url(fork_id, "get_account");
+        self.send(self.http.post(url).query(query).json(req)).await
+    }
+
+    pub async fn account_exists(
+        &self,
+        fork_id: &Uuid,
+        pubkey: &Pubkey,
+        local_only: bool,
+    ) -> anyhow::Result<bool> {
+        self.get_with_query(
+            fork_id,
+            &format!("accounts/{}/exists", pubkey),
+            &AccountExistsQuery { local_only },
+        )
+        .await
+    }
+
+    pub async fn get_token_balance(
+        &self,
+        fork_id: &Uuid,
+        token_account: &Pubkey,
+    ) -> anyhow::Result<TokenBalance> {
+        self.get(
+            fork_id,
+            &format!("accounts/{}/token_balance", token_account),
+        )
+        .await
+    }
+
+    /// Sends a JSON-RPC request (or batch) to the fork, returning the
+    /// JSON-RPC response as is
+    pub async fn rpc(&self, fork_id: &Uuid, request: &impl Serialize) -> anyhow::Result<Value> {
+        let response = self
+            .http
+            .post(self.fork_url(fork_id, "rpc"))
+            .json(request)
+            .send()
+            .await?;
+        Ok(response.json().await?)
+    }
+
+    /// Accounts of the fork owned by a program. `req` is a `{ program_id,
+    /// filters, data_slice }` object.
+    pub async fn program_accounts(
+        &self,
+        fork_id: &Uuid,
+        req: &impl Serialize,
+    ) -> anyhow::Result<Vec<ProgramAccount>> {
+        self.post(fork_id, "program_accounts", req).await
+    }
+
+    /// Clones a program and its accounts from mainnet. `req` is a
+    /// `{ program_id, filters, limit }` object.
+    pub async fn clone_program_accounts(
+        &self,
+        fork_id: &Uuid,
+        req: &impl Serialize,
+    ) -> anyhow::Result<ClonedProgram> {
+        self.post(fork_id, "clone_program_accounts", req).await
+    }
+
+    /// A page of the executed transactions. `query` holds the filters and
+    /// pagination, e.g. `json!({ "success": false, "limit": 10 })`.
+    pub async fn get_executed_transactions(
+        &self,
+        fork_id: &Uuid,
+        query: &impl Serialize,
+    ) -> anyhow::Result<TransactionPage> {
+        self.post_with_query(fork_id, "get_executed_transactions", query)
+            .await
+    }
+
+    /// A page of the simulated transactions, see
+    /// [`get_executed_transactions`](Self::get_executed_transactions)
+    pub async fn get_simulated_transactions(
+        &self,
+        fork_id: &Uuid,
+        query: &impl Serialize,
+    ) -> anyhow::Result<TransactionPage> {
+        self.post_with_query(fork_id, "get_simulated_transactions", query)
+            .await
+    }
+
+    /// A recorded transaction by signature, also searching the simulated
+    /// ones if `simulated`
+    pub async fn get_transaction(
+        &self,
+        fork_id: &Uuid,
+        signature: &str,
+        simulated: bool,
+    ) -> anyhow::Result<TransactionRecord> {
+        self.get_with_query(
+            fork_id,
+            &format!("transactions/{}", signature),
+            &TransactionLookupQuery { simulated },
+        )
+        .await
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    fn fork_url(&self, fork_id: &Uuid, route: &str) -> String {
+        self.url(&format!("/forks/{}/{}", fork_id, route))
+    }
+
+    /// Gets a route of a fork
+    async fn get<T: DeserializeOwned>(&self, fork_id: &Uuid, route: &str) -> anyhow::Result<T> {
+        self.send(self.http.get(self.fork_url(fork_id, route)))
+            .await
+    }
+
+    async fn get_with_query<T: DeserializeOwned>(
+        &self,
+        fork_id: &Uuid,
+        route: &str,
+        query: &impl Serialize,
+    ) -> anyhow::Result<T> {
+        self.send(self.http.get(self.fork_url(fork_id, route)).query(query))
+            .await
+    }
+
+    /// Posts `body` to a route of a fork
+    async fn post<T: DeserializeOwned>(
+        &self,
+        fork_id: &Uuid,
+        route: &str,
+        body: &impl Serialize,
+    ) -> anyhow::Result<T> {
+        self.send(self.http.post(self.fork_url(fork_id, route)).json(body))
+            .await
+    }
+
+    /// Posts to a route of a fork taking its parameters in the query string
+    async fn post_with_query<T: DeserializeOwned>(
+        &self,
+        fork_id: &Uuid,
+        route: &str,
+        query: &impl Serialize,
+    ) -> anyhow::Result<T> {
+        self.send(self.http.post(self.fork_url(fork_id, route)).query(query))
+            .await
+    }
+
+    /// Sends a request and unwraps its [`ApiResponse`]. Validation errors
+    /// come with a 4xx status but still in an [`ApiResponse`], so the
+    /// status isn't checked.
+    async fn send<T: DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> anyhow::Result<T> {
+        let response = request.send().await?;
+        let status = response.status();
+        response
+            .json::<ApiResponse<T>>()
+            .await
+            .with_context(|| format!("unexpected response with status {}", status))?
+            .into_result()
+    }
+}
+
+/// Encodes a transaction the way the API expects it, base64 of bincode
+pub fn encode_transaction(tx: &VersionedTransaction) -> anyhow::Result<String> {
+    Ok(engine::general_purpose::STANDARD.encode(bincode::serialize(tx)?))
+}
+
+/// Request of the routes taking nothing but a transaction
+fn transaction_request(tx: &VersionedTransaction) -> anyhow::Result<ExecuteRequest> {
+    Ok(ExecuteRequest {
+        tx_base64: encode_transaction(tx)?,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::{
+        Json, Router,
+        body::Bytes,
+        extract::State,
+        http::{Method, Uri},
+    };
+    use litesvm::types::TransactionMetadata;
+    use serde_json::json;
+    use solana_sdk::{
+        message::Message,
+        signature::{Keypair, Signer},
+        transaction::Transaction,
+    };
+    use solana_system_interface::instruction as system_instruction;
+
+    use super::*;
+    use crate::types::ReturnData;
+
+    /// Request received by the mock server: method, path and query, body
+    type Received = (String, String, Value);
+
+    /// Serves canned responses by path, recording the requests. Returns the
+    /// URL of the server.
+    async fn mock_server(
+        responses: Vec<(String, Value)>,
+        received: Arc<Mutex<Vec<Received>>>,
+    ) -> String {
+        let app = Router::new()
+            .fallback(
+                |State(received): State<Arc<Mutex<Vec<Received>>>>,
+                 method: Method,
+                 uri: Uri,
+                 body: Bytes| async move {
+                    let body = serde_json::from_slice(&body).unwrap_or(Value::Null);
+                    received
+                        .lock()
+                        .unwrap()
+                        .push((method.to_string(), uri.to_string(), body));
+                    let response = responses
+                        .iter()
+                        .find(|(path, _)| *path == uri.path())
+                        .map(|(_, response)| response.clone())
+                        .unwrap_or_else(
+                            || json!({ "success": false, "data": null, "error": "Fork not found" }),
+                        );
+                    Json(response)
+                },
+            )
+            .with_state(received);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", address)
+    }
+
+    fn ok(data: impl Serialize) -> Value {
+        json!({ "success": true, "data": data, "error": null })
+    }
+
+    fn transfer_tx() -> VersionedTransaction {
+        let payer = Keypair::new();
+        let ix = system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1_000);
+        let message = Message::new(&[ix], Some(&payer.pubkey()));
+        Transaction::new(&[&payer], message, Hash::new_unique()).into()
+    }
+
+    #[test]
+    fn test_url() {
+        let client = ForkClient::new("http://localhost:8080/");
+        let fork_id = Uuid::new_v4();
+        assert_eq!(
+            client.fork_url(&fork_id, "execute"),
+            format!("http://localhost:8080/forks/{}/execute", fork_id)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mock_server() {
+        let fork_id = Uuid::new_v4();
+        let route = |route: &str| format!("/forks/{}/{}", fork_id, route);
+        let program_id = Pubkey::new_unique();
+        let executed = ExecuteResponse {
+            meta: TransactionMetadata::default(),
+            program_return: Some(ReturnData {
+                program_id: program_id.to_string(),
+                data_base64: "AQID".to_string(),
+            }),
+            compute_units: None,
+        };
+        let exported = json!([{
+            "pubkey": Pubkey::new_unique().to_string(),
+            "lamports": 1_000,
+            "owner": Pubkey::default().to_string(),
+            "data_base64": "",
+            "executable": false,
+            "rent_epoch": 0,
+        }]);
+        let responses = vec![
+            ("/forks".to_string(), ok(fork_id)),
+            (route("execute"), ok(&executed)),
+            (route("set_lamports"), ok("Set lamports")),
+            (route("minimum_balance"), ok(890_880)),
+            (route("export"), exported.clone()),
+            (
+                route("rpc"),
+                json!({ "jsonrpc": "2.0", "id": 1, "result": 42 }),
+            ),
+        ];
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let client = ForkClient::new(mock_server(responses, Arc::clone(&received)).await);
+
+        assert_eq!(
+            client
+                .create_fork(&json!({ "offline": true }))
+                .await
+                .unwrap(),
+            fork_id
+        );
+
+        let tx = transfer_tx();
+        let response = client.execute(&fork_id, &tx).await.unwrap();
+        assert_eq!(response.program_return, executed.program_return);
+        assert_eq!(response.meta.signature, executed.meta.signature);
+
+        let pubkey = Pubkey::new_unique();
+        client.set_lamports(&fork_id, &pubkey, 5_000).await.unwrap();
+        assert_eq!(
+            client.minimum_balance(&fork_id, 165).await.unwrap(),
+            890_880
+        );
+        assert_eq!(
+            client.export_accounts(&fork_id).await.unwrap(),
+            exported.as_array().unwrap().clone()
+        );
+        let rpc = json!({ "jsonrpc": "2.0", "id": 1, "method": "getSlot" });
+        assert_eq!(client.rpc(&fork_id, &rpc).await.unwrap()["result"], 42);
+
+        // Errors of the server are returned as is, export ones included
+        let missing = Uuid::new_v4();
+        let err = client.get_stats(&missing).await.unwrap_err();
+        assert_eq!(err.to_string(), "Fork not found");
+        let err = client.export_accounts(&missing).await.unwrap_err();
+        assert_eq!(err.to_string(), "Fork not found");
+
+        let received = received.lock().unwrap();
+        assert_eq!(
+            received[0],
+            (
+                "POST".to_string(),
+                "/forks".to_string(),
+                json!({ "offline": true })
+            )
+        );
+        assert_eq!(
+            received[1],
+            (
+                "POST".to_string(),
+                route("execute"),
+                serde_json::to_value(transaction_request(&tx).unwrap()).unwrap()
+            )
+        );
+        assert_eq!(
+            received[2].2,
+            json!({ "pubkey": pubkey.to_string(), "lamports": 5_000, "allow_rent_paying": false })
+        );
+        assert_eq!(
+            received[3],
+            (
+                "GET".to_string(),
+                format!("{}?data_len=165", route("minimum_balance")),
+                Value::Null
+            )
+        );
+        assert_eq!(received[4].1, route("export"));
+        assert_eq!(received[5].2, rpc);
+    }
+}
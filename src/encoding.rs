@@ -0,0 +1,201 @@
+use base64::{Engine, engine};
+use serde_json::json;
+use simulation_engine::types::DataSlice;
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use spl_token::solana_program::{program_option::COption, program_pack::Pack};
+use spl_token::{
+    ID,
+    state::{Account as TokenAccount, AccountState, Mint},
+};
+
+pub use simulation_engine::types::{AccountEncoding, ParsedAccount, UiAccount, UiAccountData};
+
+/// Encodes an account for the HTTP API. Data of accounts which can't be
+/// parsed with `JsonParsed` falls back to base64.
+pub fn encode_account(account: Account, encoding: AccountEncoding) -> UiAccount {
+    let parsed = match encoding {
+        AccountEncoding::JsonParsed => parse_account_data(&account),
+        AccountEncoding::Base64 => None,
+    };
+    let data = match parsed {
+        Some(parsed) => UiAccountData::Json(parsed),
+        None => UiAccountData::Binary(
+            engine::general_purpose::STANDARD.encode(&account.data),
+            AccountEncoding::Base64,
+        ),
+    };
+
+    UiAccount {
+        lamports: account.lamports,
+        owner: account.owner.to_string(),
+        data,
+        executable: account.executable,
+        rent_epoch: account.rent_epoch,
+    }
+}
+
+/// Keeps only a slice of the data of an account, failing if it lies past
+/// the end of the data. Sliced token accounts and mints no longer parse, so
+/// their data is returned as base64.
+pub fn slice_account_data(account: &mut Account, slice: DataSlice) -> anyhow::Result<()> {
+    let len = account.data.len();
+    let Some(end) = slice
+        .offset
+        .checked_add(slice.length)
+        .filter(|end| *end <= len)
+    else {
+        anyhow::bail!(
+            "Data slice at offset {} of length {} exceeds the data length {}",
+            slice.offset,
+            slice.length,
+            len
+        );
+    };
+    account.data.truncate(end);
+    account.data.drain(..slice.offset);
+    Ok(())
+}
+
+/// Parses the data of SPL token accounts and mints
+fn parse_account_data(account: &Account) -> Option<ParsedAccount> {
+    if account.owner != Pubkey::new_from_array(*ID.as_array()) {
+        return None;
+    }
+
+    let parsed = match account.data.len() {
+        TokenAccount::LEN => {
+            let token_acc = TokenAccount::unpack(&account.data).ok()?;
+            json!({
+                "type": "account",
+                "info": {
+                    "mint": token_acc.mint.to_string(),
+                    "owner": token_acc.owner.to_string(),
+                    "amount": token_acc.amount.to_string(),
+                    "delegate": coption_to_string(token_acc.delegate),
+                    "delegatedAmount": token_acc.delegated_amount.to_string(),
+                    "state": match token_acc.state {
+                        AccountState::Uninitialized => "uninitialized",
+                        AccountState::Initialized => "initialized",
+                        AccountState::Frozen => "frozen",
+                    },
+                    "isNative": token_acc.is_native.is_some(),
+                    "closeAuthority": coption_to_string(token_acc.close_authority),
+                }
+            })
+        }
+        Mint::LEN => {
+            let mint = Mint::unpack(&account.data).ok()?;
+            json!({
+                "type": "mint",
+                "info": {
+                    "mintAuthority": coption_to_string(mint.mint_authority),
+                    "supply": mint.supply.to_string(),
+                    "decimals": mint.decimals,
+                    "isInitialized": mint.is_initialized,
+                    "freezeAuthority": coption_to_string(mint.freeze_authority),
+                }
+            })
+        }
+        _ => return None,
+    };
+
+    Some(ParsedAccount {
+        program: "spl-token".to_string(),
+        parsed,
+        space: account.data.len(),
+    })
+}
+
+fn coption_to_string<T: ToString>(value: COption<T>) -> Option<String> {
+    match value {
+        COption::Some(v) => Some(v.to_string()),
+        COption::None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spl_token::solana_program::pubkey;
+
+    fn token_program() -> Pubkey {
+        Pubkey::new_from_array(*ID.as_array())
+    }
+
+    #[test]
+    fn test_base64_encoding() {
+        let account = Account::new(10, 3, &Pubkey::new_unique());
+        let ui = encode_account(account, AccountEncoding::Base64);
+
+        let value = serde_json::to_value(&ui).unwrap();
+        assert_eq!(value["data"], json!(["AAAA", "base64"]));
+        assert_eq!(value["lamports"], 10);
+    }
+
+    #[test]
+    fn test_slice_account_data() {
+        let mint = Mint {
+            supply: 1_000,
+            decimals: 6,
+            is_initialized: true,
+            ..Default::default()
+        };
+        let mut account = Account::new(10, Mint::LEN, &token_program());
+        mint.pack_into_slice(&mut account.data);
+
+        // The supply is stored after the mint authority
+        let slice = DataSlice {
+            offset: 36,
+            length: 8,
+        };
+        let mut sliced = account.clone();
+        slice_account_data(&mut sliced, slice).unwrap();
+        assert_eq!(sliced.data, 1_000u64.to_le_bytes());
+        let ui = encode_account(sliced, AccountEncoding::JsonParsed);
+        assert!(matches!(ui.data, UiAccountData::Binary(..)));
+
+        let mut empty = account.clone();
+        let end = DataSlice {
+            offset: Mint::LEN,
+            length: 0,
+        };
+        slice_account_data(&mut empty, end).unwrap();
+        assert!(empty.data.is_empty());
+
+        for (offset, length) in [(Mint::LEN, 1), (80, 8), (usize::MAX, 1)] {
+            let slice = DataSlice { offset, length };
+            assert!(slice_account_data(&mut account.clone(), slice).is_err());
+        }
+    }
+
+    #[test]
+    fn test_json_parsed_token_account() {
+        let mint = pubkey::Pubkey::new_unique();
+        let token_acc = TokenAccount {
+            mint,
+            amount: 5,
+            state: AccountState::Initialized,
+            ..Default::default()
+        };
+
+        let mut account = Account::new(1, TokenAccount::LEN, &token_program());
+        token_acc.pack_into_slice(&mut account.data);
+
+        let value =
+            serde_json::to_value(encode_account(account, AccountEncoding::JsonParsed)).unwrap();
+        assert_eq!(value["data"]["program"], "spl-token");
+        assert_eq!(value["data"]["parsed"]["type"], "account");
+        assert_eq!(value["data"]["parsed"]["info"]["mint"], mint.to_string());
+        assert_eq!(value["data"]["parsed"]["info"]["amount"], "5");
+    }
+
+    #[test]
+    fn test_json_parsed_falls_back_to_base64() {
+        let account = Account::new(1, 8, &Pubkey::new_unique());
+        let ui = encode_account(account, AccountEncoding::JsonParsed);
+        assert!(matches!(
+            ui.data,
+            UiAccountData::Binary(_, AccountEncoding::Base64)
+        ));
+    }
+}
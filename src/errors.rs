@@ -0,0 +1,152 @@
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    instruction::InstructionError, message::VersionedMessage, pubkey::Pubkey,
+    transaction::TransactionError,
+};
+
+/// Names of the custom errors of the system program, by code
+const SYSTEM_ERRORS: &[&str] = &[
+    "AccountAlreadyInUse",
+    "ResultWithNegativeLamports",
+    "InvalidProgramId",
+    "InvalidAccountDataLength",
+    "MaxSeedLengthExceeded",
+    "AddressWithSeedMismatch",
+    "NonceNoRecentBlockhashes",
+    "NonceBlockhashNotExpired",
+    "NonceUnexpectedBlockhashValue",
+];
+
+/// Names of the custom errors of the SPL token program, by code
+const TOKEN_ERRORS: &[&str] = &[
+    "NotRentExempt",
+    "InsufficientFunds",
+    "InvalidMint",
+    "MintMismatch",
+    "OwnerMismatch",
+    "FixedSupply",
+    "AlreadyInUse",
+    "InvalidNumberOfProvidedSigners",
+    "InvalidNumberOfRequiredSigners",
+    "UninitializedState",
+    "NativeNotSupported",
+    "NonNativeHasBalance",
+    "InvalidInstruction",
+    "InvalidState",
+    "Overflow",
+    "AuthorityTypeNotSupported",
+    "MintCannotFreeze",
+    "AccountFrozen",
+    "MintDecimalsMismatch",
+    "NonNativeNotSupported",
+];
+
+/// Why a transaction failed, locating the failing instruction
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct TransactionFailure {
+    pub error: TransactionError,
+    /// Index of the failing instruction, for instruction errors
+    pub instruction_index: Option<u8>,
+    /// Program invoked by the failing instruction
+    pub program_id: Option<String>,
+    /// Code of a program specific (`Custom`) instruction error
+    pub custom_code: Option<u32>,
+    /// Name of the custom error, for the system and SPL token programs
+    pub custom_name: Option<String>,
+}
+
+/// Describes the failure of a transaction with the given message
+pub fn describe_failure(
+    message: &VersionedMessage,
+    error: &TransactionError,
+) -> TransactionFailure {
+    let mut failure = TransactionFailure {
+        error: error.clone(),
+        instruction_index: None,
+        program_id: None,
+        custom_code: None,
+        custom_name: None,
+    };
+    let TransactionError::InstructionError(index, instruction_error) = error else {
+        return failure;
+    };
+
+    failure.instruction_index = Some(*index);
+    // Program ids are always static keys, even in v0 messages
+    let program_id = message.instructions().get(*index as usize).and_then(|ix| {
+        message
+            .static_account_keys()
+            .get(ix.program_id_index as usize)
+    });
+    failure.program_id = program_id.map(|id| id.to_string());
+    if let InstructionError::Custom(code) = instruction_error {
+        failure.custom_code = Some(*code);
+        failure.custom_name = program_id
+            .and_then(|id| custom_error_name(id, *code))
+            .map(str::to_string);
+    }
+    failure
+}
+
+/// Name of a custom error code of a known program
+fn custom_error_name(program_id: &Pubkey, code: u32) -> Option<&'static str> {
+    let names = if *program_id == solana_system_interface::program::ID {
+        SYSTEM_ERRORS
+    } else if program_id.to_bytes() == spl_token::ID.to_bytes() {
+        TOKEN_ERRORS
+    } else {
+        return None;
+    };
+    names.get(code as usize).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{
+        hash::Hash,
+        instruction::{AccountMeta, Instruction},
+        message::Message,
+    };
+
+    #[test]
+    fn test_describe_failure() {
+        let payer = Pubkey::new_unique();
+        let token_program = Pubkey::new_from_array(spl_token::ID.to_bytes());
+        let other_program = Pubkey::new_unique();
+        let message = VersionedMessage::Legacy(Message::new_with_blockhash(
+            &[
+                Instruction::new_with_bytes(other_program, &[], vec![]),
+                Instruction::new_with_bytes(
+                    token_program,
+                    &[],
+                    vec![AccountMeta::new(payer, true)],
+                ),
+            ],
+            Some(&payer),
+            &Hash::default(),
+        ));
+
+        let failure = describe_failure(
+            &message,
+            &TransactionError::InstructionError(1, InstructionError::Custom(1)),
+        );
+        assert_eq!(failure.instruction_index, Some(1));
+        assert_eq!(failure.program_id, Some(token_program.to_string()));
+        assert_eq!(failure.custom_code, Some(1));
+        assert_eq!(failure.custom_name.as_deref(), Some("InsufficientFunds"));
+
+        // Unknown programs only get their code
+        let failure = describe_failure(
+            &message,
+            &TransactionError::InstructionError(0, InstructionError::Custom(6000)),
+        );
+        assert_eq!(failure.program_id, Some(other_program.to_string()));
+        assert_eq!(failure.custom_code, Some(6000));
+        assert_eq!(failure.custom_name, None);
+
+        let failure = describe_failure(&message, &TransactionError::AccountNotFound);
+        assert_eq!(failure.instruction_index, None);
+        assert_eq!(failure.program_id, None);
+    }
+}
@@ -0,0 +1,271 @@
+use agave_feature_set::{
+    FeatureSet, enable_secp256r1_precompile, migrate_stake_program_to_core_bpf,
+};
+use solana_sdk::{message::VersionedMessage, pubkey::Pubkey};
+
+pub use simulation_engine::types::{FeeEstimate, FeeSchedule};
+
+/// Fee charged per signature by the fork runtime (LiteSVM's default fee structure)
+pub const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+/// Compute units allotted to each instruction when no limit is requested
+pub const DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT: u32 = 200_000;
+/// Compute units allotted to each builtin instruction when no limit is
+/// requested
+pub const MAX_BUILTIN_ALLOCATION_COMPUTE_UNIT_LIMIT: u32 = 3_000;
+/// Maximum compute units a transaction can request
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+const COMPUTE_BUDGET_PROGRAM_ID: Pubkey =
+    Pubkey::from_str_const("ComputeBudget111111111111111111111111111111");
+const ED25519_PROGRAM_ID: Pubkey =
+    Pubkey::from_str_const("Ed25519SigVerify111111111111111111111111111");
+const SECP256K1_PROGRAM_ID: Pubkey =
+    Pubkey::from_str_const("KeccakSecp256k11111111111111111111111111111");
+const SECP256R1_PROGRAM_ID: Pubkey =
+    Pubkey::from_str_const("Secp256r1SigVerify1111111111111111111111111");
+/// Builtin until `migrate_stake_program_to_core_bpf` is active
+const STAKE_PROGRAM_ID: Pubkey =
+    Pubkey::from_str_const("Stake11111111111111111111111111111111111111");
+const BUILTIN_PROGRAM_IDS: [Pubkey; 9] = [
+    Pubkey::from_str_const("11111111111111111111111111111111"),
+    Pubkey::from_str_const("Vote111111111111111111111111111111111111111"),
+    COMPUTE_BUDGET_PROGRAM_ID,
+    Pubkey::from_str_const("BPFLoaderUpgradeab1e11111111111111111111111"),
+    Pubkey::from_str_const("BPFLoader1111111111111111111111111111111111"),
+    Pubkey::from_str_const("BPFLoader2111111111111111111111111111111111"),
+    Pubkey::from_str_const("LoaderV411111111111111111111111111111111111"),
+    SECP256K1_PROGRAM_ID,
+    ED25519_PROGRAM_ID,
+];
+const MICRO_LAMPORTS_PER_LAMPORT: u128 = 1_000_000;
+
+/// Schedule of the fork runtime, charging fees or not
+pub fn fee_schedule(fees_charged: bool) -> FeeSchedule {
+    FeeSchedule {
+        lamports_per_signature: LAMPORTS_PER_SIGNATURE,
+        default_instruction_compute_unit_limit: DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT,
+        builtin_instruction_compute_unit_limit: MAX_BUILTIN_ALLOCATION_COMPUTE_UNIT_LIMIT,
+        max_compute_unit_limit: MAX_COMPUTE_UNIT_LIMIT,
+        micro_lamports_per_lamport: MICRO_LAMPORTS_PER_LAMPORT as u64,
+        fees_charged,
+    }
+}
+
+/// Computes the fee of a message without executing it with the fee rate of
+/// `schedule`, taking the compute budget instructions and precompile
+/// signatures it contains into account
+pub fn estimate_fee(
+    message: &VersionedMessage,
+    schedule: &FeeSchedule,
+    feature_set: &FeatureSet,
+) -> FeeEstimate {
+    let compute_unit_price = requested_compute_unit_price(message);
+    let compute_unit_limit = compute_unit_limit(message, schedule, feature_set);
+    let base_fee =
+        signature_count(message, feature_set).saturating_mul(schedule.lamports_per_signature);
+    let prioritization_fee = (compute_unit_price as u128 * compute_unit_limit as u128)
+        .div_ceil(MICRO_LAMPORTS_PER_LAMPORT) as u64;
+
+    FeeEstimate {
+        base_fee,
+        prioritization_fee,
+        compute_unit_limit,
+        compute_unit_price,
+        total_fee: base_fee.saturating_add(prioritization_fee),
+    }
+}
+
+/// Compute unit limit of a message: the limit it requests, or else the
+/// default of each of its instructions, which is lower for builtin programs
+pub fn compute_unit_limit(
+    message: &VersionedMessage,
+    schedule: &FeeSchedule,
+    feature_set: &FeatureSet,
+) -> u32 {
+    let account_keys = message.static_account_keys();
+    let requested =
+        compute_budget_instructions(message).find_map(|data| match data.split_first() {
+            Some((2, rest)) if rest.len() >= 4 => {
+                Some(u32::from_le_bytes(rest[..4].try_into().unwrap()))
+            }
+            _ => None,
+        });
+    let limit = requested.unwrap_or_else(|| {
+        message
+            .instructions()
+            .iter()
+            .map(|ix| match account_keys.get(ix.program_id_index as usize) {
+                Some(program_id) if is_builtin(program_id, feature_set) => {
+                    schedule.builtin_instruction_compute_unit_limit
+                }
+                _ => schedule.default_instruction_compute_unit_limit,
+            })
+            .fold(0u32, u32::saturating_add)
+    });
+    limit.min(schedule.max_compute_unit_limit)
+}
+
+/// Helper function returning the compute unit price requested by a message
+fn requested_compute_unit_price(message: &VersionedMessage) -> u64 {
+    compute_budget_instructions(message)
+        .find_map(|data| match data.split_first() {
+            Some((3, rest)) if rest.len() >= 8 => {
+                Some(u64::from_le_bytes(rest[..8].try_into().unwrap()))
+            }
+            _ => None,
+        })
+        .unwrap_or(0)
+}
+
+/// Helper function iterating over the data of the compute budget
+/// instructions of a message
+fn compute_budget_instructions(message: &VersionedMessage) -> impl Iterator<Item = &[u8]> {
+    let account_keys = message.static_account_keys();
+    message
+        .instructions()
+        .iter()
+        .filter(|ix| {
+            account_keys.get(ix.program_id_index as usize) == Some(&COMPUTE_BUDGET_PROGRAM_ID)
+        })
+        .map(|ix| ix.data.as_slice())
+}
+
+/// Helper function counting the signatures a message is charged for: its
+/// own and the ones verified by precompiles, each declaring its count in
+/// the first byte of its data
+fn signature_count(message: &VersionedMessage, feature_set: &FeatureSet) -> u64 {
+    let account_keys = message.static_account_keys();
+    let precompile_signatures: u64 = message
+        .instructions()
+        .iter()
+        .filter(|ix| {
+            account_keys
+                .get(ix.program_id_index as usize)
+                .is_some_and(|program_id| {
+                    *program_id == ED25519_PROGRAM_ID
+                        || *program_id == SECP256K1_PROGRAM_ID
+                        || (*program_id == SECP256R1_PROGRAM_ID
+                            && feature_set.is_active(&enable_secp256r1_precompile::id()))
+                })
+        })
+        .map(|ix| u64::from(ix.data.first().copied().unwrap_or(0)))
+        .sum();
+    u64::from(message.header().num_required_signatures).saturating_add(precompile_signatures)
+}
+
+/// Helper function telling whether a program is a builtin of the runtime
+fn is_builtin(program_id: &Pubkey, feature_set: &FeatureSet) -> bool {
+    BUILTIN_PROGRAM_IDS.contains(program_id)
+        || (*program_id == STAKE_PROGRAM_ID
+            && !feature_set.is_active(&migrate_stake_program_to_core_bpf::id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{instruction::Instruction, message::Message};
+    use solana_system_interface::instruction as system_instruction;
+
+    fn message(instructions: &[Instruction]) -> VersionedMessage {
+        let payer = Pubkey::new_unique();
+        let mut instructions = instructions.to_vec();
+        instructions.push(system_instruction::transfer(
+            &payer,
+            &Pubkey::new_unique(),
+            1,
+        ));
+        VersionedMessage::Legacy(Message::new(&instructions, Some(&payer)))
+    }
+
+    fn estimate(instructions: &[Instruction]) -> FeeEstimate {
+        estimate_fee(
+            &message(instructions),
+            &fee_schedule(true),
+            &FeatureSet::all_enabled(),
+        )
+    }
+
+    #[test]
+    fn test_base_fee_only() {
+        let fee = estimate(&[]);
+        assert_eq!(fee.base_fee, LAMPORTS_PER_SIGNATURE);
+        assert_eq!(fee.prioritization_fee, 0);
+        // The transfer is a builtin instruction
+        assert_eq!(
+            fee.compute_unit_limit,
+            MAX_BUILTIN_ALLOCATION_COMPUTE_UNIT_LIMIT
+        );
+        assert_eq!(fee.total_fee, LAMPORTS_PER_SIGNATURE);
+    }
+
+    #[test]
+    fn test_prioritization_fee() {
+        let mut limit = vec![2];
+        limit.extend_from_slice(&300_000u32.to_le_bytes());
+        let mut price = vec![3];
+        price.extend_from_slice(&10_000u64.to_le_bytes());
+
+        let fee = estimate(&[
+            Instruction::new_with_bytes(COMPUTE_BUDGET_PROGRAM_ID, &limit, vec![]),
+            Instruction::new_with_bytes(COMPUTE_BUDGET_PROGRAM_ID, &price, vec![]),
+        ]);
+        assert_eq!(fee.compute_unit_limit, 300_000);
+        assert_eq!(fee.compute_unit_price, 10_000);
+        // 300_000 CU * 10_000 micro-lamports = 3_000 lamports
+        assert_eq!(fee.prioritization_fee, 3_000);
+        assert_eq!(fee.total_fee, LAMPORTS_PER_SIGNATURE + 3_000);
+    }
+
+    #[test]
+    fn test_default_compute_unit_limit() {
+        let mut price = vec![3];
+        price.extend_from_slice(&1_000_000u64.to_le_bytes());
+
+        // Two programs, the price instruction and the transfer
+        let fee = estimate(&[
+            Instruction::new_with_bytes(Pubkey::new_unique(), &[], vec![]),
+            Instruction::new_with_bytes(Pubkey::new_unique(), &[], vec![]),
+            Instruction::new_with_bytes(COMPUTE_BUDGET_PROGRAM_ID, &price, vec![]),
+        ]);
+        let limit = 2 * DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT
+            + 2 * MAX_BUILTIN_ALLOCATION_COMPUTE_UNIT_LIMIT;
+        assert_eq!(fee.compute_unit_limit, limit);
+        assert_eq!(fee.prioritization_fee, limit as u64);
+
+        // Capped at the maximum
+        let programs: Vec<_> = (0..8)
+            .map(|_| Instruction::new_with_bytes(Pubkey::new_unique(), &[], vec![]))
+            .collect();
+        assert_eq!(
+            estimate(&programs).compute_unit_limit,
+            MAX_COMPUTE_UNIT_LIMIT
+        );
+    }
+
+    #[test]
+    fn test_precompile_signatures() {
+        let fee = estimate(&[
+            Instruction::new_with_bytes(ED25519_PROGRAM_ID, &[2, 0], vec![]),
+            Instruction::new_with_bytes(SECP256R1_PROGRAM_ID, &[1, 0], vec![]),
+        ]);
+        assert_eq!(fee.base_fee, 4 * LAMPORTS_PER_SIGNATURE);
+
+        // Secp256r1 signatures are free without the precompile
+        let mut feature_set = FeatureSet::all_enabled();
+        feature_set.deactivate(&enable_secp256r1_precompile::id());
+        let schedule = FeeSchedule {
+            lamports_per_signature: 10_000,
+            ..fee_schedule(true)
+        };
+        let fee = estimate_fee(
+            &message(&[Instruction::new_with_bytes(
+                SECP256R1_PROGRAM_ID,
+                &[1, 0],
+                vec![],
+            )]),
+            &schedule,
+            &feature_set,
+        );
+        assert_eq!(fee.base_fee, 10_000);
+    }
+}
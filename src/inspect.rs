@@ -0,0 +1,265 @@
+use solana_sdk::{message::VersionedMessage, pubkey::Pubkey, transaction::VersionedTransaction};
+
+pub use simulation_engine::types::{
+    AccountKey, AddressTableLookup, InstructionBreakdown, TouchedAccount, TransactionBreakdown,
+};
+
+/// Resolves every account of a message, in the order the runtime loads
+/// them: static keys, then the writable and readonly addresses of its
+/// lookup tables. `load_table` returns the addresses of a lookup table.
+pub fn touched_accounts(
+    message: &VersionedMessage,
+    mut load_table: impl FnMut(&Pubkey) -> anyhow::Result<Vec<Pubkey>>,
+) -> anyhow::Result<Vec<TouchedAccount>> {
+    let mut touched: Vec<TouchedAccount> = message
+        .static_account_keys()
+        .iter()
+        .enumerate()
+        .map(|(index, key)| TouchedAccount {
+            pubkey: key.to_string(),
+            signer: message.is_signer(index),
+            writable: message.is_maybe_writable(index, None),
+            lookup_table: None,
+        })
+        .collect();
+
+    let lookups = message.address_table_lookups().unwrap_or_default();
+    let mut readonly = Vec::new();
+    for lookup in lookups {
+        let addresses = load_table(&lookup.account_key)?;
+        let resolve = |index: &u8, writable: bool| {
+            let Some(address) = addresses.get(*index as usize) else {
+                anyhow::bail!(
+                    "Invalid index {} of lookup table {}",
+                    index,
+                    lookup.account_key
+                );
+            };
+            Ok(TouchedAccount {
+                pubkey: address.to_string(),
+                signer: false,
+                writable,
+                lookup_table: Some(lookup.account_key.to_string()),
+            })
+        };
+        for index in &lookup.writable_indexes {
+            touched.push(resolve(index, true)?);
+        }
+        for index in &lookup.readonly_indexes {
+            readonly.push(resolve(index, false)?);
+        }
+    }
+    touched.extend(readonly);
+    Ok(touched)
+}
+
+/// Breaks a decoded transaction down into its fee payer, accounts and
+/// instructions
+pub fn inspect_transaction(tx: &VersionedTransaction) -> TransactionBreakdown {
+    let message = &tx.message;
+    let account_keys = message.static_account_keys();
+    let resolve = |index: u8| {
+        account_keys
+            .get(index as usize)
+            .map(|key| key.to_string())
+            .unwrap_or_else(|| format!("#{}", index))
+    };
+
+    TransactionBreakdown {
+        version: match message {
+            VersionedMessage::Legacy(_) => "legacy".to_string(),
+            VersionedMessage::V0(_) => "0".to_string(),
+        },
+        signatures: tx.signatures.iter().map(|sig| sig.to_string()).collect(),
+        fee_payer: resolve(0),
+        recent_blockhash: message.recent_blockhash().to_string(),
+        account_keys: account_keys
+            .iter()
+            .enumerate()
+            .map(|(index, key)| AccountKey {
+                pubkey: key.to_string(),
+                signer: message.is_signer(index),
+                writable: message.is_maybe_writable(index, None),
+            })
+            .collect(),
+        address_table_lookups: message
+            .address_table_lookups()
+            .unwrap_or_default()
+            .iter()
+            .map(|lookup| AddressTableLookup {
+                account_key: lookup.account_key.to_string(),
+                writable_indexes: lookup.writable_indexes.clone(),
+                readonly_indexes: lookup.readonly_indexes.clone(),
+            })
+            .collect(),
+        instructions: message
+            .instructions()
+            .iter()
+            .map(|ix| InstructionBreakdown {
+                program_id: resolve(ix.program_id_index),
+                accounts: ix.accounts.iter().map(|index| resolve(*index)).collect(),
+                data_len: ix.data.len(),
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lookup_table::{ADDRESS_LOOKUP_TABLE_PROGRAM, LookupTable};
+    use solana_sdk::{
+        account::Account,
+        hash::Hash,
+        instruction::{AccountMeta, Instruction},
+        message::{AddressLookupTableAccount, Message, v0},
+        signature::{Keypair, Signer},
+        transaction::Transaction,
+    };
+    use solana_system_interface::instruction as system_instruction;
+
+    #[test]
+    fn test_inspect_transfer() {
+        let payer = Keypair::new();
+        let recipient = Pubkey::new_unique();
+        let blockhash = Hash::new_unique();
+        let tx = Transaction::new(
+            &[&payer],
+            Message::new(
+                &[system_instruction::transfer(&payer.pubkey(), &recipient, 1)],
+                Some(&payer.pubkey()),
+            ),
+            blockhash,
+        );
+
+        let breakdown = inspect_transaction(&tx.into());
+        assert_eq!(breakdown.version, "legacy");
+        assert_eq!(breakdown.fee_payer, payer.pubkey().to_string());
+        assert_eq!(breakdown.recent_blockhash, blockhash.to_string());
+        assert_eq!(breakdown.signatures.len(), 1);
+        assert_eq!(
+            breakdown.account_keys,
+            vec![
+                AccountKey {
+                    pubkey: payer.pubkey().to_string(),
+                    signer: true,
+                    writable: true,
+                },
+                AccountKey {
+                    pubkey: recipient.to_string(),
+                    signer: false,
+                    writable: true,
+                },
+                AccountKey {
+                    pubkey: solana_system_interface::program::ID.to_string(),
+                    signer: false,
+                    writable: false,
+                },
+            ]
+        );
+        assert!(breakdown.address_table_lookups.is_empty());
+        assert_eq!(
+            breakdown.instructions,
+            vec![InstructionBreakdown {
+                program_id: solana_system_interface::program::ID.to_string(),
+                accounts: vec![payer.pubkey().to_string(), recipient.to_string()],
+                // Transfer discriminant (u32) and lamports (u64)
+                data_len: 12,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_touched_accounts() {
+        let payer = Keypair::new();
+        let recipient = Pubkey::new_unique();
+        let message = VersionedMessage::Legacy(Message::new(
+            &[system_instruction::transfer(&payer.pubkey(), &recipient, 1)],
+            Some(&payer.pubkey()),
+        ));
+        let touched = touched_accounts(&message, |_| unreachable!()).unwrap();
+        assert_eq!(
+            touched,
+            vec![
+                TouchedAccount {
+                    pubkey: payer.pubkey().to_string(),
+                    signer: true,
+                    writable: true,
+                    lookup_table: None,
+                },
+                TouchedAccount {
+                    pubkey: recipient.to_string(),
+                    signer: false,
+                    writable: true,
+                    lookup_table: None,
+                },
+                TouchedAccount {
+                    pubkey: solana_system_interface::program::ID.to_string(),
+                    signer: false,
+                    writable: false,
+                    lookup_table: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_touched_accounts_with_lookup_table() {
+        let payer = Keypair::new();
+        let (writable, readonly) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![readonly, writable],
+        };
+        let ix = Instruction::new_with_bytes(
+            Pubkey::new_unique(),
+            &[],
+            vec![
+                AccountMeta::new_readonly(readonly, false),
+                AccountMeta::new(writable, false),
+            ],
+        );
+        let message = VersionedMessage::V0(
+            v0::Message::try_compile(
+                &payer.pubkey(),
+                &[ix],
+                std::slice::from_ref(&table),
+                Hash::new_unique(),
+            )
+            .unwrap(),
+        );
+
+        let account = Account {
+            lamports: 1,
+            data: LookupTable::new(None, table.addresses.clone(), 1).to_data(),
+            owner: ADDRESS_LOOKUP_TABLE_PROGRAM,
+            executable: false,
+            rent_epoch: 0,
+        };
+        let touched = touched_accounts(&message, |key| {
+            assert_eq!(*key, table.key);
+            Ok(LookupTable::from_account(&account)?.addresses)
+        })
+        .unwrap();
+        let lookup_table = Some(table.key.to_string());
+        assert_eq!(touched.len(), 4);
+        assert_eq!(touched[0].pubkey, payer.pubkey().to_string());
+        assert_eq!(
+            touched[2..],
+            [
+                TouchedAccount {
+                    pubkey: writable.to_string(),
+                    signer: false,
+                    writable: true,
+                    lookup_table: lookup_table.clone(),
+                },
+                TouchedAccount {
+                    pubkey: readonly.to_string(),
+                    signer: false,
+                    writable: false,
+                    lookup_table,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,317 @@
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use uuid::Uuid;
+
+use crate::encoding::{AccountEncoding, encode_account};
+use crate::manager::ForkManager;
+
+/// Error codes defined by the JSON-RPC 2.0 spec
+pub(crate) const PARSE_ERROR: i64 = -32700;
+pub(crate) const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+pub(crate) const INVALID_PARAMS: i64 = -32602;
+pub(crate) const INTERNAL_ERROR: i64 = -32603;
+
+#[derive(Deserialize)]
+struct JsonRpcRequest {
+    jsonrpc: String,
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Serialize)]
+pub(crate) struct JsonRpcResponse {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<JsonRpcError>,
+}
+
+#[derive(Serialize)]
+struct JsonRpcError {
+    code: i64,
+    message: String,
+}
+
+impl JsonRpcResponse {
+    pub(crate) fn result(id: Value, result: Value) -> Self {
+        JsonRpcResponse {
+            jsonrpc: "2.0",
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    pub(crate) fn error(id: Value, code: i64, message: impl Into<String>) -> Self {
+        JsonRpcResponse {
+            jsonrpc: "2.0",
+            id,
+            result: None,
+            error: Some(JsonRpcError {
+                code,
+                message: message.into(),
+            }),
+        }
+    }
+}
+
+/// Optional config object of account reads
+#[derive(Deserialize, Default)]
+struct AccountConfig {
+    #[serde(default)]
+    encoding: AccountEncoding,
+}
+
+/// Handles a JSON-RPC body, either a single request or a batch of them.
+/// Requests of a batch are dispatched in order and fail independently, each
+/// response carries the `id` of its request.
+pub fn handle(manager: &ForkManager, fork_id: &Uuid, body: &[u8]) -> Value {
+    let body = match serde_json::from_slice::<Value>(body) {
+        Ok(body) => body,
+        Err(e) => {
+            return json!(JsonRpcResponse::error(
+                Value::Null,
+                PARSE_ERROR,
+                e.to_string()
+            ));
+        }
+    };
+
+    match body {
+        Value::Array(requests) if requests.is_empty() => json!(JsonRpcResponse::error(
+            Value::Null,
+            INVALID_REQUEST,
+            "empty batch"
+        )),
+        Value::Array(requests) => Value::Array(
+            requests
+                .into_iter()
+                .map(|request| json!(dispatch(manager, fork_id, request)))
+                .collect(),
+        ),
+        request => json!(dispatch(manager, fork_id, request)),
+    }
+}
+
+/// Runs a single request against a fork
+fn dispatch(manager: &ForkManager, fork_id: &Uuid, request: Value) -> JsonRpcResponse {
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let request = match serde_json::from_value::<JsonRpcRequest>(request) {
+        Ok(request) if request.jsonrpc == "2.0" => request,
+        Ok(_) => return JsonRpcResponse::error(id, INVALID_REQUEST, "jsonrpc must be \"2.0\""),
+        Err(e) => return JsonRpcResponse::error(id, INVALID_REQUEST, e.to_string()),
+    };
+
+    let result = match request.method.as_str() {
+        "getAccountInfo" => get_account_info(manager, fork_id, request.params),
+        "getMultipleAccounts" => get_multiple_accounts(manager, fork_id, request.params),
+        "getBalance" => get_balance(manager, fork_id, request.params),
+        "getLatestBlockhash" => get_latest_blockhash(manager, fork_id),
+        "getMinimumBalanceForRentExemption" => {
+            get_minimum_balance(manager, fork_id, request.params)
+        }
+        method => Err(RpcFailure::MethodNotFound(method.to_string())),
+    };
+
+    match result {
+        Ok(result) => JsonRpcResponse::result(request.id, result),
+        Err(RpcFailure::MethodNotFound(method)) => JsonRpcResponse::error(
+            request.id,
+            METHOD_NOT_FOUND,
+            format!("Method not found: {}", method),
+        ),
+        Err(RpcFailure::InvalidParams(message)) => {
+            JsonRpcResponse::error(request.id, INVALID_PARAMS, message)
+        }
+        Err(RpcFailure::Internal(e)) => {
+            JsonRpcResponse::error(request.id, INTERNAL_ERROR, format!("{:?}", e))
+        }
+    }
+}
+
+enum RpcFailure {
+    MethodNotFound(String),
+    InvalidParams(String),
+    Internal(anyhow::Error),
+}
+
+impl From<anyhow::Error> for RpcFailure {
+    fn from(e: anyhow::Error) -> Self {
+        RpcFailure::Internal(e)
+    }
+}
+
+/// Splits the positional params of a request
+fn positional(params: Value) -> Result<Vec<Value>, RpcFailure> {
+    match params {
+        Value::Null => Ok(Vec::new()),
+        Value::Array(params) => Ok(params),
+        _ => Err(RpcFailure::InvalidParams(
+            "params must be an array".to_string(),
+        )),
+    }
+}
+
+/// Deserializes the param at `index`, missing params being `null`
+fn param<T: DeserializeOwned>(params: &[Value], index: usize) -> Result<T, RpcFailure> {
+    let param = params.get(index).cloned().unwrap_or(Value::Null);
+    serde_json::from_value(param)
+        .map_err(|e| RpcFailure::InvalidParams(format!("param {}: {}", index, e)))
+}
+
+fn parse_pubkey(value: &str) -> Result<Pubkey, RpcFailure> {
+    value
+        .parse()
+        .map_err(|_| RpcFailure::InvalidParams(format!("Invalid pubkey {}", value)))
+}
+
+/// Wraps a result in the `{ context, value }` object of the Solana RPC
+fn with_context(manager: &ForkManager, fork_id: &Uuid, value: Value) -> Result<Value, RpcFailure> {
+    let (_, slot) = manager.get_blockhash(fork_id)?;
+    Ok(json!({ "context": { "slot": slot }, "value": value }))
+}
+
+/// Encodes an account the way the Solana RPC does
+fn rpc_account(account: Account, encoding: AccountEncoding) -> Value {
+    let space = account.data.len();
+    let account = encode_account(account, encoding);
+    json!({
+        "lamports": account.lamports,
+        "owner": account.owner,
+        "data": account.data,
+        "executable": account.executable,
+        "rentEpoch": account.rent_epoch,
+        "space": space,
+    })
+}
+
+fn get_account_info(
+    manager: &ForkManager,
+    fork_id: &Uuid,
+    params: Value,
+) -> Result<Value, RpcFailure> {
+    let params = positional(params)?;
+    let pubkey: String = param(&params, 0)?;
+    let config: AccountConfig = param::<Option<_>>(&params, 1)?.unwrap_or_default();
+    let account = manager.find_account(fork_id, parse_pubkey(&pubkey)?)?;
+    let value = account.map_or(Value::Null, |acc| rpc_account(acc, config.encoding));
+    with_context(manager, fork_id, value)
+}
+
+fn get_multiple_accounts(
+    manager: &ForkManager,
+    fork_id: &Uuid,
+    params: Value,
+) -> Result<Value, RpcFailure> {
+    let params = positional(params)?;
+    let pubkeys: Vec<String> = param(&params, 0)?;
+    let config: AccountConfig = param::<Option<_>>(&params, 1)?.unwrap_or_default();
+    let accounts = pubkeys
+        .iter()
+        .map(|pubkey| {
+            let account = manager.find_account(fork_id, parse_pubkey(pubkey)?)?;
+            Ok(account.map_or(Value::Null, |acc| rpc_account(acc, config.encoding)))
+        })
+        .collect::<Result<Vec<_>, RpcFailure>>()?;
+    with_context(manager, fork_id, Value::Array(accounts))
+}
+
+fn get_balance(manager: &ForkManager, fork_id: &Uuid, params: Value) -> Result<Value, RpcFailure> {
+    let pubkey: String = param(&positional(params)?, 0)?;
+    let account = manager.find_account(fork_id, parse_pubkey(&pubkey)?)?;
+    with_context(
+        manager,
+        fork_id,
+        json!(account.map_or(0, |acc| acc.lamports)),
+    )
+}
+
+fn get_latest_blockhash(manager: &ForkManager, fork_id: &Uuid) -> Result<Value, RpcFailure> {
+    let (blockhash, slot) = manager.get_blockhash(fork_id)?;
+    with_context(
+        manager,
+        fork_id,
+        json!({ "blockhash": blockhash.to_string(), "lastValidBlockHeight": slot }),
+    )
+}
+
+fn get_minimum_balance(
+    manager: &ForkManager,
+    fork_id: &Uuid,
+    params: Value,
+) -> Result<Value, RpcFailure> {
+    let data_len: usize = param(&positional(params)?, 0)?;
+    Ok(json!(manager.minimum_balance(fork_id, data_len)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::tests::offline_fork;
+
+    #[test]
+    fn test_batch() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let funded = Pubkey::new_unique();
+        manager
+            .set_lamports(&fork_id, funded, 1_000_000, false)
+            .unwrap();
+
+        let body = json!([
+            { "jsonrpc": "2.0", "id": 1, "method": "getBalance", "params": [funded.to_string()] },
+            { "jsonrpc": "2.0", "id": "missing", "method": "getAccountInfo", "params": [Pubkey::new_unique().to_string()] },
+            { "jsonrpc": "2.0", "id": 3, "method": "getAccountInfo", "params": ["invalid"] },
+            { "jsonrpc": "2.0", "id": 4, "method": "unknownMethod" },
+            { "id": 5 },
+        ]);
+        let responses = handle(&manager, &fork_id, body.to_string().as_bytes());
+        let responses = responses.as_array().unwrap();
+        assert_eq!(responses.len(), 5);
+
+        assert_eq!(responses[0]["id"], 1);
+        assert_eq!(responses[0]["result"]["value"], 1_000_000);
+        assert_eq!(responses[1]["id"], "missing");
+        assert_eq!(responses[1]["result"]["value"], Value::Null);
+        assert_eq!(responses[2]["error"]["code"], INVALID_PARAMS);
+        assert_eq!(responses[3]["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(responses[4]["id"], 5);
+        assert_eq!(responses[4]["error"]["code"], INVALID_REQUEST);
+    }
+
+    #[test]
+    fn test_single_request() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let funded = Pubkey::new_unique();
+        manager
+            .set_lamports(&fork_id, funded, 1_000_000, false)
+            .unwrap();
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "getAccountInfo",
+            "params": [funded.to_string(), { "encoding": "base64" }],
+        });
+        let response = handle(&manager, &fork_id, body.to_string().as_bytes());
+        assert_eq!(response["result"]["value"]["lamports"], 1_000_000);
+        assert_eq!(response["result"]["value"]["data"][1], "base64");
+
+        assert_eq!(
+            handle(&manager, &fork_id, b"[]")["error"]["code"],
+            INVALID_REQUEST
+        );
+        assert_eq!(
+            handle(&manager, &fork_id, b"{")["error"]["code"],
+            PARSE_ERROR
+        );
+    }
+}
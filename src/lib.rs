@@ -0,0 +1,6 @@
+//! Wire types of the simulation engine's HTTP API, and a typed client
+//! behind the `client` feature
+
+#[cfg(feature = "client")]
+pub mod client;
+pub mod types;
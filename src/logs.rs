@@ -0,0 +1,256 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+
+pub use simulation_engine::types::{InstructionCompute, LogMatch};
+
+/// Default maximum number of log lines kept per transaction
+pub const DEFAULT_LOG_MAX_LINES: usize = 1_000;
+/// Default maximum size of the log lines kept per transaction, in bytes
+pub const DEFAULT_LOG_MAX_BYTES: usize = 256 * 1024;
+
+/// Caps on the logs of a transaction, applied to responses and to the
+/// transactions recorded by forks
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct LogLimits {
+    pub max_lines: usize,
+    pub max_bytes: usize,
+}
+
+impl Default for LogLimits {
+    fn default() -> Self {
+        LogLimits {
+            max_lines: DEFAULT_LOG_MAX_LINES,
+            max_bytes: DEFAULT_LOG_MAX_BYTES,
+        }
+    }
+}
+
+impl LogLimits {
+    /// Reads the limits from the `LOG_MAX_LINES` and `LOG_MAX_BYTES` env
+    /// vars, using defaults for missing ones
+    pub fn from_env() -> Self {
+        let env = |var: &str| {
+            std::env::var(var)
+                .ok()
+                .and_then(|v| v.parse::<usize>().ok())
+        };
+        LogLimits::default().with_overrides(env("LOG_MAX_LINES"), env("LOG_MAX_BYTES"))
+    }
+
+    /// Replaces the limits set by a request
+    pub fn with_overrides(self, max_lines: Option<usize>, max_bytes: Option<usize>) -> Self {
+        LogLimits {
+            max_lines: max_lines.unwrap_or(self.max_lines),
+            max_bytes: max_bytes.unwrap_or(self.max_bytes),
+        }
+    }
+
+    /// Drops the lines past the limits, replacing them with a
+    /// `...(N lines truncated)` marker. Lines dropped by a previous
+    /// truncation are carried over to the new marker.
+    pub fn truncate(&self, logs: &mut Vec<String>) {
+        let dropped = logs.last().and_then(|line| truncated_lines(line));
+        if dropped.is_some() {
+            logs.pop();
+        }
+        let mut bytes = 0;
+        let kept = logs
+            .iter()
+            .take(self.max_lines)
+            .take_while(|line| {
+                bytes += line.len();
+                bytes <= self.max_bytes
+            })
+            .count();
+        let truncated = logs.len() - kept + dropped.unwrap_or(0);
+        if truncated > 0 {
+            logs.truncate(kept);
+            logs.push(format!("...({} lines truncated)", truncated));
+        }
+    }
+}
+
+/// Helper function parsing the number of lines a `...(N lines truncated)`
+/// marker stands for
+fn truncated_lines(line: &str) -> Option<usize> {
+    line.strip_prefix("...(")?
+        .strip_suffix(" lines truncated)")?
+        .parse()
+        .ok()
+}
+
+/// Matches program log lines against a substring or a regex
+pub enum LogMatcher {
+    Substring(String),
+    Regex(Regex),
+}
+
+impl LogMatcher {
+    pub fn new(pattern: &str, regex: bool) -> anyhow::Result<Self> {
+        if regex {
+            Ok(LogMatcher::Regex(Regex::new(pattern)?))
+        } else {
+            Ok(LogMatcher::Substring(pattern.to_string()))
+        }
+    }
+
+    pub fn is_match(&self, line: &str) -> bool {
+        match self {
+            LogMatcher::Substring(pattern) => line.contains(pattern.as_str()),
+            LogMatcher::Regex(regex) => regex.is_match(line),
+        }
+    }
+
+    /// Matches the logs of a simulation, `error` being set if it failed
+    pub fn match_logs(&self, logs: Vec<String>, error: Option<String>) -> LogMatch {
+        let matching_lines: Vec<String> = logs
+            .iter()
+            .filter(|line| self.is_match(line))
+            .cloned()
+            .collect();
+        LogMatch {
+            success: error.is_none(),
+            error,
+            matched: !matching_lines.is_empty(),
+            matching_lines,
+            logs,
+        }
+    }
+}
+
+/// Parses the `Program <id> consumed <x> of <y> compute units` lines of a
+/// transaction's logs. Invocations are listed in the order they returned,
+/// so CPIs come before the instruction invoking them and their units are
+/// included in its own. Must run before the logs are truncated.
+pub fn instruction_compute_units(logs: &[String]) -> Vec<InstructionCompute> {
+    logs.iter()
+        .filter_map(|line| {
+            let (program_id, rest) = line.strip_prefix("Program ")?.split_once(" consumed ")?;
+            // Skips program logs, which come as `Program log: ...`
+            program_id.parse::<Pubkey>().ok()?;
+            let (consumed, budget) = rest.strip_suffix(" compute units")?.split_once(" of ")?;
+            Some(InstructionCompute {
+                program_id: program_id.to_string(),
+                units_consumed: consumed.parse().ok()?,
+                units_budget: budget.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn logs() -> Vec<String> {
+        vec![
+            "Program 11111111111111111111111111111111 invoke [1]".to_string(),
+            "Program log: Instruction: Transfer".to_string(),
+            "Program 11111111111111111111111111111111 success".to_string(),
+        ]
+    }
+
+    #[test]
+    fn test_substring() {
+        let matcher = LogMatcher::new("Instruction: Transfer", false).unwrap();
+        let result = matcher.match_logs(logs(), None);
+        assert!(result.success);
+        assert!(result.matched);
+        assert_eq!(
+            result.matching_lines,
+            vec!["Program log: Instruction: Transfer"]
+        );
+
+        // Regex syntax is matched literally
+        let matcher = LogMatcher::new("invoke \\[1\\]", false).unwrap();
+        assert!(!matcher.match_logs(logs(), None).matched);
+    }
+
+    #[test]
+    fn test_regex() {
+        let matcher = LogMatcher::new(r"^Program \w+ (invoke|success)", true).unwrap();
+        let result = matcher.match_logs(logs(), Some("failed".to_string()));
+        assert!(!result.success);
+        assert_eq!(result.matching_lines.len(), 2);
+
+        assert!(LogMatcher::new("(", true).is_err());
+    }
+
+    #[test]
+    fn test_instruction_compute_units() {
+        let token = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+        let lines: Vec<String> = [
+            "Program ComputeBudget111111111111111111111111111111 invoke [1]",
+            "Program ComputeBudget111111111111111111111111111111 success",
+            "Program JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4 invoke [1]",
+            "Program log: Instruction: Route",
+            &format!("Program {} invoke [2]", token),
+            "Program log: Instruction: Transfer",
+            &format!("Program {} consumed 4645 of 180000 compute units", token),
+            &format!("Program {} success", token),
+            "Program log: consumed 12 of 34 compute units",
+            "Program JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4 consumed 25362 of 199850 compute units",
+            "Program JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4 success",
+            "Program 11111111111111111111111111111111 invoke [1]",
+            "Program 11111111111111111111111111111111 success",
+        ]
+        .iter()
+        .map(|line| line.to_string())
+        .collect();
+
+        // Builtins don't log their consumption, and program logs mimicking
+        // the runtime's line are skipped
+        assert_eq!(
+            instruction_compute_units(&lines),
+            vec![
+                InstructionCompute {
+                    program_id: token.to_string(),
+                    units_consumed: 4645,
+                    units_budget: 180_000,
+                },
+                InstructionCompute {
+                    program_id: "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4".to_string(),
+                    units_consumed: 25362,
+                    units_budget: 199_850,
+                },
+            ]
+        );
+        assert!(instruction_compute_units(&logs()).is_empty());
+    }
+
+    #[test]
+    fn test_truncate() {
+        let mut lines = logs();
+        LogLimits::default().truncate(&mut lines);
+        assert_eq!(lines, logs());
+
+        let mut lines = logs();
+        LogLimits::default()
+            .with_overrides(Some(1), None)
+            .truncate(&mut lines);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1], "...(2 lines truncated)");
+
+        // The line crossing the byte limit is dropped
+        let mut lines = logs();
+        LogLimits::default()
+            .with_overrides(None, Some(logs()[0].len() + 1))
+            .truncate(&mut lines);
+        assert_eq!(lines[0], logs()[0]);
+        assert_eq!(lines[1], "...(2 lines truncated)");
+
+        // Truncating again counts the lines dropped the first time, not
+        // their marker
+        let limits = LogLimits::default().with_overrides(Some(1), None);
+        limits.truncate(&mut lines);
+        assert_eq!(
+            lines,
+            [logs()[0].clone(), "...(2 lines truncated)".to_string()]
+        );
+        LogLimits::default()
+            .with_overrides(Some(0), None)
+            .truncate(&mut lines);
+        assert_eq!(lines, ["...(3 lines truncated)"]);
+    }
+}
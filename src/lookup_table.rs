@@ -0,0 +1,135 @@
+use solana_sdk::{account::Account, pubkey::Pubkey};
+
+/// Program owning address lookup tables
+pub const ADDRESS_LOOKUP_TABLE_PROGRAM: Pubkey =
+    Pubkey::from_str_const("AddressLookupTab1e1111111111111111111111111");
+/// Maximum number of addresses of a lookup table
+pub const MAX_ADDRESSES: usize = 256;
+/// Size of the metadata preceding the addresses of a lookup table
+pub const META_SIZE: usize = 56;
+/// Tag of the `LookupTable` variant of the program's account state
+const LOOKUP_TABLE_TAG: u32 = 1;
+
+/// State of an address lookup table account
+#[derive(Clone, PartialEq, Debug)]
+pub struct LookupTable {
+    /// `u64::MAX` while the table is active
+    pub deactivation_slot: u64,
+    pub last_extended_slot: u64,
+    /// Number of addresses before the last extension, the ones added by it
+    /// only being usable after `last_extended_slot`
+    pub last_extended_slot_start_index: u8,
+    /// Authority allowed to extend the table, `None` once frozen
+    pub authority: Option<Pubkey>,
+    pub addresses: Vec<Pubkey>,
+}
+
+impl LookupTable {
+    /// Active table holding `addresses`, all of them usable at `slot`
+    pub fn new(authority: Option<Pubkey>, addresses: Vec<Pubkey>, slot: u64) -> Self {
+        let mut table = LookupTable {
+            deactivation_slot: u64::MAX,
+            last_extended_slot: 0,
+            last_extended_slot_start_index: 0,
+            authority,
+            addresses: Vec::new(),
+        };
+        table.extend(addresses, slot);
+        table
+    }
+
+    /// Appends `addresses`, keeping them all usable at `slot`. Tables are
+    /// written directly rather than extended by a transaction, so unlike on
+    /// mainnet the new addresses don't wait for the next slot. At slot 0 the
+    /// 256th address can't be activated, as the runtime only activates up to
+    /// `last_extended_slot_start_index` addresses in the slot of an
+    /// extension.
+    pub fn extend(&mut self, addresses: Vec<Pubkey>, slot: u64) {
+        self.addresses.extend(addresses);
+        self.last_extended_slot = slot.saturating_sub(1);
+        self.last_extended_slot_start_index = self.addresses.len().min(u8::MAX as usize) as u8;
+    }
+
+    /// Decodes a lookup table account
+    pub fn from_account(account: &Account) -> anyhow::Result<Self> {
+        if account.owner != ADDRESS_LOOKUP_TABLE_PROGRAM {
+            anyhow::bail!("Account is not an address lookup table");
+        }
+        let data = &account.data;
+        if data.len() < META_SIZE || data[..4] != LOOKUP_TABLE_TAG.to_le_bytes() {
+            anyhow::bail!("Lookup table is uninitialized");
+        }
+        let addresses = &data[META_SIZE..];
+        if !addresses.len().is_multiple_of(32) {
+            anyhow::bail!("Lookup table data is misaligned");
+        }
+        let slot = |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+        Ok(LookupTable {
+            deactivation_slot: slot(4),
+            last_extended_slot: slot(12),
+            last_extended_slot_start_index: data[20],
+            authority: (data[21] == 1).then(|| Pubkey::try_from(&data[22..54]).unwrap()),
+            addresses: addresses
+                .chunks_exact(32)
+                .map(|chunk| Pubkey::try_from(chunk).unwrap())
+                .collect(),
+        })
+    }
+
+    /// Encodes the table as the data of its account, the bincode layout of
+    /// the program's `ProgramState::LookupTable`
+    pub fn to_data(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(META_SIZE + self.addresses.len() * 32);
+        data.extend_from_slice(&LOOKUP_TABLE_TAG.to_le_bytes());
+        data.extend_from_slice(&self.deactivation_slot.to_le_bytes());
+        data.extend_from_slice(&self.last_extended_slot.to_le_bytes());
+        data.push(self.last_extended_slot_start_index);
+        match &self.authority {
+            Some(authority) => {
+                data.push(1);
+                data.extend_from_slice(authority.as_ref());
+            }
+            None => data.push(0),
+        }
+        // Padding up to the fixed size of the metadata
+        data.resize(META_SIZE, 0);
+        for address in &self.addresses {
+            data.extend_from_slice(address.as_ref());
+        }
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        for authority in [Some(Pubkey::new_unique()), None] {
+            let mut table = LookupTable::new(authority, vec![Pubkey::new_unique()], 10);
+            table.extend(vec![Pubkey::new_unique(), Pubkey::new_unique()], 12);
+            assert_eq!(table.last_extended_slot, 11);
+            assert_eq!(table.last_extended_slot_start_index, 3);
+
+            let account = Account {
+                lamports: 1,
+                data: table.to_data(),
+                owner: ADDRESS_LOOKUP_TABLE_PROGRAM,
+                executable: false,
+                rent_epoch: 0,
+            };
+            assert_eq!(account.data.len(), META_SIZE + 3 * 32);
+            assert_eq!(LookupTable::from_account(&account).unwrap(), table);
+        }
+
+        let uninitialized = Account {
+            lamports: 1,
+            data: vec![0; META_SIZE],
+            owner: ADDRESS_LOOKUP_TABLE_PROGRAM,
+            executable: false,
+            rent_epoch: 0,
+        };
+        assert!(LookupTable::from_account(&uninitialized).is_err());
+    }
+}
@@ -1,83 +1,403 @@
+#![recursion_limit = "256"]
+
 use axum::{
-    Json, Router,
-    extract::{Path, State},
-    routing::{delete, post},
+    Extension, Json, Router,
+    body::{Body, BodyDataStream, Bytes},
+    extract::{DefaultBodyLimit, FromRequest, Path, Query, Request, State, WebSocketUpgrade},
+    http::{HeaderMap, StatusCode, header},
+    middleware::{self, Next},
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+    routing::{delete, get, post},
 };
 use base64::{Engine, engine};
-use bincode;
-use litesvm::types::TransactionMetadata;
+use bincode::{self, Options};
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, stream};
 use serde::{Deserialize, Serialize};
 use std::{
+    convert::Infallible,
     net::SocketAddr,
-    sync::{Arc, Mutex},
+    sync::{Arc, RwLock},
     time::Duration,
 };
-use tokio::time;
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time,
+};
+use tower_http::{
+    compression::CompressionLayer,
+    cors::{AllowOrigin, Any, CorsLayer},
+};
 use uuid::Uuid;
+mod encoding;
+mod errors;
+mod fees;
+mod inspect;
+mod jsonrpc;
+mod logs;
+mod lookup_table;
 mod manager;
-use manager::ForkManager;
-use solana_sdk::{account::Account, pubkey::Pubkey};
+mod openapi;
+mod rpc;
+mod sync;
+mod trace;
+mod ws;
+use manager::{
+    DEFAULT_EXECUTION_TIMEOUT, DEFAULT_HISTORY_CAPACITY, DEFAULT_MAX_FORKS, DeleteForkResult,
+    ForkManager,
+};
+use solana_sdk::{hash::Hash, pubkey::Pubkey, signature::Signature};
+use spl_token::solana_program::program_pack::Pack;
+use spl_token::state::{Account as TokenAccount, Mint};
 
-use solana_sdk::transaction::VersionedTransaction;
+use solana_packet::PACKET_DATA_SIZE;
+use solana_sdk::{
+    message::VersionedMessage,
+    transaction::{TransactionError, VersionedTransaction},
+};
+
+use crate::encoding::{UiAccount, encode_account, slice_account_data};
+use crate::errors::{TransactionFailure, describe_failure};
+use crate::fees::{FeeEstimate, FeeSchedule};
+use crate::inspect::{TouchedAccount, TransactionBreakdown, inspect_transaction};
+use crate::logs::{LogLimits, LogMatch, LogMatcher, instruction_compute_units};
+use crate::manager::{
+    ActiveFeature, BatchDeleteResult, ClonedProgram, ComputeFit, EpochInfo, ExportedAccount,
+    ForkDiff, ForkOptions, ForkSetup, ForkStats, ForkSummary, ProgramAccountsFilter,
+    ProgramAddress, ReplayResult, ReturnData, SendResult, SetupResult, SimulateOptions,
+    SlotHashEntry, TokenBalance, TokenBalanceEntry, TokenBalanceOptions, TransactionHistoryQuery,
+    TransactionPage, TransactionRecord,
+};
+use crate::rpc::RpcPolicy;
+use crate::sync::RwLockExt;
+use crate::trace::build_trace;
+use simulation_engine::types::{
+    AccountExistsQuery, AccountReadQuery, ApiResponse, AssociatedTokenAddressRequest,
+    BlockhashResponse, ComputeFitRequest, CreateLookupTableRequest, CreateMintRequest, DataSlice,
+    DiffForksRequest, ExecuteRequest, ExecuteResponse, ExtendLookupTableRequest, FindPdaRequest,
+    FreezeTokenRequest, FundedAccount, GetAccountRequest, HealthResponse, ListForksQuery,
+    MinimumBalanceQuery, ProgramAccount, ReplayHistoryRequest, SetBlockhashRequest,
+    SetEpochRequest, SetExecutableRequest, SetLamportsRequest, SetOwnerRequest, SetRentRequest,
+    SetTokenBalanceRequest, SetTokenDelegateRequest, SimulateMatchRequest, SimulateRequest,
+    SimulateResponse, SlotHashesQuery, TransactionEventsQuery, TransactionLookupQuery,
+};
 
-use crate::manager::TransactionRecord;
+/// Maximum length of a base64 encoded transaction
+const MAX_TX_BASE64_LEN: usize = PACKET_DATA_SIZE.div_ceil(3) * 4;
+/// Default interval of the cleanup of expired forks
+const DEFAULT_CLEANUP_INTERVAL_SECS: u64 = 60;
+/// Default body limit of transaction submission routes
+const DEFAULT_TX_BODY_LIMIT: usize = 16 * 1024;
+/// Default body limit of the setup route, large enough for program ELFs
+const DEFAULT_SETUP_BODY_LIMIT: usize = 16 * 1024 * 1024;
+/// Default body limit of every other route
+const DEFAULT_BODY_LIMIT: usize = 1024 * 1024;
+/// Default number of API requests handled at once, excess ones being shed
+const DEFAULT_CONCURRENCY_LIMIT: usize = 256;
 
 #[derive(Deserialize)]
-struct ExecuteRequest {
-    tx_base64: String,
+struct ProgramAccountsRequest {
+    program_id: String,
+    filters: Option<Vec<ProgramAccountsFilter>>,
+    /// Only return this range of the data of each account
+    data_slice: Option<DataSlice>,
 }
 
 #[derive(Deserialize)]
-struct SetLamportsRequest {
-    pubkey: String,
-    lamports: u64,
+struct CloneProgramAccountsRequest {
+    program_id: String,
+    filters: Option<Vec<ProgramAccountsFilter>>,
+    /// Maximum number of owned accounts cloned, 10000 by default
+    limit: Option<usize>,
 }
 
-#[derive(Deserialize)]
-struct GetAccountRequest {
-    pubkey: String,
+/// [`ApiResponse`] of a route mutating a fork, telling when the fork expires
+#[derive(Serialize)]
+struct ForkApiResponse<T> {
+    #[serde(flatten)]
+    response: ApiResponse<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_at: Option<DateTime<Utc>>,
+    /// Decoded error of a failed transaction
+    #[serde(skip_serializing_if = "Option::is_none")]
+    failure: Option<TransactionFailure>,
 }
 
-#[derive(Deserialize)]
-struct SetTokenBalanceRequest {
-    token_account: String,
-    mint: String,
-    owner: String,
-    amount: u64,
+impl<T> From<ApiResponse<T>> for ForkApiResponse<T> {
+    fn from(response: ApiResponse<T>) -> Self {
+        ForkApiResponse {
+            response,
+            expires_at: None,
+            failure: None,
+        }
+    }
 }
 
+impl<T> ForkApiResponse<T> {
+    /// Attaches the decoded error of a transaction which failed with `e`
+    fn with_failure(mut self, message: &VersionedMessage, e: &anyhow::Error) -> Self {
+        self.failure = e
+            .downcast_ref::<TransactionError>()
+            .map(|err| describe_failure(message, err));
+        self
+    }
+}
+
+/// Response of fork creation, with the balances of the accounts funded by
+/// the `fund` option
 #[derive(Serialize)]
-struct ApiResponse<T> {
-    success: bool,
-    data: Option<T>,
-    error: Option<String>,
+struct CreateForkResponse {
+    #[serde(flatten)]
+    response: ForkApiResponse<Uuid>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    funded: Vec<FundedAccount>,
+}
+
+impl From<ApiResponse<Uuid>> for CreateForkResponse {
+    fn from(response: ApiResponse<Uuid>) -> Self {
+        CreateForkResponse {
+            response: response.into(),
+            funded: Vec::new(),
+        }
+    }
+}
+
+/// Caps on the logs of a response, the server's ones unless overridden by
+/// the request
+fn log_limits(
+    manager: &RwLock<ForkManager>,
+    max_lines: Option<usize>,
+    max_bytes: Option<usize>,
+) -> LogLimits {
+    manager
+        .read_or_recover()
+        .log_limits()
+        .with_overrides(max_lines, max_bytes)
+}
+
+/// Wall-clock deadline of an execution, the server's one unless overridden
+/// by the request
+fn execution_timeout(manager: &RwLock<ForkManager>, timeout_ms: Option<u64>) -> Duration {
+    timeout_ms.map_or_else(
+        || manager.read_or_recover().execution_timeout(),
+        Duration::from_millis,
+    )
+}
+
+/// Runs a call locking a fork on the blocking pool, giving up on it after
+/// `timeout`.
+///
+/// LiteSVM can't be interrupted, so a call which times out keeps running in
+/// the background: the fork stays locked until it completes, which the
+/// compute budget bounds, and its effects are kept (a timed out transaction
+/// may still be executed). Only the request is released early, the call
+/// holding the request's `permit` until it completes.
+async fn run_with_timeout<T: Send + 'static>(
+    timeout: Duration,
+    permit: Option<ConcurrencyPermit>,
+    call: impl FnOnce() -> anyhow::Result<T> + Send + 'static,
+) -> anyhow::Result<T> {
+    let call = move || {
+        let _permit = permit;
+        call()
+    };
+    match time::timeout(timeout, tokio::task::spawn_blocking(call)).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => Err(anyhow::anyhow!("execution failed: {}", e)),
+        Err(_) => anyhow::bail!("execution timed out after {} ms", timeout.as_millis()),
+    }
+}
+
+/// Waits for the artificial delay of a fork, without holding any lock
+async fn artificial_delay(manager: &RwLock<ForkManager>, fork_id: &Uuid) {
+    let delay = manager.read_or_recover().artificial_delay(fork_id);
+    if !delay.is_zero() {
+        time::sleep(delay).await;
+    }
+}
+
+/// Adds the expiry time of a fork to a response, omitted if the fork
+/// doesn't exist
+fn with_expiry<T>(
+    manager: &RwLock<ForkManager>,
+    fork_id: &Uuid,
+    response: ApiResponse<T>,
+) -> ForkApiResponse<T> {
+    ForkApiResponse {
+        response,
+        failure: None,
+        expires_at: manager
+            .read_or_recover()
+            .get_fork(fork_id)
+            .map(|fork| fork.expires_at_utc()),
+    }
 }
 
 #[tokio::main]
 async fn main() {
     tracing_subscriber::fmt::init();
-    let manager = Arc::new(Mutex::new(ForkManager::new()));
+    let max_forks = std::env::var("MAX_FORKS")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_MAX_FORKS);
+    let history_capacity = std::env::var("HISTORY_CAPACITY")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_HISTORY_CAPACITY);
+    let execution_timeout = std::env::var("EXECUTION_TIMEOUT_MS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .map_or(DEFAULT_EXECUTION_TIMEOUT, Duration::from_millis);
+    // Accounts every fork is seeded with, for canned offline environments
+    let fixture = match std::env::var("FIXTURE_FILE") {
+        Ok(path) => {
+            let fixture = ForkManager::load_fixture(std::path::Path::new(&path))
+                .unwrap_or_else(|e| panic!("failed to load fixture {}: {:?}", path, e));
+            tracing::info!(accounts = fixture.len(), %path, "loaded fixture");
+            fixture
+        }
+        Err(_) => Vec::new(),
+    };
+    let manager = Arc::new(RwLock::new(
+        ForkManager::new()
+            .with_max_forks(max_forks)
+            .with_rpc_policy(RpcPolicy::from_env())
+            .with_log_limits(LogLimits::from_env())
+            .with_history_capacity(history_capacity)
+            .with_execution_timeout(execution_timeout)
+            .with_fixture(fixture)
+            .with_allowed_rpc_urls(allowed_rpc_urls()),
+    ));
 
     // clean up forks every if older than 15 minutes
+    let cleanup_interval = std::env::var("CLEANUP_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(DEFAULT_CLEANUP_INTERVAL_SECS);
     let cleanup_manager = Arc::clone(&manager);
     tokio::spawn(async move {
-        let mut interval = time::interval(Duration::from_secs(60));
+        let mut interval = time::interval(Duration::from_secs(cleanup_interval));
         loop {
             interval.tick().await;
-            if let Ok(mut mgr) = cleanup_manager.lock() {
-                mgr.cleanup_expired();
+            // Dropping forks may take a while, off the async runtime
+            let cleaning = Arc::clone(&cleanup_manager);
+            let cleaned = tokio::task::spawn_blocking(move || {
+                let mut mgr = cleaning.write_or_recover();
+                (mgr.cleanup_expired(), mgr.forks.len())
+            })
+            .await;
+            match cleaned {
+                Ok((swept, active_forks)) => tracing::info!(swept, active_forks, "cleanup ran"),
+                Err(e) => tracing::error!(error = ?e, "cleanup failed"),
             }
         }
     });
 
-    let app = Router::new()
-        .route("/forks", post(create_fork))
+    // Bodies over these limits are rejected with 413 before being buffered
+    let tx_body_limit = DefaultBodyLimit::max(body_limit("TX_BODY_LIMIT", DEFAULT_TX_BODY_LIMIT));
+    let setup_body_limit =
+        DefaultBodyLimit::max(body_limit("SETUP_BODY_LIMIT", DEFAULT_SETUP_BODY_LIMIT));
+
+    let api = Router::new()
+        .route("/health", get(health))
+        .route("/openapi.json", get(openapi_document))
+        .route("/forks", get(list_forks).post(create_fork))
+        .route("/forks/diff", post(diff_forks))
+        .route("/forks/delete_batch", post(delete_forks))
         .route("/forks/{id}", delete(delete_fork))
-        .route("/forks/{id}/execute", post(execute_transaction))
-        .route("/forks/{id}/simulate", post(simulate_transaction))
+        .route(
+            "/forks/{id}/execute",
+            post(execute_transaction).layer(tx_body_limit),
+        )
+        .route(
+            "/forks/{id}/send",
+            post(send_transaction).layer(tx_body_limit),
+        )
+        .route(
+            "/forks/{id}/simulate",
+            post(simulate_transaction).layer(tx_body_limit),
+        )
+        .route(
+            "/forks/{id}/simulate_and_match",
+            post(simulate_and_match).layer(tx_body_limit),
+        )
+        .route(
+            "/forks/{id}/simulate_compute",
+            post(simulate_compute).layer(tx_body_limit),
+        )
+        .route(
+            "/forks/{id}/estimate_fee",
+            post(estimate_fee).layer(tx_body_limit),
+        )
+        .route(
+            "/forks/{id}/decode",
+            post(decode_transaction_handler).layer(tx_body_limit),
+        )
+        .route(
+            "/forks/{id}/touched_accounts",
+            post(touched_accounts).layer(tx_body_limit),
+        )
         .route("/forks/{id}/set_lamports", post(set_lamports))
         .route("/forks/{id}/set_token_balance", post(set_token_balance))
+        .route("/forks/{id}/set_token_balances", post(set_token_balances))
+        .route("/forks/{id}/freeze_token", post(freeze_token))
+        .route(
+            "/forks/{id}/setup",
+            post(setup_fork).layer(setup_body_limit),
+        )
+        .route("/forks/{id}/set_token_delegate", post(set_token_delegate))
+        .route("/forks/{id}/create_mint", post(create_mint))
+        .route("/forks/{id}/create_lookup_table", post(create_lookup_table))
+        .route("/forks/{id}/extend_lookup_table", post(extend_lookup_table))
+        .route("/forks/{id}/find_pda", post(find_pda))
+        .route(
+            "/forks/{id}/associated_token_address",
+            post(associated_token_address),
+        )
+        .route("/forks/{id}/set_owner", post(set_owner))
+        .route("/forks/{id}/set_executable", post(set_executable))
+        .route("/forks/{id}/blockhash", get(get_blockhash))
+        .route("/forks/{id}/set_blockhash", post(set_blockhash))
+        .route("/forks/{id}/stats", get(get_stats))
+        .route("/forks/{id}/fees", get(get_fees))
+        .route("/forks/{id}/features", get(get_features))
+        .route("/forks/{id}/export", get(export_accounts))
+        .route(
+            "/forks/{id}/import",
+            post(import_accounts).layer(setup_body_limit),
+        )
+        .route(
+            "/forks/{id}/replay_history",
+            post(replay_history).layer(setup_body_limit),
+        )
+        .route("/forks/{id}/rent", post(set_rent))
+        .route("/forks/{id}/set_epoch", post(set_epoch))
+        .route("/forks/{id}/slot_hashes", get(get_slot_hashes))
+        .route("/forks/{id}/minimum_balance", get(minimum_balance))
+        .route(
+            "/forks/{id}/minimum_balance/token_account",
+            get(token_account_minimum_balance),
+        )
+        .route(
+            "/forks/{id}/minimum_balance/mint",
+            get(mint_minimum_balance),
+        )
         .route("/forks/{id}/get_account", post(get_account))
+        .route("/forks/{id}/accounts/{pubkey}/exists", get(account_exists))
+        .route(
+            "/forks/{id}/accounts/{pubkey}/token_balance",
+            get(get_token_balance),
+        )
+        .route("/forks/{id}/rpc", post(json_rpc))
+        .route("/forks/{id}/program_accounts", post(get_program_accounts))
+        .route(
+            "/forks/{id}/clone_program_accounts",
+            post(clone_program_accounts),
+        )
         .route(
             "/forks/{id}/get_executed_transactions",
             post(get_executed_transactions),
@@ -86,22 +406,1476 @@ async fn main() {
             "/forks/{id}/get_simulated_transactions",
             post(get_simulated_transactions),
         )
-        .with_state(manager);
+        .route("/forks/{id}/transactions/{signature}", get(get_transaction))
+        .layer(DefaultBodyLimit::max(body_limit(
+            "BODY_LIMIT",
+            DEFAULT_BODY_LIMIT,
+        )))
+        // Compressed when the client sends `Accept-Encoding`
+        .layer(CompressionLayer::new());
+    let concurrency_limit = std::env::var("CONCURRENCY_LIMIT")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|limit| *limit > 0)
+        .unwrap_or(DEFAULT_CONCURRENCY_LIMIT);
+    let api = with_load_shedding(api, concurrency_limit);
+
+    // Streams are kept out of the compression layer so events are flushed
+    // to the client as soon as they are sent
+    let streams = Router::new()
+        .route("/forks/{id}/accounts/{pubkey}/watch", get(watch_account))
+        .route("/forks/{id}/events", get(transaction_events))
+        .route("/forks/{id}/ws", get(websocket));
+
+    let app = api
+        .merge(streams)
+        .layer(cors_layer())
+        .with_state(Arc::clone(&manager));
+
+    let addr = SocketAddr::from(([127, 0, 0, 1], 8080));
+    tracing::info!("server running at {}", addr);
+    tracing::info!(
+        "cleanup task started - will run every {} seconds",
+        cleanup_interval
+    );
+    axum::serve(tokio::net::TcpListener::bind(addr).await.unwrap(), app)
+        .with_graceful_shutdown(shutdown_signal())
+        .await
+        .unwrap();
+
+    // In-flight requests have completed at this point
+    let mgr = manager.read_or_recover();
+    tracing::info!(active_forks = mgr.forks.len(), "shutting down");
+    if let Ok(path) = std::env::var("SNAPSHOT_ON_SHUTDOWN") {
+        match mgr.snapshot_to_file(std::path::Path::new(&path)) {
+            Ok(count) => tracing::info!(forks = count, %path, "snapshotted active forks"),
+            Err(e) => tracing::error!(error = ?e, %path, "failed to snapshot active forks"),
+        }
+    } else {
+        tracing::info!("discarding active forks, set SNAPSHOT_ON_SHUTDOWN to persist them");
+    }
+}
+
+/// Builds the CORS layer from the comma separated `ALLOWED_ORIGINS` env var,
+/// allowing every origin when it isn't set
+fn cors_layer() -> CorsLayer {
+    let origins = match std::env::var("ALLOWED_ORIGINS") {
+        Ok(origins) => AllowOrigin::list(
+            origins
+                .split(',')
+                .map(str::trim)
+                .filter(|origin| !origin.is_empty())
+                .filter_map(|origin| origin.parse().ok()),
+        ),
+        Err(_) => AllowOrigin::from(Any),
+    };
+    CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods(Any)
+        .allow_headers(Any)
+}
+
+/// Endpoints the `rpc_url` of requests may point to, from the comma
+/// separated `RPC_URL_ALLOWLIST` env var. None when it isn't set, so
+/// requests can't make the server reach arbitrary hosts.
+fn allowed_rpc_urls() -> Vec<String> {
+    std::env::var("RPC_URL_ALLOWLIST")
+        .map(|urls| {
+            urls.split(',')
+                .map(str::trim)
+                .filter(|url| !url.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Reads a body limit in bytes from the environment
+fn body_limit(var: &str, default: usize) -> usize {
+    std::env::var(var)
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(default)
+}
+
+/// Slot of the concurrency limit taken by a request. Work the request
+/// leaves running in the background keeps a clone, so the slot is only
+/// released once that work completes too.
+#[derive(Clone)]
+struct ConcurrencyPermit {
+    _permit: Arc<OwnedSemaphorePermit>,
+}
+
+/// Concurrency limit shared by every route, for work a request starts
+/// after it was answered (e.g. transactions sent over a WebSocket)
+#[derive(Clone)]
+struct ConcurrencyLimit(Arc<Semaphore>);
+
+impl ConcurrencyLimit {
+    /// Takes a slot of the limit, `None` if they are all taken
+    fn try_acquire(&self) -> Option<ConcurrencyPermit> {
+        let permit = Arc::clone(&self.0).try_acquire_owned().ok()?;
+        Some(ConcurrencyPermit {
+            _permit: Arc::new(permit),
+        })
+    }
+}
+
+/// Caps the number of requests `router` handles at once, answering excess
+/// ones with 503 right away instead of queueing them. The limit is shared by
+/// every route, and handlers get the [`ConcurrencyPermit`] of their request
+/// and the [`ConcurrencyLimit`] as extensions.
+fn with_load_shedding<S>(router: Router<S>, limit: usize) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    router.layer(middleware::from_fn_with_state(
+        Arc::new(Semaphore::new(limit)),
+        shed_load,
+    ))
+}
+
+async fn shed_load(
+    State(semaphore): State<Arc<Semaphore>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let limit = ConcurrencyLimit(semaphore);
+    let Some(permit) = limit.try_acquire() else {
+        return overloaded().into_response();
+    };
+    request.extensions_mut().insert(permit.clone());
+    request.extensions_mut().insert(limit);
+    let response = next.run(request).await;
+    drop(permit);
+    response
+}
+
+fn overloaded() -> (StatusCode, Json<ApiResponse<()>>) {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(ApiResponse {
+            success: false,
+            data: None,
+            error: Some("Server overloaded, retry later".into()),
+        }),
+    )
+}
+
+/// Resolves when the server receives SIGINT or SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    tracing::info!("shutdown signal received, waiting for in-flight requests");
+}
+
+/// Error returned when a submitted transaction can't be decoded
+#[derive(Debug)]
+enum DecodeError {
+    TooLarge(usize),
+    Base64(base64::DecodeError),
+    Bincode(bincode::Error),
+    NotATransaction(String),
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::TooLarge(len) => write!(
+                f,
+                "transaction too large: {} base64 characters, at most {} allowed",
+                len, MAX_TX_BASE64_LEN
+            ),
+            DecodeError::Base64(e) => write!(f, "not valid base64: {}", e),
+            DecodeError::Bincode(e) => write!(f, "not valid bincode: {}", e),
+            DecodeError::NotATransaction(e) => {
+                write!(f, "valid bincode but not a transaction: {}", e)
+            }
+        }
+    }
+}
+
+/// Decodes a base64 encoded, bincode serialized transaction.
+///
+/// The input is checked against the maximum transaction size before
+/// decoding, and bincode is bounded to the same size so a malicious length
+/// prefix can't make it allocate huge buffers.
+fn decode_transaction(tx_base64: &str) -> Result<VersionedTransaction, DecodeError> {
+    if tx_base64.len() > MAX_TX_BASE64_LEN {
+        return Err(DecodeError::TooLarge(tx_base64.len()));
+    }
+
+    let tx_bytes = engine::general_purpose::STANDARD
+        .decode(tx_base64)
+        .map_err(DecodeError::Base64)?;
+    let tx = bincode::DefaultOptions::new()
+        .with_limit(PACKET_DATA_SIZE as u64)
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .deserialize::<VersionedTransaction>(&tx_bytes)
+        .map_err(DecodeError::Bincode)?;
+    tx.sanitize()
+        .map_err(|e| DecodeError::NotATransaction(e.to_string()))?;
+    Ok(tx)
+}
+
+#[axum::debug_handler]
+async fn health(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+) -> Json<ApiResponse<HealthResponse>> {
+    let manager = manager.read_or_recover();
+    Json(ApiResponse {
+        success: true,
+        data: Some(HealthResponse {
+            active_forks: manager.forks.len(),
+            last_cleanup: manager.last_cleanup(),
+        }),
+        error: None,
+    })
+}
+
+/// OpenAPI description of the routes
+#[axum::debug_handler]
+async fn openapi_document() -> Json<serde_json::Value> {
+    Json(openapi::document())
+}
+
+#[axum::debug_handler]
+async fn create_fork(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Json<CreateForkResponse> {
+    // The body is optional, an empty one creates a fork with default options
+    let options = if body.is_empty() {
+        ForkOptions::default()
+    } else {
+        match serde_json::from_slice::<ForkOptions>(&body) {
+            Ok(options) => options,
+            Err(e) => {
+                return Json(
+                    ApiResponse {
+                        success: false,
+                        data: None,
+                        error: Some(format!("invalid fork options: {}", e)),
+                    }
+                    .into(),
+                );
+            }
+        }
+    };
+
+    // Mainnet state is fetched on a blocking thread without the manager lock
+    let idempotency_key = headers
+        .get("idempotency-key")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+
+    let fund: Vec<Pubkey> = options
+        .fund
+        .iter()
+        .filter_map(|entry| entry.pubkey.parse().ok())
+        .collect();
+    let creator = Arc::clone(&manager);
+    let created = tokio::task::spawn_blocking(move || {
+        ForkManager::create_fork_concurrently(&creator, options, idempotency_key.as_deref())
+    })
+    .await
+    .map_err(anyhow::Error::from)
+    .and_then(|result| result);
+
+    match created {
+        Ok(fork_id) => {
+            let funded = fund
+                .into_iter()
+                .filter_map(|pubkey| {
+                    let account = manager
+                        .read_or_recover()
+                        .get_account(&fork_id, pubkey)
+                        .ok()?;
+                    Some(FundedAccount {
+                        pubkey: pubkey.to_string(),
+                        lamports: account.lamports,
+                    })
+                })
+                .collect();
+            Json(CreateForkResponse {
+                response: with_expiry(
+                    &manager,
+                    &fork_id,
+                    ApiResponse {
+                        success: true,
+                        data: Some(fork_id),
+                        error: None,
+                    },
+                ),
+                funded,
+            })
+        }
+        Err(e) => Json(
+            ApiResponse {
+                success: false,
+                data: None,
+                error: Some(format!("{:?}", e)),
+            }
+            .into(),
+        ),
+    }
+}
+
+#[axum::debug_handler]
+async fn list_forks(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Query(query): Query<ListForksQuery>,
+) -> Json<ApiResponse<Vec<ForkSummary>>> {
+    let forks = manager.read_or_recover().list_forks(query.label.as_deref());
+    Json(ApiResponse {
+        success: true,
+        data: Some(forks),
+        error: None,
+    })
+}
+
+/// Replays the transaction log of a fork on a new fork
+#[axum::debug_handler]
+async fn replay_history(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    body: Bytes,
+) -> (StatusCode, Json<ForkApiResponse<ReplayResult>>) {
+    // The body is optional, an empty one replays the fork's history
+    let transactions = if body.is_empty() {
+        Ok(None)
+    } else {
+        serde_json::from_slice::<ReplayHistoryRequest>(&body)
+            .map_err(|e| format!("invalid replay request: {}", e))
+            .and_then(|req| {
+                req.transactions
+                    .map(|transactions| {
+                        transactions
+                            .iter()
+                            .map(|tx| decode_transaction(tx).map_err(|e| e.to_string()))
+                            .collect::<Result<Vec<_>, _>>()
+                    })
+                    .transpose()
+            })
+    };
+    let transactions = match transactions {
+        Ok(transactions) => transactions,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(
+                    ApiResponse {
+                        success: false,
+                        data: None,
+                        error: Some(e),
+                    }
+                    .into(),
+                ),
+            );
+        }
+    };
+
+    // Transactions may fetch accounts from mainnet, off the async runtime
+    let replaying = Arc::clone(&manager);
+    let replayed = tokio::task::spawn_blocking(move || {
+        ForkManager::replay_history(&replaying, &fork_id, transactions)
+    })
+    .await
+    .map_err(anyhow::Error::from)
+    .and_then(|result| result);
+
+    let response = match replayed {
+        Ok(result) => {
+            let replay_id = result.fork_id;
+            with_expiry(
+                &manager,
+                &replay_id,
+                ApiResponse {
+                    success: true,
+                    data: Some(result),
+                    error: None,
+                },
+            )
+        }
+        Err(e) => ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        }
+        .into(),
+    };
+    (StatusCode::OK, Json(response))
+}
+
+/// Lists the accounts which differ between two forks
+#[axum::debug_handler]
+async fn diff_forks(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Json(req): Json<DiffForksRequest>,
+) -> Json<ApiResponse<ForkDiff>> {
+    match manager
+        .read_or_recover()
+        .diff_forks(&req.left, &req.right, req.offset, req.limit)
+    {
+        Ok(diff) => Json(ApiResponse {
+            success: true,
+            data: Some(diff),
+            error: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        }),
+    }
+}
+
+/// Deletes a batch of forks, reporting the outcome for each id
+#[axum::debug_handler]
+async fn delete_forks(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Json(ids): Json<Vec<Uuid>>,
+) -> Json<ApiResponse<BatchDeleteResult>> {
+    let deleting = Arc::clone(&manager);
+    let deleted =
+        tokio::task::spawn_blocking(move || deleting.write_or_recover().delete_forks(&ids)).await;
+    Json(match deleted {
+        Ok(result) => ApiResponse {
+            success: true,
+            data: Some(result),
+            error: None,
+        },
+        Err(e) => ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        },
+    })
+}
+
+#[axum::debug_handler]
+async fn delete_fork(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+) -> Json<ApiResponse<String>> {
+    // Dropping a fork may take a while, off the async runtime
+    let deleting = Arc::clone(&manager);
+    let deleted =
+        tokio::task::spawn_blocking(move || deleting.write_or_recover().delete_fork(&fork_id))
+            .await;
+    match deleted {
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        }),
+        Ok(DeleteForkResult::Deleted) => Json(ApiResponse {
+            success: true,
+            data: Some(format!("Deleted fork {}", fork_id)),
+            error: None,
+        }),
+        Ok(DeleteForkResult::NotFound) => Json(ApiResponse {
+            success: false,
+            data: None,
+            error: Some("Fork not found".into()),
+        }),
+        Ok(DeleteForkResult::Expired) => Json(ApiResponse {
+            success: false,
+            data: None,
+            error: Some("Fork expired".into()),
+        }),
+    }
+}
+
+#[axum::debug_handler]
+async fn execute_transaction(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    permit: Option<Extension<ConcurrencyPermit>>,
+    Json(req): Json<ExecuteRequest>,
+) -> (StatusCode, Json<ForkApiResponse<ExecuteResponse>>) {
+    let tx = match decode_transaction(&req.tx_base64) {
+        Ok(tx) => tx,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(
+                    ApiResponse {
+                        success: false,
+                        data: None,
+                        error: Some(e.to_string()),
+                    }
+                    .into(),
+                ),
+            );
+        }
+    };
+
+    let message = tx.message.clone();
+    let limits = log_limits(&manager, req.log_max_lines, req.log_max_bytes);
+    // Executed without the manager lock, only the fork's is held
+    let executing = manager.read_or_recover().scoped(&fork_id);
+    let timeout = execution_timeout(&manager, req.timeout_ms);
+    let executed = run_with_timeout(timeout, permit.map(|Extension(p)| p), move || {
+        executing.execute_transaction_with_rpc(&fork_id, tx, req.rpc_url.as_deref())
+    })
+    .await;
+    let response = match executed {
+        Ok(mut result) => {
+            let compute_units = req
+                .compute_units
+                .then(|| instruction_compute_units(&result.logs));
+            limits.truncate(&mut result.logs);
+            with_expiry(
+                &manager,
+                &fork_id,
+                ApiResponse {
+                    success: true,
+                    data: Some(ExecuteResponse {
+                        compute_units,
+                        ..result.into()
+                    }),
+                    error: None,
+                },
+            )
+        }
+        Err(e) => with_expiry(
+            &manager,
+            &fork_id,
+            ApiResponse {
+                success: false,
+                data: None,
+                error: Some(format!("{:?}", e)),
+            },
+        )
+        .with_failure(&message, &e),
+    };
+    artificial_delay(&manager, &fork_id).await;
+    (StatusCode::OK, Json(response))
+}
+
+/// Simulates a transaction and executes it only if the simulation succeeded
+#[axum::debug_handler]
+async fn send_transaction(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    permit: Option<Extension<ConcurrencyPermit>>,
+    Json(req): Json<ExecuteRequest>,
+) -> (StatusCode, Json<ForkApiResponse<SendResult>>) {
+    let tx = match decode_transaction(&req.tx_base64) {
+        Ok(tx) => tx,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(
+                    ApiResponse {
+                        success: false,
+                        data: None,
+                        error: Some(e.to_string()),
+                    }
+                    .into(),
+                ),
+            );
+        }
+    };
+
+    let message = tx.message.clone();
+    let limits = log_limits(&manager, req.log_max_lines, req.log_max_bytes);
+    // Sent without the manager lock, only the fork's is held
+    let sending = manager.read_or_recover().scoped(&fork_id);
+    let timeout = execution_timeout(&manager, req.timeout_ms);
+    let sent = run_with_timeout(timeout, permit.map(|Extension(p)| p), move || {
+        sending.send_transaction_with_rpc(&fork_id, tx, req.rpc_url.as_deref())
+    })
+    .await;
+    let response = match sent {
+        Ok(mut result) => {
+            limits.truncate(&mut result.simulation.logs);
+            if let Some(execution) = &mut result.execution {
+                limits.truncate(&mut execution.logs);
+            }
+            let failure = result
+                .error
+                .as_ref()
+                .map(|err| describe_failure(&message, err));
+            let response = ApiResponse {
+                success: result.error.is_none(),
+                error: result.error.as_ref().map(|e| format!("{:?}", e)),
+                data: Some(result),
+            };
+            ForkApiResponse {
+                failure,
+                ..with_expiry(&manager, &fork_id, response)
+            }
+        }
+        Err(e) => with_expiry(
+            &manager,
+            &fork_id,
+            ApiResponse {
+                success: false,
+                data: None,
+                error: Some(format!("{:?}", e)),
+            },
+        ),
+    };
+    artificial_delay(&manager, &fork_id).await;
+    (StatusCode::OK, Json(response))
+}
+
+#[axum::debug_handler]
+async fn simulate_transaction(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    Json(req): Json<SimulateRequest>,
+) -> (StatusCode, Json<ForkApiResponse<SimulateResponse>>) {
+    let decoded = decode_transaction(&req.tx_base64)
+        .map_err(|e| e.to_string())
+        .and_then(|tx| {
+            let impersonate = req
+                .impersonate
+                .iter()
+                .map(|key| {
+                    key.parse::<Pubkey>()
+                        .map_err(|_| format!("Invalid pubkey {}", key))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok((tx, impersonate))
+        });
+    let (tx, impersonate) = match decoded {
+        Ok(decoded) => decoded,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(
+                    ApiResponse {
+                        success: false,
+                        data: None,
+                        error: Some(e),
+                    }
+                    .into(),
+                ),
+            );
+        }
+    };
+
+    let message = tx.message.clone();
+    let limits = log_limits(&manager, req.log_max_lines, req.log_max_bytes);
+    let response = match manager
+        .read_or_recover()
+        .simulate_transaction_detailed(
+            &fork_id,
+            tx,
+            SimulateOptions {
+                refresh_accounts: req.refresh_accounts,
+                allow_unsigned: req.allow_unsigned,
+                rpc_url: req.rpc_url,
+                impersonate,
+            },
+        )
+        .and_then(|result| result.map_err(|e| anyhow::Error::new(e.err)))
+    {
+        Ok(mut info) => {
+            let trace = req.trace.then(|| build_trace(&message, &info.meta));
+            let compute_units = req
+                .compute_units
+                .then(|| instruction_compute_units(&info.meta.logs));
+            limits.truncate(&mut info.meta.logs);
+            ApiResponse {
+                success: true,
+                data: Some(SimulateResponse {
+                    program_return: ReturnData::from_meta(&info.meta),
+                    meta: info.meta,
+                    trace,
+                    compute_units,
+                    impersonated: req.impersonate,
+                }),
+                error: None,
+            }
+            .into()
+        }
+        Err(e) => ForkApiResponse::from(ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        })
+        .with_failure(&message, &e),
+    };
+    artificial_delay(&manager, &fork_id).await;
+    (StatusCode::OK, Json(response))
+}
+
+/// Simulates a transaction and only reports whether it fits in its compute
+/// budget
+#[axum::debug_handler]
+async fn simulate_compute(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    Json(req): Json<ComputeFitRequest>,
+) -> (StatusCode, Json<ForkApiResponse<ComputeFit>>) {
+    let tx = match decode_transaction(&req.tx_base64) {
+        Ok(tx) => tx,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(
+                    ApiResponse {
+                        success: false,
+                        data: None,
+                        error: Some(e.to_string()),
+                    }
+                    .into(),
+                ),
+            );
+        }
+    };
+
+    let message = tx.message.clone();
+    let options = SimulateOptions {
+        allow_unsigned: req.allow_unsigned,
+        ..Default::default()
+    };
+    let response = match manager.read_or_recover().compute_fit(&fork_id, tx, options) {
+        Ok(fit) => ApiResponse {
+            success: true,
+            data: Some(fit),
+            error: None,
+        }
+        .into(),
+        Err(e) => ForkApiResponse::from(ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        })
+        .with_failure(&message, &e),
+    };
+    (StatusCode::OK, Json(response))
+}
+
+/// Simulates a transaction and reports which of its log lines match a
+/// pattern, including for failed simulations
+#[axum::debug_handler]
+async fn simulate_and_match(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    Json(req): Json<SimulateMatchRequest>,
+) -> (StatusCode, Json<ApiResponse<LogMatch>>) {
+    let decoded = decode_transaction(&req.tx_base64)
+        .map_err(|e| e.to_string())
+        .and_then(|tx| {
+            let matcher = LogMatcher::new(&req.pattern, req.regex)
+                .map_err(|e| format!("invalid pattern: {}", e))?;
+            Ok((tx, matcher))
+        });
+    let (tx, matcher) = match decoded {
+        Ok(decoded) => decoded,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse {
+                    success: false,
+                    data: None,
+                    error: Some(e),
+                }),
+            );
+        }
+    };
+
+    let response = match manager.read_or_recover().simulate_transaction_detailed(
+        &fork_id,
+        tx,
+        SimulateOptions::default(),
+    ) {
+        Ok(Ok(info)) => ApiResponse {
+            success: true,
+            data: Some(matcher.match_logs(info.meta.logs, None)),
+            error: None,
+        },
+        Ok(Err(failed)) => ApiResponse {
+            success: true,
+            data: Some(matcher.match_logs(failed.meta.logs, Some(failed.err.to_string()))),
+            error: None,
+        },
+        Err(e) => ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        },
+    };
+    (StatusCode::OK, Json(response))
+}
+
+#[axum::debug_handler]
+async fn estimate_fee(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    Json(req): Json<ExecuteRequest>,
+) -> (StatusCode, Json<ApiResponse<FeeEstimate>>) {
+    let tx = match decode_transaction(&req.tx_base64) {
+        Ok(tx) => tx,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse {
+                    success: false,
+                    data: None,
+                    error: Some(e.to_string()),
+                }),
+            );
+        }
+    };
+
+    let response = match manager.read_or_recover().estimate_fee(&fork_id, &tx) {
+        Ok(fee) => ApiResponse {
+            success: true,
+            data: Some(fee),
+            error: None,
+        },
+        Err(e) => ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        },
+    };
+    (StatusCode::OK, Json(response))
+}
+
+/// Fee parameters of the fork, to check fees computed client side
+#[axum::debug_handler]
+async fn get_fees(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+) -> Json<ApiResponse<FeeSchedule>> {
+    match manager.read_or_recover().fee_schedule(&fork_id) {
+        Ok(schedule) => Json(ApiResponse {
+            success: true,
+            data: Some(schedule),
+            error: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        }),
+    }
+}
+
+/// Decodes a transaction and breaks it down without executing it
+#[axum::debug_handler]
+async fn decode_transaction_handler(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    Json(req): Json<ExecuteRequest>,
+) -> (StatusCode, Json<ApiResponse<TransactionBreakdown>>) {
+    let tx = match decode_transaction(&req.tx_base64) {
+        Ok(tx) => tx,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse {
+                    success: false,
+                    data: None,
+                    error: Some(e.to_string()),
+                }),
+            );
+        }
+    };
+
+    let response = if manager.read_or_recover().get_fork(&fork_id).is_some() {
+        ApiResponse {
+            success: true,
+            data: Some(inspect_transaction(&tx)),
+            error: None,
+        }
+    } else {
+        ApiResponse {
+            success: false,
+            data: None,
+            error: Some("Fork not found".into()),
+        }
+    };
+    (StatusCode::OK, Json(response))
+}
+
+/// Lists the accounts a transaction reads and writes, without executing it
+#[axum::debug_handler]
+async fn touched_accounts(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    Json(req): Json<ExecuteRequest>,
+) -> (StatusCode, Json<ApiResponse<Vec<TouchedAccount>>>) {
+    let tx = match decode_transaction(&req.tx_base64) {
+        Ok(tx) => tx,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse {
+                    success: false,
+                    data: None,
+                    error: Some(e.to_string()),
+                }),
+            );
+        }
+    };
+
+    let response = match manager.read_or_recover().touched_accounts(&fork_id, &tx) {
+        Ok(accounts) => ApiResponse {
+            success: true,
+            data: Some(accounts),
+            error: None,
+        },
+        Err(e) => ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        },
+    };
+    (StatusCode::OK, Json(response))
+}
+
+#[axum::debug_handler]
+async fn set_lamports(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    Json(req): Json<SetLamportsRequest>,
+) -> Json<ForkApiResponse<String>> {
+    let pubkey = req.pubkey.parse::<Pubkey>().unwrap();
+
+    let response = match manager.read_or_recover().set_lamports(
+        &fork_id,
+        pubkey,
+        req.lamports,
+        req.allow_rent_paying,
+    ) {
+        Ok(_) => ApiResponse {
+            success: true,
+            data: Some(format!("Set lamports for {}", pubkey)),
+            error: None,
+        },
+        Err(e) => ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e.to_string())),
+        },
+    };
+    Json(with_expiry(&manager, &fork_id, response))
+}
+
+#[axum::debug_handler]
+async fn set_token_balance(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    Json(req): Json<SetTokenBalanceRequest>,
+) -> Json<ForkApiResponse<String>> {
+    let token_account = req.token_account.parse::<Pubkey>().unwrap();
+    let mint = req.mint.parse::<Pubkey>().unwrap();
+    let owner = req.owner.parse::<Pubkey>().unwrap();
+
+    let response = match manager.read_or_recover().set_token_balance(
+        &fork_id,
+        token_account,
+        mint,
+        owner,
+        req.amount,
+        TokenBalanceOptions {
+            decimals: req.decimals,
+            check_supply: req.check_supply,
+            lamports: req.lamports,
+        },
+    ) {
+        Ok(_) => ApiResponse {
+            success: true,
+            data: Some(format!("Set token balance for {}", token_account)),
+            error: None,
+        },
+        Err(e) => ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        },
+    };
+    Json(with_expiry(&manager, &fork_id, response))
+}
+
+/// Sets many token balances at once, returning the token accounts
+#[axum::debug_handler]
+async fn set_token_balances(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    Json(req): Json<Vec<TokenBalanceEntry>>,
+) -> Json<ForkApiResponse<Vec<String>>> {
+    let response = match manager.read_or_recover().set_token_balances(&fork_id, req) {
+        Ok(addresses) => ApiResponse {
+            success: true,
+            data: Some(addresses),
+            error: None,
+        },
+        Err(e) => ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        },
+    };
+    Json(with_expiry(&manager, &fork_id, response))
+}
+
+#[axum::debug_handler]
+async fn setup_fork(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    Json(req): Json<ForkSetup>,
+) -> Json<ForkApiResponse<SetupResult>> {
+    let response = match manager.read_or_recover().setup(&fork_id, req) {
+        Ok(result) => ApiResponse {
+            success: true,
+            data: Some(result),
+            error: None,
+        },
+        Err(e) => ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        },
+    };
+    Json(with_expiry(&manager, &fork_id, response))
+}
+
+#[axum::debug_handler]
+async fn freeze_token(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    Json(req): Json<FreezeTokenRequest>,
+) -> (StatusCode, Json<ForkApiResponse<String>>) {
+    let token_account = match req.token_account.parse::<Pubkey>() {
+        Ok(token_account) => token_account,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(
+                    ApiResponse {
+                        success: false,
+                        data: None,
+                        error: Some(format!("Invalid token account: {}", e)),
+                    }
+                    .into(),
+                ),
+            );
+        }
+    };
+
+    let response =
+        match manager
+            .read_or_recover()
+            .set_token_account_state(&fork_id, token_account, req.frozen)
+        {
+            Ok(_) => ApiResponse {
+                success: true,
+                data: Some(format!(
+                    "{} token account {}",
+                    if req.frozen { "Froze" } else { "Thawed" },
+                    token_account
+                )),
+                error: None,
+            },
+            Err(e) => ApiResponse {
+                success: false,
+                data: None,
+                error: Some(format!("{:?}", e)),
+            },
+        };
+    (
+        StatusCode::OK,
+        Json(with_expiry(&manager, &fork_id, response)),
+    )
+}
+
+#[axum::debug_handler]
+async fn set_token_delegate(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    Json(req): Json<SetTokenDelegateRequest>,
+) -> (StatusCode, Json<ForkApiResponse<String>>) {
+    let token_account = match req.token_account.parse::<Pubkey>() {
+        Ok(token_account) => token_account,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(
+                    ApiResponse {
+                        success: false,
+                        data: None,
+                        error: Some(format!("Invalid token account: {}", e)),
+                    }
+                    .into(),
+                ),
+            );
+        }
+    };
+    let delegate = match req.delegate.parse::<Pubkey>() {
+        Ok(delegate) => delegate,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(
+                    ApiResponse {
+                        success: false,
+                        data: None,
+                        error: Some(format!("Invalid delegate: {}", e)),
+                    }
+                    .into(),
+                ),
+            );
+        }
+    };
+
+    let response = match manager.read_or_recover().set_token_delegate(
+        &fork_id,
+        token_account,
+        delegate,
+        req.amount,
+    ) {
+        Ok(_) => ApiResponse {
+            success: true,
+            data: Some(format!("Set token delegate for {}", token_account)),
+            error: None,
+        },
+        Err(e) => ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        },
+    };
+    (
+        StatusCode::OK,
+        Json(with_expiry(&manager, &fork_id, response)),
+    )
+}
+
+/// Derives a program address from its seeds
+#[axum::debug_handler]
+async fn find_pda(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    Json(req): Json<FindPdaRequest>,
+) -> (StatusCode, Json<ApiResponse<ProgramAddress>>) {
+    let decoded = req
+        .program_id
+        .parse::<Pubkey>()
+        .map_err(|_| format!("Invalid program id {}", req.program_id))
+        .and_then(|program_id| {
+            let seeds = req
+                .seeds
+                .iter()
+                .enumerate()
+                .map(|(index, seed)| {
+                    engine::general_purpose::STANDARD
+                        .decode(seed)
+                        .map_err(|e| format!("Seed {} is not valid base64: {}", index, e))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok((program_id, seeds))
+        });
+    let (program_id, seeds) = match decoded {
+        Ok(decoded) => decoded,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse {
+                    success: false,
+                    data: None,
+                    error: Some(e),
+                }),
+            );
+        }
+    };
+
+    let response = match manager
+        .read_or_recover()
+        .find_pda(&fork_id, &program_id, &seeds)
+    {
+        Ok(pda) => ApiResponse {
+            success: true,
+            data: Some(pda),
+            error: None,
+        },
+        Err(e) => ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        },
+    };
+    (StatusCode::OK, Json(response))
+}
+
+/// Derives the associated token account of an owner for a mint
+#[axum::debug_handler]
+async fn associated_token_address(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    Json(req): Json<AssociatedTokenAddressRequest>,
+) -> (StatusCode, Json<ApiResponse<ProgramAddress>>) {
+    let parse = |key: &str| {
+        key.parse::<Pubkey>()
+            .map_err(|_| format!("Invalid pubkey {}", key))
+    };
+    let decoded = (|| {
+        let token_program = req.token_program.as_deref().map(parse).transpose()?;
+        Ok((parse(&req.owner)?, parse(&req.mint)?, token_program))
+    })();
+    let (owner, mint, token_program) = match decoded {
+        Ok(decoded) => decoded,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse {
+                    success: false,
+                    data: None,
+                    error: Some(e),
+                }),
+            );
+        }
+    };
+
+    let response = match manager.read_or_recover().associated_token_address(
+        &fork_id,
+        &owner,
+        &mint,
+        token_program,
+    ) {
+        Ok(ata) => ApiResponse {
+            success: true,
+            data: Some(ata),
+            error: None,
+        },
+        Err(e) => ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        },
+    };
+    (StatusCode::OK, Json(response))
+}
+
+#[axum::debug_handler]
+async fn create_mint(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    Json(req): Json<CreateMintRequest>,
+) -> Json<ForkApiResponse<String>> {
+    let parse = |key: &str| {
+        key.parse::<Pubkey>()
+            .map_err(|_| anyhow::anyhow!("Invalid pubkey {}", key))
+    };
+    let created = (|| {
+        let mint = req.mint.as_deref().map(parse).transpose()?;
+        let mint_authority = parse(&req.mint_authority)?;
+        let freeze_authority = req.freeze_authority.as_deref().map(parse).transpose()?;
+        manager.read_or_recover().create_mint(
+            &fork_id,
+            mint,
+            req.decimals,
+            mint_authority,
+            freeze_authority,
+        )
+    })();
+
+    let response = match created {
+        Ok(mint) => ApiResponse {
+            success: true,
+            data: Some(mint.to_string()),
+            error: None,
+        },
+        Err(e) => ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        },
+    };
+    Json(with_expiry(&manager, &fork_id, response))
+}
+
+/// Creates an address lookup table for v0 transactions
+#[axum::debug_handler]
+async fn create_lookup_table(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    Json(req): Json<CreateLookupTableRequest>,
+) -> Json<ForkApiResponse<String>> {
+    let parse = |key: &str| {
+        key.parse::<Pubkey>()
+            .map_err(|_| anyhow::anyhow!("Invalid pubkey {}", key))
+    };
+    let created = (|| {
+        let address = req.address.as_deref().map(parse).transpose()?;
+        let authority = req.authority.as_deref().map(parse).transpose()?;
+        let addresses = req
+            .addresses
+            .iter()
+            .map(|key| parse(key))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        manager
+            .read_or_recover()
+            .create_lookup_table(&fork_id, address, authority, addresses)
+    })();
+
+    let response = match created {
+        Ok(address) => ApiResponse {
+            success: true,
+            data: Some(address.to_string()),
+            error: None,
+        },
+        Err(e) => ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        },
+    };
+    Json(with_expiry(&manager, &fork_id, response))
+}
+
+/// Appends addresses to a lookup table, returning all of its addresses
+#[axum::debug_handler]
+async fn extend_lookup_table(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    Json(req): Json<ExtendLookupTableRequest>,
+) -> Json<ForkApiResponse<Vec<String>>> {
+    let parse = |key: &str| {
+        key.parse::<Pubkey>()
+            .map_err(|_| anyhow::anyhow!("Invalid pubkey {}", key))
+    };
+    let extended = (|| {
+        let lookup_table = parse(&req.lookup_table)?;
+        let addresses = req
+            .addresses
+            .iter()
+            .map(|key| parse(key))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        manager
+            .read_or_recover()
+            .extend_lookup_table(&fork_id, lookup_table, addresses)
+    })();
+
+    let response = match extended {
+        Ok(addresses) => ApiResponse {
+            success: true,
+            data: Some(addresses.iter().map(|key| key.to_string()).collect()),
+            error: None,
+        },
+        Err(e) => ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        },
+    };
+    Json(with_expiry(&manager, &fork_id, response))
+}
+
+/// Reassigns an account to another program, keeping the rest of it
+#[axum::debug_handler]
+async fn set_owner(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    Json(req): Json<SetOwnerRequest>,
+) -> Json<ForkApiResponse<String>> {
+    let parse = |key: &str| {
+        key.parse::<Pubkey>()
+            .map_err(|_| anyhow::anyhow!("Invalid pubkey {}", key))
+    };
+    let updated = (|| {
+        let pubkey = parse(&req.pubkey)?;
+        let owner = parse(&req.owner)?;
+        manager
+            .read_or_recover()
+            .set_account_owner(&fork_id, pubkey, owner)
+    })();
+
+    let response = match updated {
+        Ok(_) => ApiResponse {
+            success: true,
+            data: Some(format!("Set owner of {} to {}", req.pubkey, req.owner)),
+            error: None,
+        },
+        Err(e) => ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        },
+    };
+    Json(with_expiry(&manager, &fork_id, response))
+}
+
+/// Flips the `executable` flag of an account
+#[axum::debug_handler]
+async fn set_executable(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    Json(req): Json<SetExecutableRequest>,
+) -> Json<ForkApiResponse<String>> {
+    let updated = req
+        .pubkey
+        .parse::<Pubkey>()
+        .map_err(|_| anyhow::anyhow!("Invalid pubkey {}", req.pubkey))
+        .and_then(|pubkey| {
+            manager
+                .read_or_recover()
+                .set_executable(&fork_id, pubkey, req.executable)
+        });
 
-    let addr = SocketAddr::from(([127, 0, 0, 1], 8080));
-    println!("server running at {}", addr);
-    println!("Cleanup task started - will run every 60 seconds");
-    axum::serve(tokio::net::TcpListener::bind(addr).await.unwrap(), app)
-        .await
-        .unwrap();
+    let response = match updated {
+        Ok(()) => ApiResponse {
+            success: true,
+            data: Some(format!(
+                "Marked {} {}",
+                req.pubkey,
+                if req.executable {
+                    "executable"
+                } else {
+                    "not executable"
+                }
+            )),
+            error: None,
+        },
+        Err(e) => ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        },
+    };
+    Json(with_expiry(&manager, &fork_id, response))
 }
 
 #[axum::debug_handler]
-async fn create_fork(State(manager): State<Arc<Mutex<ForkManager>>>) -> Json<ApiResponse<Uuid>> {
-    match manager.lock().unwrap().create_fork() {
-        Ok(fork_id) => Json(ApiResponse {
+async fn get_blockhash(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+) -> Json<ApiResponse<BlockhashResponse>> {
+    match manager.read_or_recover().get_blockhash(&fork_id) {
+        Ok((blockhash, slot)) => Json(ApiResponse {
             success: true,
-            data: Some(fork_id),
+            data: Some(BlockhashResponse {
+                blockhash: blockhash.to_string(),
+                slot,
+            }),
             error: None,
         }),
         Err(e) => Json(ApiResponse {
@@ -113,40 +1887,49 @@ async fn create_fork(State(manager): State<Arc<Mutex<ForkManager>>>) -> Json<Api
 }
 
 #[axum::debug_handler]
-async fn delete_fork(
-    State(manager): State<Arc<Mutex<ForkManager>>>,
+async fn set_blockhash(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
     Path(fork_id): Path<Uuid>,
-) -> Json<ApiResponse<String>> {
-    if manager.lock().unwrap().delete_fork(&fork_id) {
-        Json(ApiResponse {
+    Json(req): Json<SetBlockhashRequest>,
+) -> Json<ForkApiResponse<String>> {
+    let blockhash = match req.blockhash.parse::<Hash>() {
+        Ok(blockhash) => blockhash,
+        Err(e) => {
+            return Json(
+                ApiResponse {
+                    success: false,
+                    data: None,
+                    error: Some(format!("{:?}", e)),
+                }
+                .into(),
+            );
+        }
+    };
+
+    let response = match manager.read_or_recover().set_blockhash(&fork_id, blockhash) {
+        Ok(_) => ApiResponse {
             success: true,
-            data: Some(format!("Deleted fork {}", fork_id)),
+            data: Some(format!("Set blockhash {} for fork {}", blockhash, fork_id)),
             error: None,
-        })
-    } else {
-        Json(ApiResponse {
+        },
+        Err(e) => ApiResponse {
             success: false,
             data: None,
-            error: Some("Fork not found".into()),
-        })
-    }
+            error: Some(format!("{:?}", e)),
+        },
+    };
+    Json(with_expiry(&manager, &fork_id, response))
 }
 
 #[axum::debug_handler]
-async fn execute_transaction(
-    State(manager): State<Arc<Mutex<ForkManager>>>,
+async fn get_stats(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
     Path(fork_id): Path<Uuid>,
-    Json(req): Json<ExecuteRequest>,
-) -> Json<ApiResponse<TransactionMetadata>> {
-    let tx_bytes = engine::general_purpose::STANDARD
-        .decode(&req.tx_base64)
-        .unwrap();
-    let tx: VersionedTransaction = bincode::deserialize(&tx_bytes).unwrap();
-
-    match manager.lock().unwrap().execute_transaction(&fork_id, tx) {
-        Ok(result) => Json(ApiResponse {
+) -> Json<ApiResponse<ForkStats>> {
+    match manager.read_or_recover().get_stats(&fork_id) {
+        Ok(stats) => Json(ApiResponse {
             success: true,
-            data: Some(result),
+            data: Some(stats),
             error: None,
         }),
         Err(e) => Json(ApiResponse {
@@ -158,20 +1941,14 @@ async fn execute_transaction(
 }
 
 #[axum::debug_handler]
-async fn simulate_transaction(
-    State(manager): State<Arc<Mutex<ForkManager>>>,
+async fn get_features(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
     Path(fork_id): Path<Uuid>,
-    Json(req): Json<ExecuteRequest>,
-) -> Json<ApiResponse<TransactionMetadata>> {
-    let tx_bytes = engine::general_purpose::STANDARD
-        .decode(&req.tx_base64)
-        .unwrap();
-    let tx: VersionedTransaction = bincode::deserialize(&tx_bytes).unwrap();
-
-    match manager.lock().unwrap().simulate_transaction(&fork_id, tx) {
-        Ok(info) => Json(ApiResponse {
+) -> Json<ApiResponse<Vec<ActiveFeature>>> {
+    match manager.read_or_recover().get_features(&fork_id) {
+        Ok(features) => Json(ApiResponse {
             success: true,
-            data: Some(info.meta),
+            data: Some(features),
             error: None,
         }),
         Err(e) => Json(ApiResponse {
@@ -183,51 +1960,123 @@ async fn simulate_transaction(
 }
 
 #[axum::debug_handler]
-async fn set_lamports(
-    State(manager): State<Arc<Mutex<ForkManager>>>,
+async fn set_rent(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
     Path(fork_id): Path<Uuid>,
-    Json(req): Json<SetLamportsRequest>,
-) -> Json<ApiResponse<String>> {
-    let pubkey = req.pubkey.parse::<Pubkey>().unwrap();
+    Json(req): Json<SetRentRequest>,
+) -> Json<ForkApiResponse<String>> {
+    let response = match manager.read_or_recover().set_rent(
+        &fork_id,
+        req.lamports_per_byte_year,
+        req.exemption_threshold,
+        req.burn_percent,
+    ) {
+        Ok(_) => ApiResponse {
+            success: true,
+            data: Some(format!("Set rent for fork {}", fork_id)),
+            error: None,
+        },
+        Err(e) => ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        },
+    };
+    Json(with_expiry(&manager, &fork_id, response))
+}
 
-    match manager
-        .lock()
-        .unwrap()
-        .set_lamports(&fork_id, pubkey, req.lamports)
-    {
-        Ok(_) => Json(ApiResponse {
+/// Moves the fork's clock to another epoch
+#[axum::debug_handler]
+async fn set_epoch(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    Json(req): Json<SetEpochRequest>,
+) -> Json<ForkApiResponse<EpochInfo>> {
+    let response = match manager.read_or_recover().set_epoch(&fork_id, req.epoch) {
+        Ok(info) => ApiResponse {
             success: true,
-            data: Some(format!("Set lamports for {}", pubkey)),
+            data: Some(info),
+            error: None,
+        },
+        Err(e) => ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        },
+    };
+    Json(with_expiry(&manager, &fork_id, response))
+}
+
+/// Current entries of the fork's `SlotHashes` sysvar, most recent first
+#[axum::debug_handler]
+async fn get_slot_hashes(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    Query(query): Query<SlotHashesQuery>,
+) -> Json<ApiResponse<Vec<SlotHashEntry>>> {
+    match manager.read_or_recover().slot_hashes(&fork_id, query.limit) {
+        Ok(entries) => Json(ApiResponse {
+            success: true,
+            data: Some(entries),
             error: None,
         }),
         Err(e) => Json(ApiResponse {
             success: false,
             data: None,
-            error: Some(format!("{:?}", e.to_string())),
+            error: Some(format!("{:?}", e)),
         }),
     }
 }
 
+/// Serves Solana JSON-RPC account reads, accepting single requests as well
+/// as batches
 #[axum::debug_handler]
-async fn set_token_balance(
-    State(manager): State<Arc<Mutex<ForkManager>>>,
+async fn json_rpc(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
     Path(fork_id): Path<Uuid>,
-    Json(req): Json<SetTokenBalanceRequest>,
-) -> Json<ApiResponse<String>> {
-    let token_account = req.token_account.parse::<Pubkey>().unwrap();
-    let mint = req.mint.parse::<Pubkey>().unwrap();
-    let owner = req.owner.parse::<Pubkey>().unwrap();
+    body: Bytes,
+) -> Json<serde_json::Value> {
+    Json(jsonrpc::handle(&manager.read_or_recover(), &fork_id, &body))
+}
 
-    match manager.lock().unwrap().set_token_balance(
-        &fork_id,
-        token_account,
-        mint,
-        owner,
-        req.amount,
-    ) {
-        Ok(_) => Json(ApiResponse {
+#[axum::debug_handler]
+async fn minimum_balance(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    Query(query): Query<MinimumBalanceQuery>,
+) -> Json<ApiResponse<u64>> {
+    minimum_balance_of(&manager, &fork_id, query.data_len)
+}
+
+/// Rent-exempt minimum balance of an SPL token account
+#[axum::debug_handler]
+async fn token_account_minimum_balance(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+) -> Json<ApiResponse<u64>> {
+    minimum_balance_of(&manager, &fork_id, TokenAccount::LEN)
+}
+
+/// Rent-exempt minimum balance of an SPL mint
+#[axum::debug_handler]
+async fn mint_minimum_balance(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+) -> Json<ApiResponse<u64>> {
+    minimum_balance_of(&manager, &fork_id, Mint::LEN)
+}
+
+/// Helper function answering with the rent-exempt minimum balance of an
+/// account holding `data_len` bytes
+fn minimum_balance_of(
+    manager: &RwLock<ForkManager>,
+    fork_id: &Uuid,
+    data_len: usize,
+) -> Json<ApiResponse<u64>> {
+    match manager.read_or_recover().minimum_balance(fork_id, data_len) {
+        Ok(lamports) => Json(ApiResponse {
             success: true,
-            data: Some(format!("Set token balance for {}", token_account)),
+            data: Some(lamports),
             error: None,
         }),
         Err(e) => Json(ApiResponse {
@@ -240,15 +2089,87 @@ async fn set_token_balance(
 
 #[axum::debug_handler]
 async fn get_account(
-    State(manager): State<Arc<Mutex<ForkManager>>>,
+    State(manager): State<Arc<RwLock<ForkManager>>>,
     Path(fork_id): Path<Uuid>,
+    Query(query): Query<AccountReadQuery>,
     Json(req): Json<GetAccountRequest>,
-) -> Json<ApiResponse<Account>> {
+) -> Json<ApiResponse<UiAccount>> {
     let pubkey = req.pubkey.parse::<Pubkey>().unwrap();
-    match manager.lock().unwrap().get_account(&fork_id, pubkey) {
+    let manager = manager.read_or_recover();
+    let account = if query.local_only {
+        manager.get_local_account(&fork_id, pubkey)
+    } else {
+        manager.get_account_with_rpc(&fork_id, pubkey, req.rpc_url.as_deref())
+    }
+    .and_then(|mut account| {
+        if let Some(slice) = req.data_slice {
+            slice_account_data(&mut account, slice)?;
+        }
+        Ok(account)
+    });
+    match account {
         Ok(result) => Json(ApiResponse {
             success: true,
-            data: Some(result),
+            data: Some(encode_account(result, query.encoding)),
+            error: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        }),
+    }
+}
+
+/// Reads the balance of a token account
+#[axum::debug_handler]
+async fn get_token_balance(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path((fork_id, pubkey)): Path<(Uuid, String)>,
+) -> Json<ApiResponse<TokenBalance>> {
+    let balance = pubkey
+        .parse::<Pubkey>()
+        .map_err(anyhow::Error::from)
+        .and_then(|pubkey| {
+            manager
+                .read_or_recover()
+                .get_token_balance(&fork_id, pubkey)
+        });
+
+    match balance {
+        Ok(balance) => Json(ApiResponse {
+            success: true,
+            data: Some(balance),
+            error: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        }),
+    }
+}
+
+/// Tells whether an account exists in the fork
+#[axum::debug_handler]
+async fn account_exists(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path((fork_id, pubkey)): Path<(Uuid, String)>,
+    Query(query): Query<AccountExistsQuery>,
+) -> Json<ApiResponse<bool>> {
+    let exists = pubkey
+        .parse::<Pubkey>()
+        .map_err(anyhow::Error::from)
+        .and_then(|pubkey| {
+            manager
+                .read_or_recover()
+                .account_exists(&fork_id, pubkey, query.local_only)
+        });
+
+    match exists {
+        Ok(exists) => Json(ApiResponse {
+            success: true,
+            data: Some(exists),
             error: None,
         }),
         Err(e) => Json(ApiResponse {
@@ -259,12 +2180,438 @@ async fn get_account(
     }
 }
 
+/// Upgrades to a WebSocket serving the JSON-RPC methods of the fork and
+/// `sendTransaction` with its status notification
+#[axum::debug_handler]
+async fn websocket(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    limit: Option<Extension<ConcurrencyLimit>>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    let limit = limit.map(|Extension(limit)| limit);
+    upgrade.on_upgrade(move |socket| ws::handle_socket(socket, manager, fork_id, limit))
+}
+
+/// Streams the account as Server-Sent Events, first its current state and
+/// then every time it changes, until the fork expires
+#[axum::debug_handler]
+async fn watch_account(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path((fork_id, pubkey)): Path<(Uuid, String)>,
+    Query(query): Query<AccountReadQuery>,
+) -> Response {
+    let watch = pubkey
+        .parse::<Pubkey>()
+        .map_err(anyhow::Error::from)
+        .and_then(|pubkey| manager.read_or_recover().watch_account(&fork_id, pubkey));
+
+    match watch {
+        Ok(stream) => Sse::new(stream.map(move |account| {
+            Event::default().json_data(account.map(|acc| encode_account(acc, query.encoding)))
+        }))
+        .keep_alive(KeepAlive::default())
+        .into_response(),
+        Err(e) => Json(ApiResponse::<()> {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        })
+        .into_response(),
+    }
+}
+
+/// Content type of newline delimited JSON, one account per line
+const NDJSON: &str = "application/x-ndjson";
+
+/// Whether a header lists the NDJSON content type
+fn is_ndjson(headers: &HeaderMap, name: header::HeaderName) -> bool {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(',').any(|v| v.trim().starts_with(NDJSON)))
+}
+
+/// Size of the NDJSON lines parsed before their accounts are imported
+const IMPORT_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+/// Reads NDJSON accounts as the body arrives, a chunk of lines at a time,
+/// so only a chunk and the line being received are held in memory. A
+/// line can't be longer than `max_line` bytes.
+struct NdjsonAccounts {
+    data: BodyDataStream,
+    buffer: Vec<u8>,
+    line: usize,
+    max_line: usize,
+    done: bool,
+}
+
+impl NdjsonAccounts {
+    fn new(body: Body, max_line: usize) -> Self {
+        NdjsonAccounts {
+            data: body.into_data_stream(),
+            buffer: Vec::new(),
+            line: 0,
+            max_line,
+            done: false,
+        }
+    }
+
+    /// Next chunk of accounts, `None` once the body is consumed
+    async fn next_chunk(&mut self) -> anyhow::Result<Option<Vec<ExportedAccount>>> {
+        let mut accounts = Vec::new();
+        let mut size = 0;
+        while size < IMPORT_CHUNK_SIZE && !self.done {
+            match self.data.next().await {
+                Some(bytes) => self.buffer.extend_from_slice(&bytes?),
+                // The last line may not end with a newline
+                None => {
+                    self.done = true;
+                    self.buffer.push(b'\n');
+                }
+            }
+
+            let mut start = 0;
+            while let Some(end) = self.buffer[start..].iter().position(|byte| *byte == b'\n') {
+                let line = &self.buffer[start..start + end];
+                start += end + 1;
+                self.line += 1;
+                if line.trim_ascii().is_empty() {
+                    continue;
+                }
+                accounts.push(serde_json::from_slice(line).map_err(|e| {
+                    anyhow::anyhow!("Invalid account on line {}: {}", self.line, e)
+                })?);
+                size += line.len();
+            }
+            self.buffer.drain(..start);
+            if self.buffer.len() > self.max_line {
+                anyhow::bail!(
+                    "Line {} is longer than {} bytes",
+                    self.line + 1,
+                    self.max_line
+                );
+            }
+        }
+        Ok((!accounts.is_empty()).then_some(accounts))
+    }
+}
+
+/// Streams the accounts of a fork one account at a time, as a JSON array or
+/// as NDJSON when the client accepts `application/x-ndjson`
+#[axum::debug_handler]
+async fn export_accounts(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Response {
+    let accounts = match manager.read_or_recover().export_accounts_stream(&fork_id) {
+        Ok(accounts) => accounts,
+        Err(e) => {
+            return Json(ApiResponse::<()> {
+                success: false,
+                data: None,
+                error: Some(format!("{:?}", e)),
+            })
+            .into_response();
+        }
+    };
+
+    if is_ndjson(&headers, header::ACCEPT) {
+        let lines = accounts
+            .map(|account| format!("{}\n", serde_json::to_string(&account).unwrap()))
+            .map(Ok::<_, Infallible>);
+        return ([(header::CONTENT_TYPE, NDJSON)], Body::from_stream(lines)).into_response();
+    }
+
+    let elements = accounts.enumerate().map(|(i, account)| {
+        let separator = if i == 0 { "" } else { "," };
+        format!("{}{}", separator, serde_json::to_string(&account).unwrap())
+    });
+    let body = stream::once(async { "[".to_string() })
+        .chain(elements)
+        .chain(stream::once(async { "]".to_string() }))
+        .map(Ok::<_, Infallible>);
+
+    (
+        [(header::CONTENT_TYPE, "application/json")],
+        Body::from_stream(body),
+    )
+        .into_response()
+}
+
+/// Imports accounts sent as a JSON array, all or nothing and within the
+/// setup body limit, or streamed as NDJSON with
+/// `Content-Type: application/x-ndjson`.
+///
+/// NDJSON bodies aren't limited in size: their accounts are imported as
+/// they arrive, in chunks which are each all or nothing, so a failure
+/// leaves the chunks before it imported.
+#[axum::debug_handler]
+async fn import_accounts(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    request: Request,
+) -> (StatusCode, Json<ForkApiResponse<usize>>) {
+    let failure = |status, error| {
+        (
+            status,
+            Json(
+                ApiResponse {
+                    success: false,
+                    data: None,
+                    error: Some(error),
+                }
+                .into(),
+            ),
+        )
+    };
+    // Imported without the manager lock, only the fork's is held
+    let importing = manager.read_or_recover().scoped(&fork_id);
+
+    let imported = if is_ndjson(request.headers(), header::CONTENT_TYPE) {
+        let max_line = body_limit("SETUP_BODY_LIMIT", DEFAULT_SETUP_BODY_LIMIT);
+        let mut accounts = NdjsonAccounts::new(request.into_body(), max_line);
+        let partial = |e: anyhow::Error, imported| match imported {
+            0 => e,
+            imported => e.context(format!("{} accounts were imported", imported)),
+        };
+        let mut imported = 0;
+        loop {
+            let chunk = match accounts.next_chunk().await {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => break Ok(imported),
+                // Nothing was imported, as for an invalid JSON array
+                Err(e) if imported == 0 => {
+                    return failure(StatusCode::BAD_REQUEST, e.to_string());
+                }
+                Err(e) => break Err(partial(e, imported)),
+            };
+            match importing.import_accounts(&fork_id, chunk) {
+                Ok(count) => imported += count,
+                Err(e) => break Err(partial(e, imported)),
+            }
+        }
+    } else {
+        let accounts = match Bytes::from_request(request, &()).await {
+            Ok(body) => serde_json::from_slice::<Vec<ExportedAccount>>(&body),
+            Err(rejection) => return failure(rejection.status(), rejection.body_text()),
+        };
+        match accounts {
+            Ok(accounts) => importing.import_accounts(&fork_id, accounts),
+            Err(e) => return failure(StatusCode::BAD_REQUEST, e.to_string()),
+        }
+    };
+
+    let response = match imported {
+        Ok(count) => ApiResponse {
+            success: true,
+            data: Some(count),
+            error: None,
+        },
+        Err(e) => ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        },
+    };
+    (
+        StatusCode::OK,
+        Json(with_expiry(&manager, &fork_id, response)),
+    )
+}
+
+/// Streams executed/simulated transactions as Server-Sent Events. Clients
+/// reconnecting with `Last-Event-ID` receive the recent events they missed.
+#[axum::debug_handler]
+async fn transaction_events(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    Query(query): Query<TransactionEventsQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+
+    let events = manager.read_or_recover().transaction_events(
+        &fork_id,
+        last_event_id,
+        query.account_changes,
+    );
+    match events {
+        Ok(stream) => Sse::new(stream.map(|event| {
+            Event::default()
+                .id(event.id.to_string())
+                .event("transaction")
+                .json_data(event)
+        }))
+        .keep_alive(KeepAlive::default())
+        .into_response(),
+        Err(e) => Json(ApiResponse::<()> {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        })
+        .into_response(),
+    }
+}
+
+#[axum::debug_handler]
+async fn get_program_accounts(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    Json(req): Json<ProgramAccountsRequest>,
+) -> (StatusCode, Json<ApiResponse<Vec<ProgramAccount>>>) {
+    let program_id = match req.program_id.parse::<Pubkey>() {
+        Ok(program_id) => program_id,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Invalid program id: {}", e)),
+                }),
+            );
+        }
+    };
+    let accounts = manager
+        .read_or_recover()
+        .get_program_accounts(&fork_id, program_id, req.filters)
+        .and_then(|accounts| {
+            accounts
+                .into_iter()
+                .map(|(pubkey, mut account)| {
+                    if let Some(slice) = req.data_slice {
+                        slice_account_data(&mut account, slice)
+                            .map_err(|e| anyhow::anyhow!("Account {}: {}", pubkey, e))?;
+                    }
+                    Ok(ProgramAccount {
+                        pubkey: pubkey.to_string(),
+                        account,
+                    })
+                })
+                .collect::<anyhow::Result<Vec<_>>>()
+        });
+    let response = match accounts {
+        Ok(accounts) => ApiResponse {
+            success: true,
+            data: Some(accounts),
+            error: None,
+        },
+        Err(e) => ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        },
+    };
+    (StatusCode::OK, Json(response))
+}
+
+/// Clones a program and the accounts it owns from mainnet
+#[axum::debug_handler]
+async fn clone_program_accounts(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path(fork_id): Path<Uuid>,
+    Json(req): Json<CloneProgramAccountsRequest>,
+) -> (StatusCode, Json<ForkApiResponse<ClonedProgram>>) {
+    let program_id = match req.program_id.parse::<Pubkey>() {
+        Ok(program_id) => program_id,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(
+                    ApiResponse {
+                        success: false,
+                        data: None,
+                        error: Some(format!("Invalid program id: {}", e)),
+                    }
+                    .into(),
+                ),
+            );
+        }
+    };
+
+    // Program accounts may take long to fetch, off the async runtime
+    let cloning = manager.read_or_recover().scoped(&fork_id);
+    let cloned = tokio::task::spawn_blocking(move || {
+        cloning.clone_program_accounts(&fork_id, program_id, req.filters, req.limit)
+    })
+    .await
+    .map_err(anyhow::Error::from)
+    .and_then(|result| result);
+    let response = match cloned {
+        Ok(cloned) => ApiResponse {
+            success: true,
+            data: Some(cloned),
+            error: None,
+        },
+        Err(e) => ApiResponse {
+            success: false,
+            data: None,
+            error: Some(format!("{:?}", e)),
+        },
+    };
+    (
+        StatusCode::OK,
+        Json(with_expiry(&manager, &fork_id, response)),
+    )
+}
+
+/// Looks a transaction up by signature, like the `getTransaction` RPC
+#[axum::debug_handler]
+async fn get_transaction(
+    State(manager): State<Arc<RwLock<ForkManager>>>,
+    Path((fork_id, signature)): Path<(Uuid, String)>,
+    Query(query): Query<TransactionLookupQuery>,
+) -> (StatusCode, Json<ApiResponse<TransactionRecord>>) {
+    let Ok(signature) = signature.parse::<Signature>() else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse {
+                success: false,
+                data: None,
+                error: Some(format!("Invalid signature {}", signature)),
+            }),
+        );
+    };
+
+    let response =
+        match manager
+            .read_or_recover()
+            .get_transaction(&fork_id, &signature, query.simulated)
+        {
+            Ok(Some(record)) => ApiResponse {
+                success: true,
+                data: Some(record),
+                error: None,
+            },
+            Ok(None) => ApiResponse {
+                success: false,
+                data: None,
+                error: Some("Transaction not found".to_string()),
+            },
+            Err(e) => ApiResponse {
+                success: false,
+                data: None,
+                error: Some(format!("{:?}", e)),
+            },
+        };
+    (StatusCode::OK, Json(response))
+}
+
 #[axum::debug_handler]
 async fn get_executed_transactions(
-    State(manager): State<Arc<Mutex<ForkManager>>>,
+    State(manager): State<Arc<RwLock<ForkManager>>>,
     Path(fork_id): Path<Uuid>,
-) -> Json<ApiResponse<Vec<TransactionRecord>>> {
-    match manager.lock().unwrap().get_executed_transactions(&fork_id) {
+    Query(query): Query<TransactionHistoryQuery>,
+) -> Json<ApiResponse<TransactionPage>> {
+    match manager
+        .read_or_recover()
+        .get_executed_transactions(&fork_id, &query)
+    {
         Ok(txns) => Json(ApiResponse {
             success: true,
             data: Some(txns),
@@ -280,10 +2627,14 @@ async fn get_executed_transactions(
 
 #[axum::debug_handler]
 async fn get_simulated_transactions(
-    State(manager): State<Arc<Mutex<ForkManager>>>,
+    State(manager): State<Arc<RwLock<ForkManager>>>,
     Path(fork_id): Path<Uuid>,
-) -> Json<ApiResponse<Vec<TransactionRecord>>> {
-    match manager.lock().unwrap().get_simulated_transactions(&fork_id) {
+    Query(query): Query<TransactionHistoryQuery>,
+) -> Json<ApiResponse<TransactionPage>> {
+    match manager
+        .read_or_recover()
+        .get_simulated_transactions(&fork_id, &query)
+    {
         Ok(txns) => Json(ApiResponse {
             success: true,
             data: Some(txns),
@@ -296,3 +2647,213 @@ async fn get_simulated_transactions(
         }),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{
+        account::Account,
+        message::{Message, VersionedMessage},
+        signature::{Keypair, Signer},
+        transaction::Transaction,
+    };
+    use solana_system_interface::instruction as system_instruction;
+
+    fn encode(bytes: &[u8]) -> String {
+        engine::general_purpose::STANDARD.encode(bytes)
+    }
+
+    fn transfer_message(payer: &Pubkey) -> Message {
+        let ix = system_instruction::transfer(payer, &Pubkey::new_unique(), 1);
+        Message::new(&[ix], Some(payer))
+    }
+
+    #[tokio::test]
+    async fn test_run_with_timeout() {
+        let done = run_with_timeout(Duration::from_secs(5), None, || Ok(1)).await;
+        assert_eq!(done.unwrap(), 1);
+
+        let slow = run_with_timeout(Duration::from_millis(10), None, || {
+            std::thread::sleep(Duration::from_millis(200));
+            Ok(())
+        })
+        .await;
+        assert_eq!(
+            slow.unwrap_err().to_string(),
+            "execution timed out after 10 ms"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_shedding() {
+        use tower::ServiceExt;
+
+        // Requests signal when they are being handled, then wait to be
+        // released, so the slot is held for as long as the test needs
+        let started = Arc::new(tokio::sync::Notify::new());
+        let release = Arc::new(tokio::sync::Notify::new());
+        let (handling, releasing) = (Arc::clone(&started), Arc::clone(&release));
+        let router = with_load_shedding(
+            Router::new().route(
+                "/",
+                get(move || async move {
+                    handling.notify_one();
+                    releasing.notified().await;
+                    "done"
+                }),
+            ),
+            1,
+        );
+        let request = || {
+            axum::http::Request::builder()
+                .uri("/")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let busy = tokio::spawn(router.clone().oneshot(request()));
+        started.notified().await;
+        let shed = router.clone().oneshot(request()).await.unwrap();
+        assert_eq!(shed.status(), StatusCode::SERVICE_UNAVAILABLE);
+        release.notify_one();
+        assert_eq!(busy.await.unwrap().unwrap().status(), StatusCode::OK);
+
+        // The slot is released once the request completes
+        release.notify_one();
+        let served = router.oneshot(request()).await.unwrap();
+        assert_eq!(served.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_work_keeps_its_permit() {
+        use tower::ServiceExt;
+
+        let (release, released) = std::sync::mpsc::channel::<()>();
+        let released = Arc::new(std::sync::Mutex::new(released));
+        let router = with_load_shedding(
+            Router::new().route(
+                "/",
+                get(move |permit: Option<Extension<ConcurrencyPermit>>| {
+                    let released = Arc::clone(&released);
+                    async move {
+                        let permit = permit.map(|Extension(p)| p);
+                        run_with_timeout(Duration::from_millis(10), permit, move || {
+                            let _ = released.lock().unwrap().recv();
+                            Ok(())
+                        })
+                        .await
+                        .map_or_else(|e| e.to_string(), |_| "done".to_string())
+                    }
+                }),
+            ),
+            1,
+        );
+        let request = || {
+            axum::http::Request::builder()
+                .uri("/")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let timed_out = router.clone().oneshot(request()).await.unwrap();
+        assert_eq!(timed_out.status(), StatusCode::OK);
+
+        // The blocked work still holds the request's slot
+        let shed = router.clone().oneshot(request()).await.unwrap();
+        assert_eq!(shed.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        // Until it completes
+        release.send(()).unwrap();
+        for _ in 0..100 {
+            let response = router.clone().oneshot(request()).await.unwrap();
+            if response.status() == StatusCode::OK {
+                release.send(()).unwrap();
+                return;
+            }
+            time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("the slot was never released");
+    }
+
+    #[tokio::test]
+    async fn test_ndjson_accounts() {
+        let account = ExportedAccount::new(
+            &Pubkey::new_unique(),
+            &Account::new(1_000, 3, &Pubkey::new_unique()),
+        );
+        let line = serde_json::to_string(&account).unwrap();
+        // Lines split across the frames of the body
+        let body = format!("{}\n\n{}", line, line);
+        let (first, second) = body.split_at(line.len() / 2);
+        let frames = stream::iter([first.to_string(), second.to_string()]);
+        let body = Body::from_stream(frames.map(Ok::<_, Infallible>));
+        let mut accounts = NdjsonAccounts::new(body, line.len());
+        let chunk = accounts.next_chunk().await.unwrap().unwrap();
+        assert_eq!(chunk.len(), 2);
+        assert_eq!(chunk[1].pubkey, account.pubkey);
+        assert!(accounts.next_chunk().await.unwrap().is_none());
+
+        let body = Body::from(format!("{}\n{{", line));
+        let err = NdjsonAccounts::new(body, line.len())
+            .next_chunk()
+            .await
+            .unwrap_err();
+        assert!(err.to_string().starts_with("Invalid account on line 2"));
+
+        let body = Body::from(format!("{}\n{}0", line, line));
+        let err = NdjsonAccounts::new(body, line.len())
+            .next_chunk()
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("Line 2 is longer than {} bytes", line.len())
+        );
+    }
+
+    #[test]
+    fn test_decode_valid_transaction() {
+        let payer = Keypair::new();
+        let tx = Transaction::new(
+            &[&payer],
+            transfer_message(&payer.pubkey()),
+            Default::default(),
+        );
+        let tx_base64 = encode(&bincode::serialize(&tx).unwrap());
+
+        let decoded = decode_transaction(&tx_base64).unwrap();
+        assert_eq!(decoded.signatures, tx.signatures);
+    }
+
+    #[test]
+    fn test_decode_invalid_base64() {
+        let err = decode_transaction("not base64!").unwrap_err();
+        assert!(err.to_string().starts_with("not valid base64"));
+    }
+
+    #[test]
+    fn test_decode_invalid_bincode() {
+        let err = decode_transaction(&encode(&[1, 2, 3])).unwrap_err();
+        assert!(err.to_string().starts_with("not valid bincode"));
+    }
+
+    #[test]
+    fn test_decode_not_a_transaction() {
+        // Deserializes fine, but is missing the signature its message requires
+        let tx = VersionedTransaction {
+            signatures: vec![],
+            message: VersionedMessage::Legacy(transfer_message(&Pubkey::new_unique())),
+        };
+        let err = decode_transaction(&encode(&bincode::serialize(&tx).unwrap())).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("valid bincode but not a transaction")
+        );
+    }
+
+    #[test]
+    fn test_decode_too_large() {
+        let err = decode_transaction(&"A".repeat(MAX_TX_BASE64_LEN + 4)).unwrap_err();
+        assert!(err.to_string().starts_with("transaction too large"));
+    }
+}
@@ -1,58 +1,918 @@
 use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    hash::{DefaultHasher, Hash as _, Hasher},
+    sync::{
+        Arc, Mutex, RwLock,
+        atomic::{AtomicUsize, Ordering},
+    },
     time::{Duration, Instant},
 };
 
-use chrono::{Local, Utc};
+use agave_feature_set::{FEATURE_NAMES, FeatureSet};
+use base64::{Engine, engine};
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt, stream};
 use litesvm::{
     LiteSVM,
-    types::{SimulatedTransactionInfo, TransactionMetadata},
+    types::{FailedTransactionMetadata, SimulatedTransactionInfo, TransactionMetadata},
 };
 use serde::{Deserialize, Serialize};
-use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_filter::{self, RpcFilterType};
+use solana_nonce::{
+    state::{DurableNonce, State as NonceState},
+    versions::Versions as NonceVersions,
+};
 use solana_program::example_mocks::solana_sdk::system_program;
 use solana_sdk::{
-    account::Account, clock::Clock, pubkey::Pubkey,
-    slot_hashes::SlotHashes, transaction::VersionedTransaction,
+    account::Account,
+    clock::Clock,
+    epoch_schedule::EpochSchedule,
+    hash::Hash,
+    instruction::InstructionError,
+    message::VersionedMessage,
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::{MAX_SEED_LEN, MAX_SEEDS, Pubkey},
+    rent::Rent,
+    signature::{Keypair, Signature, Signer},
+    slot_hashes::SlotHashes,
+    transaction::{TransactionError, VersionedTransaction},
 };
-use spl_token::solana_program::program_pack::Pack;
 use spl_token::solana_program::pubkey;
+use spl_token::solana_program::{program_option::COption, program_pack::Pack};
 use spl_token::{
     ID,
-    state::{Account as TokenAccount, AccountState},
+    state::{Account as TokenAccount, AccountState, Mint},
 };
+use tokio::sync::{broadcast, watch};
+use tracing::{info, warn};
 use uuid::Uuid;
 
+use crate::fees::{self, FeeEstimate, FeeSchedule};
+use crate::inspect::{self, TouchedAccount};
+use crate::logs::LogLimits;
+use crate::lookup_table::{self, LookupTable};
+use crate::rpc::{Commitment, MainnetRpc, RpcPolicy, is_allowed_rpc_url, is_valid_rpc_url};
+use crate::sync::{MutexExt, RwLockExt};
+
+pub use simulation_engine::types::{
+    AccountDiff, ActiveFeature, BatchDeleteResult, CleanupReport, ClonedProgram, ComputeFit,
+    DeleteForkResult, EpochInfo, ForkDeletion, ForkDiff, ForkStats, ForkSummary, ProgramAddress,
+    ReplayResult, ReturnData, SendResult, SetupResult, SlotHashEntry, TokenBalance,
+    TransactionPage, TransactionRecord,
+};
+
 const DEFAULT_RPC_CLIENT: &str = "https://api.mainnet-beta.solana.com";
+/// Default maximum number of forks alive at the same time
+pub const DEFAULT_MAX_FORKS: usize = 100;
+/// Decimals of mints created when setting a token balance of a missing mint
+const DEFAULT_MINT_DECIMALS: u8 = 9;
+/// Number of recent transaction events kept for reconnecting subscribers
+const EVENT_BUFFER_SIZE: usize = 64;
+/// Number of expired fork ids remembered to report them as expired
+const RECENTLY_EXPIRED_CAPACITY: usize = 1024;
+/// Maximum number of accounts fetched by a single `getMultipleAccounts` call
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+/// How long an idempotency key of a fork creation is remembered
+const IDEMPOTENCY_KEY_TTL: Duration = Duration::from_secs(15 * 60);
+/// Number of transaction records returned by a history query without `limit`
+const DEFAULT_HISTORY_LIMIT: usize = 100;
+/// Maximum number of transaction records returned by a history query
+const MAX_HISTORY_LIMIT: usize = 1_000;
+/// Maximum number of accounts cloned by `clone_program_accounts` without
+/// `limit`
+const DEFAULT_CLONE_LIMIT: usize = 10_000;
+/// Number of `SlotHashes` entries returned without `limit`
+const DEFAULT_SLOT_HASHES_LIMIT: usize = 32;
+/// Default maximum number of transactions of each kind kept by a fork
+pub const DEFAULT_HISTORY_CAPACITY: usize = 10_000;
+/// Instruction data of the system program's `AdvanceNonceAccount`
+const ADVANCE_NONCE_ACCOUNT: [u8; 4] = 4u32.to_le_bytes();
+/// Maximum size of the data of an account, the runtime's
+/// `MAX_PERMITTED_DATA_LENGTH`
+pub const MAX_ACCOUNT_DATA_LEN: usize = 10 * 1024 * 1024;
+/// Default wall-clock time a transaction execution may take
+pub const DEFAULT_EXECUTION_TIMEOUT: Duration = Duration::from_secs(30);
+/// Loader of upgradeable programs, whose code lives in a ProgramData account
+const UPGRADEABLE_LOADER: Pubkey =
+    Pubkey::from_str_const("BPFLoaderUpgradeab1e11111111111111111111111");
+/// Program deriving the associated token account of an owner and a mint
+const ASSOCIATED_TOKEN_PROGRAM: Pubkey =
+    Pubkey::from_str_const("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+/// Token-2022 program, whose associated token accounts can be derived but
+/// whose accounts can't be set
+const TOKEN_2022_PROGRAM: Pubkey =
+    Pubkey::from_str_const("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+/// Programs allowed to own executable accounts
+const LOADERS: [Pubkey; 5] = [
+    Pubkey::from_str_const("BPFLoader1111111111111111111111111111111111"),
+    Pubkey::from_str_const("BPFLoader2111111111111111111111111111111111"),
+    UPGRADEABLE_LOADER,
+    Pubkey::from_str_const("LoaderV411111111111111111111111111111111111"),
+    Pubkey::from_str_const("NativeLoader1111111111111111111111111111111"),
+];
 
 /// A Fork of mainnet Solana network
 pub struct Fork {
-    /// Solana virtual machine/runtime
-    pub svm: Arc<Mutex<LiteSVM>>,
-    /// A List of all executed transactions in this fork
-    pub executed_transactions: Mutex<Vec<TransactionRecord>>,
-    /// A List of all simulated transactions in this fork
-    pub simulated_transactions: Mutex<Vec<TransactionRecord>>,
+    /// Solana virtual machine/runtime. Pure reads (accounts, sysvars,
+    /// blockhash) take the read lock and run concurrently, anything which
+    /// may write to it (transactions, setters, mainnet fetches) takes the
+    /// write lock.
+    pub svm: Arc<RwLock<LiteSVM>>,
+    /// Most recent executed transactions in this fork
+    pub executed_transactions: Mutex<TransactionHistory>,
+    /// Most recent simulated transactions in this fork
+    pub simulated_transactions: Mutex<TransactionHistory>,
+    /// Addresses of all accounts materialized in this fork (set locally,
+    /// preloaded from mainnet or touched by an executed transaction)
+    pub accounts: Mutex<HashSet<Pubkey>>,
+    /// Options the fork was created with
+    pub options: ForkOptions,
+    /// Runtime features the fork was created with
+    pub feature_set: FeatureSet,
+    /// Client fetching missing accounts from mainnet, `None` when offline
+    rpc: Option<MainnetRpc>,
+    /// Incremented every time the state of the fork changes
+    state_version: watch::Sender<u64>,
+    /// Publishes an event for every executed/simulated transaction
+    events: broadcast::Sender<TransactionEvent>,
+    /// Most recent events, replayed to subscribers which reconnect
+    recent_events: Mutex<VecDeque<TransactionEvent>>,
+    /// Number of event subscribers asking for account changes, which are
+    /// only computed while there is one
+    account_change_subscribers: Arc<AtomicUsize>,
+    /// Blockhash set by the user, reported instead of the runtime's one
+    /// until the runtime's blockhash changes
+    blockhash: Mutex<Option<Hash>>,
+    /// Caps on the logs of the recorded transactions
+    log_limits: LogLimits,
+    /// Maximum number of records kept in each transaction history
+    history_capacity: usize,
     /// Fork expires 15 minutes after creation
     expires_at: Instant,
 }
 
-/// A record of transaction executed/simulated on the fork
-#[derive(Deserialize, Serialize, Clone)]
-pub struct TransactionRecord {
-    pub txn: TransactionMetadata,
-    pub time: String,
+/// Options used when creating a fork
+#[derive(Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct ForkOptions {
+    /// Never contact mainnet: missing accounts are not preloaded and
+    /// sysvars keep their LiteSVM defaults, so every account the fork
+    /// needs must be seeded manually
+    pub offline: bool,
+    /// Features to deactivate, to reproduce older runtime behavior. Every
+    /// feature known to the runtime is active by default.
+    pub disabled_features: Vec<String>,
+    /// Accounts fetched from mainnet when the fork is created, instead of
+    /// lazily on the first transaction referencing them
+    pub preload: Vec<String>,
+    /// Minimum context slot of the accounts fetched from mainnet, to get
+    /// close to a historical state. RPC nodes which can't serve it answer
+    /// with their latest state. It doesn't pin the fork's clock, which
+    /// starts from mainnet's latest sysvars and moves with epoch changes.
+    pub slot: Option<u64>,
+    /// Commitment of the mainnet state fetched by the fork
+    pub commitment: Commitment,
+    /// Checks the runtime performs on the transactions of the fork
+    pub validation: ValidationOptions,
+    /// Balances set right after creation, so the fork is ready to accept
+    /// transactions from these accounts
+    pub fund: Vec<LamportsSetup>,
+    /// Human readable name of the fork
+    pub name: Option<String>,
+    /// Arbitrary key/value labels, to find the fork among many
+    pub labels: BTreeMap<String, String>,
+    /// Maximum number of executed, and of simulated, transactions kept in
+    /// the history, the oldest being dropped first. Defaults to the
+    /// server's limit.
+    pub history_capacity: Option<usize>,
+    /// Delay added before answering executions and simulations, to mimic
+    /// network latency
+    pub artificial_delay_ms: u64,
+    /// Where the lamports of token accounts created by
+    /// [`ForkManager::set_token_balance`] come from
+    pub token_rent_source: TokenRentSource,
+    /// Keep the runtime's fixed genesis blockhash instead of recording the
+    /// live mainnet one, advancing it deterministically whenever the clock
+    /// moves to another slot, so runs are reproducible
+    pub deterministic_blockhash: bool,
+}
+
+/// Source of the lamports of the token accounts created when setting a
+/// token balance
+#[derive(Deserialize, Serialize, Clone, Default, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenRentSource {
+    /// Created out of thin air, growing the total SOL of the fork
+    #[default]
+    Minted,
+    /// Debited from the owner of the token account
+    Owner,
+    /// Debited from the given account
+    Faucet(String),
+}
+
+/// Checks performed on the transactions of a fork. The defaults are lenient
+/// enough to replay mainnet transactions, enable every check for strict
+/// correctness testing.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(default)]
+pub struct ValidationOptions {
+    /// Reject transactions with missing or invalid signatures. Disable it
+    /// to send transactions on behalf of keys you don't hold.
+    pub sigverify: bool,
+    /// Charge fees, rejecting fee payers which can't afford them. When
+    /// disabled the fee payer is credited the fee before the transaction,
+    /// so transactions run for free.
+    pub fee_check: bool,
+    /// Reject transactions whose recent blockhash isn't known to the fork.
+    /// Transactions must then be signed against `GET /blockhash`.
+    pub blockhash_check: bool,
+}
+
+impl Default for ValidationOptions {
+    fn default() -> Self {
+        ValidationOptions {
+            sigverify: true,
+            fee_check: true,
+            blockhash_check: false,
+        }
+    }
+}
+
+impl ValidationOptions {
+    /// Describes the combinations of checks which are unlikely to be
+    /// intended, for a fork connected to mainnet unless `offline`
+    pub fn inconsistencies(&self, offline: bool) -> Vec<&'static str> {
+        let mut inconsistencies = Vec::new();
+        if self.blockhash_check && !self.sigverify {
+            inconsistencies.push(
+                "blockhash_check without sigverify: unsigned transactions are accepted \
+                 but still need a fork blockhash",
+            );
+        }
+        if self.blockhash_check && !offline {
+            inconsistencies.push(
+                "blockhash_check on a mainnet fork: transactions signed against a mainnet \
+                 blockhash are rejected",
+            );
+        }
+        if !self.fee_check && self.sigverify && self.blockhash_check {
+            inconsistencies.push(
+                "fee_check disabled while every other check is enabled: fee payers are \
+                 never charged",
+            );
+        }
+        inconsistencies
+    }
+}
+
+/// Whether a transaction was executed or simulated
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionKind {
+    Executed,
+    Simulated,
+}
+
+/// Event published for every transaction executed/simulated on the fork
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct TransactionEvent {
+    pub id: u64,
+    pub kind: TransactionKind,
+    pub signature: String,
     pub success: bool,
+    pub compute_units_consumed: u64,
+    /// Accounts the transaction changed, `left` being their state before
+    /// it and `right` after. Only computed while a subscriber asks for them.
+    #[serde(default, skip_serializing_if = "Option::is_none", skip_deserializing)]
+    pub account_changes: Option<Vec<AccountDiff>>,
+}
+
+/// Counts a subscriber asking for account changes until it's dropped
+struct AccountChangeSubscription(Arc<AtomicUsize>);
+
+impl AccountChangeSubscription {
+    fn new(subscribers: &Arc<AtomicUsize>) -> Self {
+        subscribers.fetch_add(1, Ordering::Relaxed);
+        AccountChangeSubscription(Arc::clone(subscribers))
+    }
+}
+
+impl Drop for AccountChangeSubscription {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// An account in a portable format, used to export and snapshot forks
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ExportedAccount {
+    pub pubkey: String,
+    pub lamports: u64,
+    pub owner: String,
+    pub data_base64: String,
+    pub executable: bool,
+    pub rent_epoch: u64,
+}
+
+impl ExportedAccount {
+    pub fn new(pubkey: &Pubkey, account: &Account) -> Self {
+        ExportedAccount {
+            pubkey: pubkey.to_string(),
+            lamports: account.lamports,
+            owner: account.owner.to_string(),
+            data_base64: engine::general_purpose::STANDARD.encode(&account.data),
+            executable: account.executable,
+            rent_epoch: account.rent_epoch,
+        }
+    }
+
+    /// Decodes the exported account back into its address and state
+    pub fn to_account(&self) -> anyhow::Result<(Pubkey, Account)> {
+        let pubkey = self
+            .pubkey
+            .parse::<Pubkey>()
+            .map_err(|_| anyhow::anyhow!("Invalid pubkey {}", self.pubkey))?;
+        let owner = self
+            .owner
+            .parse::<Pubkey>()
+            .map_err(|_| anyhow::anyhow!("Invalid owner {} of {}", self.owner, self.pubkey))?;
+        // Rejected before decoding, so huge payloads aren't buffered twice.
+        // The estimate counts padding as data, so it exceeds the decoded
+        // length by up to 2 bytes
+        if base64::decoded_len_estimate(self.data_base64.len()) > MAX_ACCOUNT_DATA_LEN + 2 {
+            anyhow::bail!(
+                "Data of {} exceeds the maximum account size of {} bytes",
+                self.pubkey,
+                MAX_ACCOUNT_DATA_LEN
+            );
+        }
+        let data = engine::general_purpose::STANDARD
+            .decode(&self.data_base64)
+            .map_err(|e| anyhow::anyhow!("Invalid data of {}: {}", self.pubkey, e))?;
+
+        check_data_len(&pubkey, data.len())?;
+
+        Ok((
+            pubkey,
+            Account {
+                lamports: self.lamports,
+                data,
+                owner,
+                executable: self.executable,
+                rent_epoch: self.rent_epoch,
+            },
+        ))
+    }
+}
+
+/// Declarative setup of a fork's state, applied atomically
+#[derive(Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct ForkSetup {
+    pub lamports: Vec<LamportsSetup>,
+    pub token_balances: Vec<TokenBalanceSetup>,
+    pub accounts: Vec<ExportedAccount>,
+    pub programs: Vec<ProgramSetup>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct LamportsSetup {
+    pub pubkey: String,
+    pub lamports: u64,
+    /// Allow data-bearing accounts below their rent-exempt minimum
+    #[serde(default)]
+    pub allow_rent_paying: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct TokenBalanceSetup {
+    pub token_account: String,
+    pub mint: String,
+    pub owner: String,
+    pub amount: u64,
+    pub decimals: Option<u8>,
+    /// Reject amounts exceeding the supply of an existing mint
+    #[serde(default)]
+    pub check_supply: bool,
+    /// Lamports of the token account when it has to be created, the
+    /// rent-exempt minimum by default
+    #[serde(default)]
+    pub lamports: Option<u64>,
+}
+
+/// Options of [`ForkManager::set_token_balance`]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokenBalanceOptions {
+    /// Decimals of the mint when it has to be created, 9 by default
+    pub decimals: Option<u8>,
+    /// Reject amounts exceeding the supply of an existing mint
+    pub check_supply: bool,
+    /// Lamports of the token account when it has to be created, the
+    /// rent-exempt minimum by default
+    pub lamports: Option<u64>,
+}
+
+/// Entry of [`ForkManager::set_token_balances`]
+#[derive(Deserialize, Serialize, Clone)]
+pub struct TokenBalanceEntry {
+    /// The owner's associated token account when omitted
+    #[serde(default)]
+    pub token_account: Option<String>,
+    pub mint: String,
+    pub owner: String,
+    pub amount: u64,
+    /// Only the SPL token program, the default, is supported
+    #[serde(default)]
+    pub token_program: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct ProgramSetup {
+    pub program_id: String,
+    pub program_base64: String,
+}
+
+/// Filters and pagination of a transaction history query
+#[derive(Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct TransactionHistoryQuery {
+    /// Only return successful (`true`) or failed (`false`) transactions
+    pub success: Option<bool>,
+    /// Only return transactions recorded at or after this time
+    pub since: Option<DateTime<Utc>>,
+    pub offset: usize,
+    /// Number of records returned, 100 by default and at most 1000
+    pub limit: Option<usize>,
+    /// Overrides the maximum number of log lines returned per transaction
+    pub log_max_lines: Option<usize>,
+    /// Overrides the maximum size of the logs returned per transaction
+    pub log_max_bytes: Option<usize>,
+    /// Return the transactions themselves in `tx_base64`
+    pub include_tx: bool,
+}
+
+/// Options of a simulation
+#[derive(Clone, Default, Debug)]
+pub struct SimulateOptions {
+    /// Re-fetch the accounts referenced by the transaction from mainnet,
+    /// overwriting the fork's copies first
+    pub refresh_accounts: bool,
+    /// Skip signature verification, to preview transactions before they
+    /// are (fully) signed
+    pub allow_unsigned: bool,
+    /// Mainnet RPC endpoint fetching the transaction's missing accounts
+    /// instead of the fork's
+    pub rpc_url: Option<String>,
+    /// Signers simulated as if they had signed, so transactions can be sent
+    /// on behalf of accounts whose keys the user doesn't hold. The
+    /// signatures of the other signers are still verified.
+    pub impersonate: Vec<Pubkey>,
+}
+
+/// Most recent transactions of a kind recorded by a fork
+#[derive(Default)]
+pub struct TransactionHistory {
+    /// Retained records, oldest first
+    pub records: VecDeque<TransactionRecord>,
+    /// Number of transactions ever recorded, dropped ones included
+    pub recorded: u64,
+}
+
+impl TransactionHistoryQuery {
+    /// Applies the filters and pagination to records in recording order
+    pub fn page(&self, records: &VecDeque<TransactionRecord>) -> TransactionPage {
+        let matching = records.iter().filter(|record| {
+            self.success.is_none_or(|success| record.success == success)
+                && self.since.is_none_or(|since| record.time >= since)
+        });
+        TransactionPage {
+            total: matching.clone().count(),
+            transactions: matching
+                .skip(self.offset)
+                .take(
+                    self.limit
+                        .unwrap_or(DEFAULT_HISTORY_LIMIT)
+                        .min(MAX_HISTORY_LIMIT),
+                )
+                .cloned()
+                .collect(),
+        }
+    }
 }
 
 impl Fork {
-    pub fn new(svm: Arc<Mutex<LiteSVM>>) -> Self {
+    pub fn new(
+        svm: Arc<RwLock<LiteSVM>>,
+        options: ForkOptions,
+        feature_set: FeatureSet,
+        rpc: Option<MainnetRpc>,
+    ) -> Self {
         Fork {
+            rpc,
+            options,
+            feature_set,
             expires_at: Instant::now() + Duration::from_secs(15 * 60),
             svm,
-            executed_transactions: Mutex::new(Vec::new()),
-            simulated_transactions: Mutex::new(Vec::new()),
+            executed_transactions: Mutex::new(TransactionHistory::default()),
+            simulated_transactions: Mutex::new(TransactionHistory::default()),
+            accounts: Mutex::new(HashSet::new()),
+            state_version: watch::channel(0).0,
+            events: broadcast::channel(EVENT_BUFFER_SIZE).0,
+            recent_events: Mutex::new(VecDeque::with_capacity(EVENT_BUFFER_SIZE)),
+            account_change_subscribers: Arc::new(AtomicUsize::new(0)),
+            blockhash: Mutex::new(None),
+            log_limits: LogLimits::default(),
+            history_capacity: DEFAULT_HISTORY_CAPACITY,
+        }
+    }
+
+    /// Records an executed/simulated transaction and publishes an event for
+    /// it, successful if `result` is
+    fn record_transaction(
+        &self,
+        kind: TransactionKind,
+        tx: VersionedTransaction,
+        result: Result<TransactionMetadata, FailedTransactionMetadata>,
+        account_changes: Option<Vec<AccountDiff>>,
+    ) {
+        let success = result.is_ok();
+        let mut txn = result.unwrap_or_else(|failed| failed.meta);
+        // Events are published under the lock so subscribers never miss or
+        // duplicate an event between the replayed ones and the live ones
+        let mut recent = self.recent_events.lock_or_recover();
+        let event = TransactionEvent {
+            id: recent.back().map_or(1, |last| last.id + 1),
+            kind,
+            signature: txn.signature.to_string(),
+            success,
+            compute_units_consumed: txn.compute_units_consumed,
+            account_changes,
+        };
+        if recent.len() == EVENT_BUFFER_SIZE {
+            recent.pop_front();
+        }
+        recent.push_back(event.clone());
+        let _ = self.events.send(event);
+
+        let records = match kind {
+            TransactionKind::Executed => &self.executed_transactions,
+            TransactionKind::Simulated => &self.simulated_transactions,
+        };
+        self.log_limits.truncate(&mut txn.logs);
+        let mut history = records.lock_or_recover();
+        history.recorded += 1;
+        history.records.push_back(TransactionRecord {
+            txn,
+            tx: Some(tx),
+            tx_base64: None,
+            time: Utc::now(),
+            success,
+        });
+        if history.records.len() > self.history_capacity {
+            history.records.pop_front();
+        }
+    }
+
+    /// Builds a fork from its options, fetching its initial state from
+    /// mainnet unless it's offline, then seeds it with the `fixture`
+    /// accounts. The manager's `log_limits` and `history_capacity` apply
+    /// unless the options override them.
+    fn from_options(
+        fork_id: &Uuid,
+        options: ForkOptions,
+        rpc_policy: RpcPolicy,
+        log_limits: LogLimits,
+        history_capacity: usize,
+        fixture: &[(Pubkey, Account)],
+    ) -> anyhow::Result<Self> {
+        let _span = tracing::info_span!("create_fork", fork_id = %fork_id).entered();
+
+        let mut feature_set = FeatureSet::all_enabled();
+        for feature in &options.disabled_features {
+            let feature_id = feature
+                .parse::<Pubkey>()
+                .map_err(|_| anyhow::anyhow!("Invalid feature id {}", feature))?;
+            feature_set.deactivate(&feature_id);
+        }
+
+        let preload = options
+            .preload
+            .iter()
+            .map(|key| {
+                key.parse::<Pubkey>()
+                    .map_err(|_| anyhow::anyhow!("Invalid preload account {}", key))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if options.offline && !preload.is_empty() {
+            anyhow::bail!("Accounts can't be preloaded in an offline fork");
+        }
+        let fund = options
+            .fund
+            .iter()
+            .map(|entry| {
+                Ok((
+                    parse_pubkey(&entry.pubkey)?,
+                    entry.lamports,
+                    entry.allow_rent_paying,
+                ))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        for inconsistency in options.validation.inconsistencies(options.offline) {
+            warn!(validation = ?options.validation, "inconsistent validation options, {}", inconsistency);
+        }
+
+        let mut svm = new_svm(&feature_set)
+            .with_sigverify(options.validation.sigverify)
+            .with_blockhash_check(options.validation.blockhash_check);
+
+        let rpc = (!options.offline).then(|| {
+            MainnetRpc::new(
+                DEFAULT_RPC_CLIENT,
+                options.slot,
+                options.commitment,
+                rpc_policy,
+            )
+        });
+        let preloaded = match &rpc {
+            Some(rpc) => preload_accounts(&mut svm, rpc, &preload)?,
+            None => Vec::new(),
+        };
+
+        if let Some(rpc) = &rpc {
+            let refreshed = update_sysvars(&mut svm, rpc, options.deterministic_blockhash);
+            if refreshed.is_complete() {
+                info!("updated sysvars");
+            } else {
+                warn!(?refreshed, "sysvars partially updated");
+            }
+        }
+        for (pubkey, account) in fixture {
+            svm.set_account(*pubkey, account.clone())?;
+        }
+
+        let history_capacity = options.history_capacity.unwrap_or(history_capacity);
+        let fork = Fork {
+            log_limits,
+            history_capacity,
+            ..Fork::new(Arc::new(RwLock::new(svm)), options, feature_set, rpc)
+        };
+        fork.track_accounts(&preloaded);
+        fork.track_accounts(fixture.iter().map(|(pubkey, _)| pubkey));
+        {
+            let mut svm = fork.svm.write_or_recover();
+            for (pubkey, lamports, allow_rent_paying) in fund {
+                fork.write_lamports(&mut *svm, pubkey, lamports, allow_rent_paying)?;
+            }
+        }
+        Ok(fork)
+    }
+
+    /// Helper function which debits the lamports of a created token account
+    /// from the fork's [`TokenRentSource`]
+    fn pay_token_account_rent(
+        &self,
+        svm: &mut impl AccountStore,
+        owner: Pubkey,
+        lamports: u64,
+    ) -> anyhow::Result<()> {
+        let payer = match &self.options.token_rent_source {
+            TokenRentSource::Minted => return Ok(()),
+            TokenRentSource::Owner => owner,
+            TokenRentSource::Faucet(faucet) => parse_pubkey(faucet)?,
+        };
+        let Some(mut account) = svm.get_account(&payer) else {
+            anyhow::bail!("Token account rent payer {} not found in fork", payer);
+        };
+        account.lamports = account.lamports.checked_sub(lamports).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Token account rent payer {} can't afford {} lamports",
+                payer,
+                lamports
+            )
+        })?;
+        svm.set_account(payer, account)?;
+        Ok(())
+    }
+
+    /// Helper function which sets lamports of an address in the svm.
+    ///
+    /// Balances above `i64::MAX` are rejected as they most likely come from
+    /// a wrapped negative value. Unless `allow_rent_paying`, accounts
+    /// holding data can't drop below their rent-exempt minimum, which would
+    /// make them collectable.
+    fn write_lamports(
+        &self,
+        svm: &mut impl AccountStore,
+        pubkey: Pubkey,
+        lamports: u64,
+        allow_rent_paying: bool,
+    ) -> anyhow::Result<()> {
+        if lamports > i64::MAX as u64 {
+            anyhow::bail!(
+                "Lamports {} for {} exceed {}, is it a wrapped negative value?",
+                lamports,
+                pubkey,
+                i64::MAX
+            );
+        }
+        let mut account = match svm.get_account(&pubkey) {
+            Some(acc) => acc,
+            None => Account::new(0, 0, &system_program::ID),
+        };
+        if !allow_rent_paying && !account.data.is_empty() {
+            let minimum = svm.rent().minimum_balance(account.data.len());
+            if lamports < minimum {
+                anyhow::bail!(
+                    "Lamports {} for {} are below its rent-exempt minimum {}, set allow_rent_paying to force it",
+                    lamports,
+                    pubkey,
+                    minimum
+                );
+            }
+        }
+        account.lamports = lamports;
+        svm.set_account(pubkey, account)?;
+        self.track_accounts([&pubkey]);
+        Ok(())
+    }
+
+    /// Subscribes to transaction events, returning the buffered events
+    /// published after `last_event_id` along with a receiver of new ones
+    pub fn subscribe_events(
+        &self,
+        last_event_id: Option<u64>,
+    ) -> (Vec<TransactionEvent>, broadcast::Receiver<TransactionEvent>) {
+        let recent = self.recent_events.lock_or_recover();
+        let missed = match last_event_id {
+            Some(last) => recent.iter().filter(|e| e.id > last).cloned().collect(),
+            None => Vec::new(),
+        };
+        (missed, self.events.subscribe())
+    }
+
+    /// Snapshots the accounts a transaction may write, to report the ones
+    /// it changed with [`account_changes`]. `None` unless an event
+    /// subscriber asked for account changes.
+    fn writable_accounts(
+        &self,
+        svm: &LiteSVM,
+        tx: &VersionedTransaction,
+    ) -> Option<Vec<(Pubkey, Option<Account>)>> {
+        if self.account_change_subscribers.load(Ordering::Relaxed) == 0 {
+            return None;
+        }
+        // Transactions referencing missing lookup tables fail to load, so
+        // they change nothing
+        let touched = inspect::touched_accounts(&tx.message, |key| {
+            let Some(account) = svm.get_account(key) else {
+                anyhow::bail!("Lookup table {} not found", key);
+            };
+            Ok(LookupTable::from_account(&account)?.addresses)
+        })
+        .unwrap_or_default();
+        Some(
+            touched
+                .into_iter()
+                .filter(|account| account.writable)
+                .filter_map(|account| account.pubkey.parse::<Pubkey>().ok())
+                .map(|pubkey| (pubkey, svm.get_account(&pubkey)))
+                .collect(),
+        )
+    }
+
+    /// Wall-clock time the fork expires at
+    pub fn expires_at_utc(&self) -> DateTime<Utc> {
+        let remaining = self.expires_at.saturating_duration_since(Instant::now());
+        Utc::now() + chrono::Duration::from_std(remaining).unwrap_or_default()
+    }
+
+    /// Fee parameters of the fork's runtime
+    fn fee_schedule(&self) -> FeeSchedule {
+        fees::fee_schedule(self.options.validation.fee_check)
+    }
+
+    /// Fee of a transaction on the fork
+    fn estimate_fee(&self, tx: &VersionedTransaction) -> FeeEstimate {
+        fees::estimate_fee(&tx.message, &self.fee_schedule(), &self.feature_set)
+    }
+
+    /// Moves the runtime to a new blockhash, which replaces the one set by
+    /// the user
+    fn expire_blockhash(&self, svm: &mut LiteSVM) {
+        svm.expire_blockhash();
+        *self.blockhash.lock_or_recover() = None;
+    }
+
+    /// Notifies watchers that the state of the fork changed
+    fn state_changed(&self) {
+        self.state_version.send_modify(|version| *version += 1);
+    }
+
+    /// Gets all accounts materialized in this fork, sorted by address
+    fn materialized_accounts(&self, svm: &LiteSVM) -> Vec<(Pubkey, Account)> {
+        let mut accounts: Vec<_> = self
+            .accounts
+            .lock_or_recover()
+            .iter()
+            .filter_map(|key| svm.get_account(key).map(|acc| (*key, acc)))
+            .collect();
+        accounts.sort_by_key(|(key, _)| *key);
+        accounts
+    }
+
+    /// Records addresses of accounts materialized in this fork
+    fn track_accounts<'a>(&self, keys: impl IntoIterator<Item = &'a Pubkey>) {
+        self.accounts.lock_or_recover().extend(keys);
+    }
+}
+
+/// Filter applied on account data when scanning program accounts, with
+/// the same JSON format as the `getProgramAccounts` RPC filters
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub enum ProgramAccountsFilter {
+    /// Account data length must be equal to this size
+    DataSize(usize),
+    /// Account data at `offset` must start with `bytes`
+    Memcmp(Memcmp),
+}
+
+/// Compares account data at `offset` with `bytes`. Over JSON, `bytes` is
+/// base58 encoded unless `encoding` is `base64`.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+#[serde(try_from = "RpcMemcmp", into = "RpcMemcmp")]
+pub struct Memcmp {
+    pub offset: usize,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+enum MemcmpEncoding {
+    #[default]
+    Base58,
+    Base64,
+}
+
+/// Memcmp filter as sent over JSON
+#[derive(Deserialize, Serialize)]
+struct RpcMemcmp {
+    offset: usize,
+    bytes: String,
+    #[serde(default)]
+    encoding: MemcmpEncoding,
+}
+
+impl TryFrom<RpcMemcmp> for Memcmp {
+    type Error = String;
+
+    fn try_from(memcmp: RpcMemcmp) -> Result<Self, Self::Error> {
+        let bytes = match memcmp.encoding {
+            MemcmpEncoding::Base58 => bs58::decode(&memcmp.bytes)
+                .into_vec()
+                .map_err(|e| format!("invalid base58 memcmp bytes: {}", e))?,
+            MemcmpEncoding::Base64 => engine::general_purpose::STANDARD
+                .decode(&memcmp.bytes)
+                .map_err(|e| format!("invalid base64 memcmp bytes: {}", e))?,
+        };
+        Ok(Memcmp {
+            offset: memcmp.offset,
+            bytes,
+        })
+    }
+}
+
+impl From<Memcmp> for RpcMemcmp {
+    fn from(memcmp: Memcmp) -> Self {
+        RpcMemcmp {
+            offset: memcmp.offset,
+            bytes: bs58::encode(memcmp.bytes).into_string(),
+            encoding: MemcmpEncoding::Base58,
+        }
+    }
+}
+
+impl ProgramAccountsFilter {
+    fn matches(&self, data: &[u8]) -> bool {
+        match self {
+            ProgramAccountsFilter::DataSize(size) => data.len() == *size,
+            // Accounts too short to hold the compared bytes don't match
+            ProgramAccountsFilter::Memcmp(memcmp) => data
+                .get(memcmp.offset..)
+                .is_some_and(|slice| slice.starts_with(&memcmp.bytes)),
+        }
+    }
+}
+
+impl From<&ProgramAccountsFilter> for RpcFilterType {
+    fn from(filter: &ProgramAccountsFilter) -> Self {
+        match filter {
+            ProgramAccountsFilter::DataSize(size) => RpcFilterType::DataSize(*size as u64),
+            ProgramAccountsFilter::Memcmp(memcmp) => RpcFilterType::Memcmp(
+                rpc_filter::Memcmp::new_raw_bytes(memcmp.offset, memcmp.bytes.clone()),
+            ),
         }
     }
 }
@@ -61,42 +921,327 @@ impl Fork {
 #[derive(Clone)]
 pub struct ForkManager {
     pub forks: HashMap<Uuid, Arc<Fork>>,
+    /// Maximum number of forks alive at the same time
+    max_forks: usize,
+    /// Ids of the most recently expired forks
+    recently_expired: VecDeque<Uuid>,
+    /// Timeout and retry policy of mainnet RPC requests made by new forks
+    rpc_policy: RpcPolicy,
+    /// Forks created with an idempotency key, along with when it was seen
+    idempotency_keys: HashMap<String, (Uuid, Instant)>,
+    /// Caps on the transaction logs recorded by new forks
+    log_limits: LogLimits,
+    /// Transaction history capacity of new forks not setting their own
+    history_capacity: usize,
+    /// Wall-clock time after which transaction executions are reported as
+    /// timed out, unless overridden by the request
+    execution_timeout: Duration,
+    /// Outcome of the latest cleanup of expired forks
+    last_cleanup: Option<CleanupReport>,
+    /// Accounts every new fork is seeded with
+    fixture: Arc<Vec<(Pubkey, Account)>>,
+    /// Endpoints requests may fetch accounts from instead of their fork's,
+    /// none by default
+    allowed_rpc_urls: Vec<String>,
 }
 
 impl ForkManager {
     pub fn new() -> Self {
         ForkManager {
             forks: HashMap::new(),
+            max_forks: DEFAULT_MAX_FORKS,
+            recently_expired: VecDeque::with_capacity(RECENTLY_EXPIRED_CAPACITY),
+            rpc_policy: RpcPolicy::default(),
+            idempotency_keys: HashMap::new(),
+            log_limits: LogLimits::default(),
+            history_capacity: DEFAULT_HISTORY_CAPACITY,
+            execution_timeout: DEFAULT_EXECUTION_TIMEOUT,
+            last_cleanup: None,
+            fixture: Arc::new(Vec::new()),
+            allowed_rpc_urls: Vec::new(),
         }
     }
 
+    /// Sets the maximum number of forks alive at the same time
+    pub fn with_max_forks(mut self, max_forks: usize) -> Self {
+        self.max_forks = max_forks;
+        self
+    }
+
+    /// Sets the timeout and retry policy of mainnet RPC requests
+    pub fn with_rpc_policy(mut self, rpc_policy: RpcPolicy) -> Self {
+        self.rpc_policy = rpc_policy;
+        self
+    }
+
+    /// Sets the endpoints the `rpc_url` of requests may point to
+    pub fn with_allowed_rpc_urls(mut self, allowed_rpc_urls: Vec<String>) -> Self {
+        self.allowed_rpc_urls = allowed_rpc_urls;
+        self
+    }
+
+    /// Sets the caps on the transaction logs recorded by new forks
+    pub fn with_log_limits(mut self, log_limits: LogLimits) -> Self {
+        self.log_limits = log_limits;
+        self
+    }
+
+    /// Caps on the transaction logs, before request overrides
+    pub fn log_limits(&self) -> LogLimits {
+        self.log_limits
+    }
+
+    /// Sets how many transactions of each kind new forks keep by default
+    pub fn with_history_capacity(mut self, history_capacity: usize) -> Self {
+        self.history_capacity = history_capacity;
+        self
+    }
+
+    /// Sets the default wall-clock deadline of transaction executions
+    pub fn with_execution_timeout(mut self, execution_timeout: Duration) -> Self {
+        self.execution_timeout = execution_timeout;
+        self
+    }
+
+    /// Default wall-clock deadline of transaction executions
+    pub fn execution_timeout(&self) -> Duration {
+        self.execution_timeout
+    }
+
+    /// Delay added before answering executions and simulations on a fork,
+    /// none if the fork doesn't exist
+    pub fn artificial_delay(&self, fork_id: &Uuid) -> Duration {
+        self.get_fork(fork_id).map_or(Duration::ZERO, |fork| {
+            Duration::from_millis(fork.options.artificial_delay_ms)
+        })
+    }
+
+    /// Sets the accounts every new fork is seeded with, see
+    /// [`ForkManager::load_fixture`]
+    pub fn with_fixture(mut self, fixture: Vec<(Pubkey, Account)>) -> Self {
+        self.fixture = Arc::new(fixture);
+        self
+    }
+
+    /// Reads a fixture file, a JSON array of accounts in the format of
+    /// [`ForkManager::export_accounts`]
+    pub fn load_fixture(path: &std::path::Path) -> anyhow::Result<Vec<(Pubkey, Account)>> {
+        let file = std::fs::File::open(path)?;
+        let accounts: Vec<ExportedAccount> =
+            serde_json::from_reader(std::io::BufReader::new(file))?;
+        accounts.iter().map(ExportedAccount::to_account).collect()
+    }
+
     /// Creates a new fork with random fork id
+    #[cfg(test)]
     pub fn create_fork(&mut self) -> anyhow::Result<Uuid> {
-        let mut svm = LiteSVM::new().with_sysvars().with_blockhash_check(false);
+        self.create_fork_with_options(ForkOptions::default())
+    }
 
-        match update_sysvars(&mut svm) {
-            Ok(_) => println!("updated sysvars"),
-            Err(e) => println!("error in updating sysvars: {:?}", e),
-        }
+    /// Creates a new fork with random fork id using the given options
+    #[cfg(test)]
+    pub fn create_fork_with_options(&mut self, options: ForkOptions) -> anyhow::Result<Uuid> {
+        self.check_capacity()?;
+        let fork_id = Uuid::new_v4();
+        let fork = Fork::from_options(
+            &fork_id,
+            options,
+            self.rpc_policy,
+            self.log_limits,
+            self.history_capacity,
+            &self.fixture,
+        )?;
+        self.insert_fork(fork_id, fork)
+    }
 
+    /// Creates a new fork without holding the manager lock while its state
+    /// is fetched from mainnet, so concurrent creations don't serialize on
+    /// RPC calls. The fork limit is checked again once the fork is built.
+    ///
+    /// Retried creations sharing an `idempotency_key` return the fork
+    /// created by the first one as long as it is alive.
+    pub fn create_fork_concurrently(
+        manager: &RwLock<ForkManager>,
+        options: ForkOptions,
+        idempotency_key: Option<&str>,
+    ) -> anyhow::Result<Uuid> {
+        let (rpc_policy, log_limits, history_capacity, fixture) = {
+            let mut manager = manager.write_or_recover();
+            if let Some(fork_id) = idempotency_key.and_then(|key| manager.idempotent_fork(key)) {
+                return Ok(fork_id);
+            }
+            manager.check_capacity()?;
+            (
+                manager.rpc_policy,
+                manager.log_limits,
+                manager.history_capacity,
+                Arc::clone(&manager.fixture),
+            )
+        };
         let fork_id = Uuid::new_v4();
-        let fork = Fork::new(Arc::new(Mutex::new(svm)));
+        let fork = Fork::from_options(
+            &fork_id,
+            options,
+            rpc_policy,
+            log_limits,
+            history_capacity,
+            &fixture,
+        )?;
 
-        self.forks.insert(fork_id, Arc::new(fork));
+        let mut manager = manager.write_or_recover();
+        let Some(key) = idempotency_key else {
+            return manager.insert_fork(fork_id, fork);
+        };
+        // A concurrent request with the same key may have won the race
+        if let Some(existing) = manager.idempotent_fork(key) {
+            return Ok(existing);
+        }
+        manager.insert_fork(fork_id, fork)?;
+        manager
+            .idempotency_keys
+            .insert(key.to_string(), (fork_id, Instant::now()));
+        Ok(fork_id)
+    }
 
+    /// Gets the fork created with an idempotency key, if the key was seen
+    /// recently and the fork is still alive
+    fn idempotent_fork(&mut self, key: &str) -> Option<Uuid> {
+        self.prune_idempotency_keys();
+        self.idempotency_keys
+            .get(key)
+            .map(|(fork_id, _)| *fork_id)
+            .filter(|fork_id| self.forks.contains_key(fork_id))
+    }
+
+    fn prune_idempotency_keys(&mut self) {
+        self.idempotency_keys
+            .retain(|_, (_, seen)| seen.elapsed() < IDEMPOTENCY_KEY_TTL);
+    }
+
+    /// Fails if the maximum number of forks are alive
+    fn check_capacity(&self) -> anyhow::Result<()> {
+        if self.forks.len() >= self.max_forks {
+            anyhow::bail!(
+                "Fork limit reached ({}/{}), delete unused forks before creating new ones",
+                self.forks.len(),
+                self.max_forks
+            );
+        }
+        Ok(())
+    }
+
+    fn insert_fork(&mut self, fork_id: Uuid, fork: Fork) -> anyhow::Result<Uuid> {
+        self.check_capacity()?;
+        self.forks.insert(fork_id, Arc::new(fork));
         Ok(fork_id)
     }
 
+    /// Copy of the manager holding only the fork `fork_id`, with the same
+    /// settings. Long operations on a fork (executions, mainnet fetches) run
+    /// on it once the manager's lock is released, so they don't stall fork
+    /// creations, deletions and the cleanup waiting for that lock.
+    pub fn scoped(&self, fork_id: &Uuid) -> ForkManager {
+        ForkManager {
+            forks: self
+                .get_fork(fork_id)
+                .map(|fork| (*fork_id, fork))
+                .into_iter()
+                .collect(),
+            recently_expired: self.recently_expired.clone(),
+            idempotency_keys: HashMap::new(),
+            last_cleanup: None,
+            fixture: Arc::clone(&self.fixture),
+            allowed_rpc_urls: self.allowed_rpc_urls.clone(),
+            ..*self
+        }
+    }
+
     pub fn get_fork(&self, id: &Uuid) -> Option<Arc<Fork>> {
-        self.forks.get(id).map(|entry| Arc::clone(entry))
+        self.forks.get(id).map(Arc::clone)
+    }
+
+    /// Lists the live forks, oldest first. A `label` filter of `key` keeps
+    /// the forks having that label, `key=value` the forks where it has that
+    /// value.
+    pub fn list_forks(&self, label: Option<&str>) -> Vec<ForkSummary> {
+        let filter = label.map(|label| match label.split_once('=') {
+            Some((key, value)) => (key, Some(value)),
+            None => (label, None),
+        });
+        let now = Instant::now();
+        let mut forks: Vec<(&Uuid, &Arc<Fork>)> = self
+            .forks
+            .iter()
+            .filter(|(_id, fork)| fork.expires_at > now)
+            .filter(|(_id, fork)| match filter {
+                Some((key, value)) => fork
+                    .options
+                    .labels
+                    .get(key)
+                    .is_some_and(|v| value.is_none_or(|value| v == value)),
+                None => true,
+            })
+            .collect();
+        // Every fork lives as long, so expiry follows creation order
+        forks.sort_by_key(|(_id, fork)| fork.expires_at);
+
+        forks
+            .into_iter()
+            .map(|(id, fork)| ForkSummary {
+                id: *id,
+                name: fork.options.name.clone(),
+                labels: fork.options.labels.clone(),
+                offline: fork.options.offline,
+                expires_at: fork.expires_at_utc(),
+            })
+            .collect()
+    }
+
+    /// Deletes a fork, telling apart unknown forks from expired ones
+    pub fn delete_fork(&mut self, id: &Uuid) -> DeleteForkResult {
+        match self.forks.remove(id) {
+            Some(fork) if fork.expires_at <= Instant::now() => {
+                self.remember_expired(*id);
+                DeleteForkResult::Expired
+            }
+            Some(_) => DeleteForkResult::Deleted,
+            None if self.recently_expired.contains(id) => DeleteForkResult::Expired,
+            None => DeleteForkResult::NotFound,
+        }
+    }
+
+    /// Deletes every fork of `ids`, reporting the outcome of each. Missing
+    /// forks don't stop the others from being deleted.
+    pub fn delete_forks(&mut self, ids: &[Uuid]) -> BatchDeleteResult {
+        let results: Vec<ForkDeletion> = ids
+            .iter()
+            .map(|id| ForkDeletion {
+                id: *id,
+                result: self.delete_fork(id),
+            })
+            .collect();
+        let deleted = results
+            .iter()
+            .filter(|deletion| deletion.result == DeleteForkResult::Deleted)
+            .count();
+        BatchDeleteResult {
+            deleted,
+            not_found: results.len() - deleted,
+            results,
+        }
     }
 
-    pub fn delete_fork(&mut self, id: &Uuid) -> bool {
-        self.forks.remove(id).is_some()
+    /// Remembers an expired fork id, forgetting the oldest one when full
+    fn remember_expired(&mut self, id: Uuid) {
+        if self.recently_expired.len() == RECENTLY_EXPIRED_CAPACITY {
+            self.recently_expired.pop_front();
+        }
+        self.recently_expired.push_back(id);
     }
 
-    /// Function which should run in the background to clean up expired forks
-    pub fn cleanup_expired(&mut self) {
+    /// Function which should run in the background to clean up expired
+    /// forks, returning how many were removed
+    pub fn cleanup_expired(&mut self) -> usize {
         let now = Instant::now();
         let expired: Vec<Uuid> = self
             .forks
@@ -105,10 +1250,23 @@ impl ForkManager {
             .map(|(id, _fork)| *id)
             .collect();
 
+        let swept = expired.len();
         for id in expired {
             self.forks.remove(&id);
-            println!("Cleaned up expired fork {}", id);
+            self.remember_expired(id);
+            info!(fork_id = %id, "cleaned up expired fork");
         }
+        self.prune_idempotency_keys();
+        self.last_cleanup = Some(CleanupReport {
+            at: Utc::now(),
+            swept,
+        });
+        swept
+    }
+
+    /// Outcome of the latest cleanup, if any ran yet
+    pub fn last_cleanup(&self) -> Option<CleanupReport> {
+        self.last_cleanup
     }
 
     /// Executes a transaction on a fork
@@ -116,111 +1274,402 @@ impl ForkManager {
         &self,
         fork_id: &Uuid,
         tx: VersionedTransaction,
+    ) -> anyhow::Result<TransactionMetadata> {
+        self.execute_transaction_with_rpc(fork_id, tx, None)
+    }
+
+    /// Executes a transaction on a fork, fetching its missing accounts from
+    /// `rpc_url` instead of the fork's endpoint, see [`ForkManager::request_rpc`]
+    #[tracing::instrument(skip_all, fields(fork_id = %fork_id))]
+    pub fn execute_transaction_with_rpc(
+        &self,
+        fork_id: &Uuid,
+        tx: VersionedTransaction,
+        rpc_url: Option<&str>,
     ) -> anyhow::Result<TransactionMetadata> {
         if let Some(fork) = self.get_fork(fork_id) {
-            let mut svm = fork.svm.lock().unwrap();
+            let rpc = self.request_rpc(&fork, rpc_url)?;
+            let mut svm = fork.svm.write_or_recover();
 
-            self.preload_missing_accounts(&mut svm, &tx);
-            let mut txns = fork.executed_transactions.lock().unwrap();
+            self.preload_missing_accounts(&fork, rpc.as_ref(), &mut svm, &tx, false);
 
-            match svm.send_transaction(tx) {
-                Ok(res) => {
-                    txns.push(TransactionRecord {
-                        txn: res.clone(),
-                        time: Local::now().to_string(),
-                        success: true,
-                    });
-                    return Ok(res);
-                }
-                Err(e) => {
-                    txns.push(TransactionRecord {
-                        txn: e.meta,
-                        time: Local::now().to_string(),
-                        success: false,
-                    });
-                    return Err(anyhow::Error::new(e.err));
-                }
-            };
+            execute_locked(&fork, &mut svm, tx).map_err(|e| anyhow::Error::new(e.err))
         } else {
             anyhow::bail!("Fork not found");
         }
     }
 
     /// Simulates a transaction on a fork
+    #[cfg(test)]
     pub fn simulate_transaction(
         &self,
         fork_id: &Uuid,
         tx: VersionedTransaction,
     ) -> anyhow::Result<SimulatedTransactionInfo> {
+        self.simulate_transaction_detailed(fork_id, tx, SimulateOptions::default())?
+            .map_err(|e| anyhow::Error::new(e.err))
+    }
+
+    /// Simulates a transaction on a fork, keeping the metadata (logs,
+    /// compute units...) of failed simulations
+    #[tracing::instrument(skip_all, fields(fork_id = %fork_id))]
+    pub fn simulate_transaction_detailed(
+        &self,
+        fork_id: &Uuid,
+        tx: VersionedTransaction,
+        options: SimulateOptions,
+    ) -> anyhow::Result<Result<SimulatedTransactionInfo, FailedTransactionMetadata>> {
         if let Some(fork) = self.get_fork(fork_id) {
-            let mut svm = fork.svm.lock().unwrap();
+            let rpc = self.request_rpc(&fork, options.rpc_url.as_deref())?;
+            let mut svm = fork.svm.write_or_recover();
 
-            self.preload_missing_accounts(&mut svm, &tx);
-            let mut txns = fork.simulated_transactions.lock().unwrap();
+            self.preload_missing_accounts(
+                &fork,
+                rpc.as_ref(),
+                &mut svm,
+                &tx,
+                options.refresh_accounts,
+            );
 
-            match svm.simulate_transaction(tx) {
-                Ok(res) => {
-                    txns.push(TransactionRecord {
-                        txn: res.meta.clone(),
-                        time: Local::now().to_string(),
-                        success: false,
-                    });
-                    return Ok(res);
-                }
-                Err(e) => {
-                    txns.push(TransactionRecord {
-                        txn: e.meta,
-                        time: Local::now().to_string(),
-                        success: false,
-                    });
-                    return Err(anyhow::Error::new(e.err));
+            let impersonating = !options.impersonate.is_empty();
+            if impersonating {
+                let verified = verify_unimpersonated_signatures(&tx, &options.impersonate)?;
+                if !verified && !options.allow_unsigned && fork.options.validation.sigverify {
+                    return Ok(Err(FailedTransactionMetadata {
+                        err: TransactionError::SignatureFailure,
+                        meta: TransactionMetadata {
+                            signature: tx.signatures.first().copied().unwrap_or_default(),
+                            ..Default::default()
+                        },
+                    }));
                 }
+                warn!(impersonate = ?options.impersonate, "simulating with impersonated signers");
             }
+
+            // Signatures are only skipped for this simulation, the lock
+            // being held until they are checked again
+            let skip_sigverify =
+                (options.allow_unsigned || impersonating) && fork.options.validation.sigverify;
+            let result = if skip_sigverify {
+                simulate_locked(&fork, &mut SigverifySkipped::new(&mut svm), tx)
+            } else {
+                simulate_locked(&fork, &mut svm, tx)
+            };
+            Ok(result)
         } else {
             anyhow::bail!("Fork not found");
         }
     }
 
-    /// Helper function which loads on-demand accounts from the mainnet
-    /// which are not present locally on the fork
-    fn preload_missing_accounts(&self, svm: &mut LiteSVM, tx: &VersionedTransaction) {
-        let client = RpcClient::new(DEFAULT_RPC_CLIENT.to_string());
-        let account_keys = tx.message.static_account_keys();
-
-        for key in account_keys {
-            if svm.get_account(key).is_none() {
-                if let Ok(acc) = client.get_account(key) {
-                    let _ = svm.set_account(*key, acc);
-                    println!("Loaded mainnet account {} into fork", key);
-                } else {
-                    println!("Warning: account {} not found on mainnet RPC", key);
-                }
-            }
-        }
-    }
-
-    /// Sets lamports of an address
-    pub fn set_lamports(
+    /// Simulates a transaction and only executes it if the simulation
+    /// succeeded, without releasing the fork in between so its state can't
+    /// change. A failed simulation leaves the fork untouched.
+    #[cfg(test)]
+    #[tracing::instrument(skip_all, fields(fork_id = %fork_id))]
+    pub fn send_transaction(
+        &self,
+        fork_id: &Uuid,
+        tx: VersionedTransaction,
+    ) -> anyhow::Result<SendResult> {
+        self.send_transaction_with_rpc(fork_id, tx, None)
+    }
+
+    /// Simulates then executes a transaction like
+    /// [`ForkManager::send_transaction`], fetching its missing accounts from
+    /// `rpc_url` instead of the fork's endpoint
+    pub fn send_transaction_with_rpc(
+        &self,
+        fork_id: &Uuid,
+        tx: VersionedTransaction,
+        rpc_url: Option<&str>,
+    ) -> anyhow::Result<SendResult> {
+        let Some(fork) = self.get_fork(fork_id) else {
+            anyhow::bail!("Fork not found");
+        };
+        let rpc = self.request_rpc(&fork, rpc_url)?;
+        let mut svm = fork.svm.write_or_recover();
+
+        self.preload_missing_accounts(&fork, rpc.as_ref(), &mut svm, &tx, false);
+
+        let simulation = match simulate_locked(&fork, &mut svm, tx.clone()) {
+            Ok(info) => info.meta,
+            Err(e) => {
+                return Ok(SendResult {
+                    simulation: e.meta,
+                    execution: None,
+                    error: Some(e.err),
+                });
+            }
+        };
+        Ok(match execute_locked(&fork, &mut svm, tx) {
+            Ok(meta) => SendResult {
+                simulation,
+                execution: Some(meta),
+                error: None,
+            },
+            Err(e) => SendResult {
+                simulation,
+                execution: Some(e.meta),
+                error: Some(e.err),
+            },
+        })
+    }
+
+    /// Estimates the fee of a transaction on a fork without executing it
+    pub fn estimate_fee(
+        &self,
+        fork_id: &Uuid,
+        tx: &VersionedTransaction,
+    ) -> anyhow::Result<FeeEstimate> {
+        if let Some(fork) = self.get_fork(fork_id) {
+            Ok(fork.estimate_fee(tx))
+        } else {
+            anyhow::bail!("Fork not found");
+        }
+    }
+
+    /// Accounts a transaction reads and writes, resolving its lookup tables
+    /// from the fork (or mainnet) without executing it
+    pub fn touched_accounts(
+        &self,
+        fork_id: &Uuid,
+        tx: &VersionedTransaction,
+    ) -> anyhow::Result<Vec<TouchedAccount>> {
+        if self.get_fork(fork_id).is_none() {
+            anyhow::bail!("Fork not found");
+        }
+        inspect::touched_accounts(&tx.message, |table| {
+            let account = self
+                .get_account(fork_id, *table)
+                .map_err(|e| anyhow::anyhow!("Lookup table {}: {}", table, e))?;
+            LookupTable::from_account(&account)
+                .map(|lookup_table| lookup_table.addresses)
+                .map_err(|e| anyhow::anyhow!("Lookup table {}: {}", table, e))
+        })
+    }
+
+    /// Simulates a transaction and only reports whether it fits in its
+    /// compute budget. Failures other than exceeding the budget are
+    /// returned as errors.
+    pub fn compute_fit(
+        &self,
+        fork_id: &Uuid,
+        tx: VersionedTransaction,
+        options: SimulateOptions,
+    ) -> anyhow::Result<ComputeFit> {
+        let fork = self
+            .get_fork(fork_id)
+            .ok_or_else(|| anyhow::anyhow!("Fork not found"))?;
+        let compute_budget =
+            fees::compute_unit_limit(&tx.message, &fork.fee_schedule(), &fork.feature_set);
+        let (compute_units_consumed, fits) =
+            match self.simulate_transaction_detailed(fork_id, tx, options)? {
+                Ok(info) => (info.meta.compute_units_consumed, true),
+                Err(failed)
+                    if matches!(
+                        failed.err,
+                        TransactionError::InstructionError(
+                            _,
+                            InstructionError::ComputationalBudgetExceeded
+                        )
+                    ) =>
+                {
+                    (failed.meta.compute_units_consumed, false)
+                }
+                Err(failed) => return Err(failed.err.into()),
+            };
+        Ok(ComputeFit {
+            compute_units_consumed,
+            compute_budget,
+            fits,
+        })
+    }
+
+    /// Returns the fee parameters of a fork
+    pub fn fee_schedule(&self, fork_id: &Uuid) -> anyhow::Result<FeeSchedule> {
+        if let Some(fork) = self.get_fork(fork_id) {
+            Ok(fork.fee_schedule())
+        } else {
+            anyhow::bail!("Fork not found");
+        }
+    }
+
+    /// Derives the program address of `seeds` and its bump seed, like
+    /// `Pubkey::find_program_address` but rejecting invalid seeds
+    pub fn find_pda(
+        &self,
+        fork_id: &Uuid,
+        program_id: &Pubkey,
+        seeds: &[Vec<u8>],
+    ) -> anyhow::Result<ProgramAddress> {
+        if self.get_fork(fork_id).is_none() {
+            anyhow::bail!("Fork not found");
+        }
+        // The bump seed is appended to the given seeds
+        if seeds.len() >= MAX_SEEDS {
+            anyhow::bail!(
+                "At most {} seeds are allowed, got {}",
+                MAX_SEEDS - 1,
+                seeds.len()
+            );
+        }
+        if let Some((index, seed)) = seeds
+            .iter()
+            .enumerate()
+            .find(|(_index, seed)| seed.len() > MAX_SEED_LEN)
+        {
+            anyhow::bail!(
+                "Seed {} is {} bytes long, at most {} are allowed",
+                index,
+                seed.len(),
+                MAX_SEED_LEN
+            );
+        }
+
+        let seeds: Vec<&[u8]> = seeds.iter().map(Vec::as_slice).collect();
+        let Some((address, bump)) = Pubkey::try_find_program_address(&seeds, program_id) else {
+            anyhow::bail!("No bump seed yields an address off the curve");
+        };
+        Ok(ProgramAddress {
+            address: address.to_string(),
+            bump,
+        })
+    }
+
+    /// Derives the associated token account of `owner` for `mint`, under
+    /// the SPL token program by default or Token-2022. These are the
+    /// addresses [`ForkManager::set_token_balances`] defaults to.
+    pub fn associated_token_address(
+        &self,
+        fork_id: &Uuid,
+        owner: &Pubkey,
+        mint: &Pubkey,
+        token_program: Option<Pubkey>,
+    ) -> anyhow::Result<ProgramAddress> {
+        if self.get_fork(fork_id).is_none() {
+            anyhow::bail!("Fork not found");
+        }
+        let spl_token = Pubkey::new_from_array(*ID.as_array());
+        let token_program = token_program.unwrap_or(spl_token);
+        if token_program != spl_token && token_program != TOKEN_2022_PROGRAM {
+            anyhow::bail!("Unsupported token program {}", token_program);
+        }
+        let (address, bump) = derive_associated_token_address(owner, mint, &token_program);
+        Ok(ProgramAddress {
+            address: address.to_string(),
+            bump,
+        })
+    }
+
+    /// Client of a request's `rpc_url`, used for that request's fetches
+    /// instead of the fork's client. `None` for offline forks, which never
+    /// fetch, and for invalid URLs, which fall back to the fork's endpoint.
+    ///
+    /// Requests could otherwise make the server reach any host of its
+    /// network, so URLs outside of the allowed ones are rejected.
+    fn request_rpc(
+        &self,
+        fork: &Fork,
+        rpc_url: Option<&str>,
+    ) -> anyhow::Result<Option<MainnetRpc>> {
+        let Some(url) = rpc_url else {
+            return Ok(None);
+        };
+        if fork.rpc.is_none() {
+            return Ok(None);
+        }
+        if !is_valid_rpc_url(url) {
+            warn!(rpc_url = url, "invalid RPC URL, using the fork's endpoint");
+            return Ok(None);
+        }
+        if !is_allowed_rpc_url(url, &self.allowed_rpc_urls) {
+            anyhow::bail!("RPC URL {} is not in RPC_URL_ALLOWLIST", url);
+        }
+        Ok(Some(MainnetRpc::new(
+            url,
+            fork.options.slot,
+            fork.options.commitment,
+            self.rpc_policy,
+        )))
+    }
+
+    /// Helper function which loads on-demand accounts from the mainnet
+    /// which are not present locally on the fork, through `rpc` or else the
+    /// fork's client. With `refresh`, accounts present locally are re-fetched
+    /// and overwritten too, except programs and accounts missing on mainnet
+    /// (e.g. seeded ones).
+    fn preload_missing_accounts(
+        &self,
+        fork: &Fork,
+        rpc: Option<&MainnetRpc>,
+        svm: &mut LiteSVM,
+        tx: &VersionedTransaction,
+        refresh: bool,
+    ) {
+        let Some(rpc) = rpc.or(fork.rpc.as_ref()) else {
+            return;
+        };
+        let account_keys = tx.message.static_account_keys();
+
+        for key in account_keys {
+            match svm.get_account(key) {
+                Some(local) if refresh && !local.executable => {
+                    if let Ok(Some(acc)) = rpc.get_account(key) {
+                        if let Err(e) = check_data_len(key, acc.data.len()) {
+                            warn!(account = %key, "keeping local account: {}", e);
+                        } else if svm.set_account(*key, acc).is_ok() {
+                            info!(account = %key, "refreshed account from mainnet");
+                        }
+                    }
+                }
+                Some(_) => {}
+                None => {
+                    if let Ok(Some(acc)) = rpc.get_account(key) {
+                        if let Err(e) = check_data_len(key, acc.data.len()) {
+                            warn!(account = %key, "skipping mainnet account: {}", e);
+                        } else if svm.set_account(*key, acc).is_ok() {
+                            fork.track_accounts([key]);
+                            info!(account = %key, "loaded mainnet account into fork");
+                        }
+                    } else {
+                        warn!(account = %key, "account not found on mainnet RPC");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Sets lamports of an address
+    #[tracing::instrument(skip_all, fields(fork_id = %fork_id))]
+    pub fn set_lamports(
         &self,
         fork_id: &Uuid,
         pubkey: Pubkey,
         lamports: u64,
+        allow_rent_paying: bool,
     ) -> anyhow::Result<()> {
         if let Some(fork) = self.get_fork(fork_id) {
-            let mut svm = fork.svm.lock().unwrap();
-            let mut account = match svm.get_account(&pubkey) {
-                Some(acc) => acc,
-                None => Account::new(0, 0, &system_program::ID),
-            };
-            account.lamports = lamports;
-            svm.set_account(pubkey, account)?;
+            let mut svm = fork.svm.write_or_recover();
+            fork.write_lamports(&mut *svm, pubkey, lamports, allow_rent_paying)?;
+            fork.state_changed();
             Ok(())
         } else {
             anyhow::bail!("Fork not found");
         }
     }
 
-    /// Sets tokens of an address for a token
+    /// Sets tokens of an address for a token.
+    ///
+    /// The mint must be a valid SPL mint. When it is neither present in the
+    /// fork nor on mainnet, a minimal mint with `decimals` (default 9) and
+    /// no authorities is created. The mint's supply changes by the difference
+    /// with the previous balance, as minting or burning would. With
+    /// `check_supply`, amounts exceeding the supply of an existing mint are
+    /// rejected.
+    ///
+    /// A missing token account is created with `lamports`, by default the
+    /// rent-exempt minimum of the fork.
+    #[tracing::instrument(skip_all, fields(fork_id = %fork_id))]
     pub fn set_token_balance(
         &self,
         fork_id: &Uuid,
@@ -228,226 +1677,5166 @@ impl ForkManager {
         mint: Pubkey,
         owner: Pubkey,
         amount: u64,
+        options: TokenBalanceOptions,
     ) -> anyhow::Result<()> {
         if let Some(fork) = self.get_fork(fork_id) {
-            let mut svm = fork.svm.lock().unwrap();
+            let mut svm = fork.svm.write_or_recover();
+            self.write_token_balance(
+                &fork,
+                &mut *svm,
+                token_account_pubkey,
+                mint,
+                owner,
+                amount,
+                options,
+            )?;
+            fork.state_changed();
+            Ok(())
+        } else {
+            anyhow::bail!("Fork not found");
+        }
+    }
+
+    /// Helper function which sets tokens of an address in the svm
+    #[allow(clippy::too_many_arguments)]
+    fn write_token_balance(
+        &self,
+        fork: &Fork,
+        svm: &mut impl AccountStore,
+        token_account_pubkey: Pubkey,
+        mint: Pubkey,
+        owner: Pubkey,
+        amount: u64,
+        options: TokenBalanceOptions,
+    ) -> anyhow::Result<()> {
+        let TokenBalanceOptions {
+            decimals,
+            check_supply,
+            lamports,
+        } = options;
+        let previous = svm
+            .get_account(&token_account_pubkey)
+            .and_then(|account| unpack_token_account(&token_account_pubkey, &account).ok())
+            .filter(|token_acc| token_acc.mint.to_bytes() == mint.to_bytes())
+            .map_or(0, |token_acc| token_acc.amount);
 
-            let mut account = svm.get_account(&token_account_pubkey).unwrap_or_else(|| {
+        let supply = self.resolve_mint(fork, svm, mint, decimals)?;
+        if check_supply
+            && let Some(supply) = supply
+            && amount > supply
+        {
+            anyhow::bail!(
+                "Amount {} exceeds the supply {} of mint {}",
+                amount,
+                supply,
+                mint
+            );
+        }
+        update_mint_supply(svm, mint, previous, amount)?;
+
+        let mut account = match svm.get_account(&token_account_pubkey) {
+            Some(account) => account,
+            None => {
+                let lamports =
+                    lamports.unwrap_or_else(|| svm.rent().minimum_balance(TokenAccount::LEN));
+                fork.pay_token_account_rent(svm, owner, lamports)?;
                 Account::new(
-                    1_000_000,
+                    lamports,
                     TokenAccount::LEN,
                     &Pubkey::new_from_array(*ID.as_array()),
                 )
-            });
+            }
+        };
 
-            let mut token_acc = TokenAccount::default();
-            token_acc.mint = pubkey::Pubkey::new_from_array(*mint.as_array());
-            token_acc.owner = pubkey::Pubkey::new_from_array(*owner.as_array());
-            token_acc.amount = amount;
-            token_acc.state = AccountState::Initialized;
+        let token_acc = TokenAccount {
+            mint: pubkey::Pubkey::new_from_array(*mint.as_array()),
+            owner: pubkey::Pubkey::new_from_array(*owner.as_array()),
+            amount,
+            state: AccountState::Initialized,
+            ..Default::default()
+        };
 
-            let mut data = vec![0u8; TokenAccount::LEN];
-            token_acc.pack_into_slice(&mut data);
+        let mut data = vec![0u8; TokenAccount::LEN];
+        token_acc.pack_into_slice(&mut data);
 
-            account.data = data;
-            account.owner = Pubkey::new_from_array(*ID.as_array());
-            account.executable = false;
-            account.rent_epoch = 0;
+        account.data = data;
+        account.owner = Pubkey::new_from_array(*ID.as_array());
+        account.executable = false;
+        account.rent_epoch = 0;
 
-            svm.set_account(token_account_pubkey, account)?;
-            Ok(())
-        } else {
+        svm.set_account(token_account_pubkey, account)?;
+        fork.track_accounts([&token_account_pubkey]);
+        Ok(())
+    }
+
+    /// Applies a whole setup document to a fork atomically: every entry is
+    /// staged on top of the fork's state first, and written to it once every
+    /// entry succeeded.
+    #[tracing::instrument(skip_all, fields(fork_id = %fork_id))]
+    pub fn setup(&self, fork_id: &Uuid, setup: ForkSetup) -> anyhow::Result<SetupResult> {
+        let Some(fork) = self.get_fork(fork_id) else {
             anyhow::bail!("Fork not found");
+        };
+
+        let lamports = setup
+            .lamports
+            .iter()
+            .map(|entry| {
+                Ok((
+                    parse_pubkey(&entry.pubkey)?,
+                    entry.lamports,
+                    entry.allow_rent_paying,
+                ))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let token_balances = setup
+            .token_balances
+            .iter()
+            .map(|entry| {
+                Ok((
+                    parse_pubkey(&entry.token_account)?,
+                    parse_pubkey(&entry.mint)?,
+                    parse_pubkey(&entry.owner)?,
+                    entry.amount,
+                    TokenBalanceOptions {
+                        decimals: entry.decimals,
+                        check_supply: entry.check_supply,
+                        lamports: entry.lamports,
+                    },
+                ))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let accounts = setup
+            .accounts
+            .iter()
+            .map(|entry| entry.to_account())
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let programs = setup
+            .programs
+            .iter()
+            .map(|entry| {
+                let bytes = engine::general_purpose::STANDARD
+                    .decode(&entry.program_base64)
+                    .map_err(|e| anyhow::anyhow!("Invalid program {}: {}", entry.program_id, e))?;
+                Ok((parse_pubkey(&entry.program_id)?, bytes))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let mut svm = fork.svm.write_or_recover();
+        let mut staged = StagedAccounts::new(&svm);
+        let mut result = SetupResult::default();
+
+        for (pubkey, amount, allow_rent_paying) in lamports {
+            fork.write_lamports(&mut staged, pubkey, amount, allow_rent_paying)?;
+            result.lamports.push(pubkey.to_string());
         }
-    }
+        for (token_account, mint, owner, amount, options) in token_balances {
+            self.write_token_balance(
+                &fork,
+                &mut staged,
+                token_account,
+                mint,
+                owner,
+                amount,
+                options,
+            )?;
+            result.token_balances.push(token_account.to_string());
+        }
+        for (pubkey, account) in accounts {
+            staged.set_account(pubkey, account)?;
+            fork.track_accounts([&pubkey]);
+            result.accounts.push(pubkey.to_string());
+        }
+        staged.verify_loadable(&fork.feature_set, &programs)?;
 
-    pub fn get_account(&self, fork_id: &Uuid, pubkey: Pubkey) -> anyhow::Result<Account> {
-        if let Some(fork) = self.get_fork(fork_id) {
-            let mut svm = fork.svm.lock().unwrap();
+        for (pubkey, account) in staged.into_writes() {
+            svm.set_account(pubkey, account)?;
+        }
+        for (program_id, bytes) in programs {
+            svm.add_program(program_id, &bytes)?;
+            fork.track_accounts([&program_id]);
+            result.programs.push(program_id.to_string());
+        }
+        fork.state_changed();
+        Ok(result)
+    }
 
-            if let Some(acc) = svm.get_account(&pubkey) {
-                println!("Account found locally!");
-                return Ok(acc);
-            }
+    /// Sets the balances of many token accounts under a single lock,
+    /// returning their addresses. Entries are all staged on top of the
+    /// fork's state first, so nothing changes if any fails.
+    #[tracing::instrument(skip_all, fields(fork_id = %fork_id))]
+    pub fn set_token_balances(
+        &self,
+        fork_id: &Uuid,
+        entries: Vec<TokenBalanceEntry>,
+    ) -> anyhow::Result<Vec<String>> {
+        let Some(fork) = self.get_fork(fork_id) else {
+            anyhow::bail!("Fork not found");
+        };
 
-            let client = RpcClient::new(DEFAULT_RPC_CLIENT.to_string());
-            match client.get_account(&pubkey) {
-                Ok(acc) => {
-                    svm.set_account(pubkey, acc.clone())?;
-                    println!("Account found on mainnet!");
-                    Ok(acc)
+        let token_program = Pubkey::new_from_array(*ID.as_array());
+        let balances = entries
+            .iter()
+            .map(|entry| {
+                if let Some(program) = &entry.token_program
+                    && parse_pubkey(program)? != token_program
+                {
+                    anyhow::bail!("Unsupported token program {}", program);
                 }
-                Err(_) => anyhow::bail!("Account not found on mainnet or fork"),
-            }
-        } else {
-            anyhow::bail!("Fork not found");
+                let mint = parse_pubkey(&entry.mint)?;
+                let owner = parse_pubkey(&entry.owner)?;
+                let token_account = match &entry.token_account {
+                    Some(token_account) => parse_pubkey(token_account)?,
+                    None => derive_associated_token_address(&owner, &mint, &token_program).0,
+                };
+                Ok((token_account, mint, owner, entry.amount))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let mut svm = fork.svm.write_or_recover();
+        let mut staged = StagedAccounts::new(&svm);
+        for (token_account, mint, owner, amount) in &balances {
+            self.write_token_balance(
+                &fork,
+                &mut staged,
+                *token_account,
+                *mint,
+                *owner,
+                *amount,
+                TokenBalanceOptions::default(),
+            )?;
+        }
+
+        for (pubkey, account) in staged.into_writes() {
+            svm.set_account(pubkey, account)?;
         }
+        fork.state_changed();
+        Ok(balances
+            .into_iter()
+            .map(|(token_account, ..)| token_account.to_string())
+            .collect())
     }
 
-    /// Gets all executed transactions on a fork
-    pub fn get_executed_transactions(
+    /// Balance of a token account, fetched from mainnet if missing from
+    /// the fork
+    pub fn get_token_balance(
         &self,
         fork_id: &Uuid,
-    ) -> anyhow::Result<Vec<TransactionRecord>> {
-        match self
-            .forks
-            .get(fork_id)
-            .unwrap()
-            .executed_transactions
-            .lock()
-        {
-            Ok(txns) => Ok(txns.to_vec()),
-            Err(_) => anyhow::bail!("failed to get executed transactions"),
-        }
+        token_account: Pubkey,
+    ) -> anyhow::Result<TokenBalance> {
+        let Some(account) = self.find_account(fork_id, token_account)? else {
+            anyhow::bail!("Token account {} not found", token_account);
+        };
+        let token_acc = unpack_token_account(&token_account, &account)?;
+        Ok(TokenBalance {
+            mint: token_acc.mint.to_string(),
+            owner: token_acc.owner.to_string(),
+            amount: token_acc.amount,
+            frozen: token_acc.state == AccountState::Frozen,
+        })
     }
 
-    /// Gets all simulated transactions on a fork
-    pub fn get_simulated_transactions(
+    /// Freezes or thaws a token account
+    #[tracing::instrument(skip_all, fields(fork_id = %fork_id))]
+    pub fn set_token_account_state(
         &self,
         fork_id: &Uuid,
-    ) -> anyhow::Result<Vec<TransactionRecord>> {
-        match self
-            .forks
-            .get(fork_id)
-            .unwrap()
-            .simulated_transactions
-            .lock()
-        {
-            Ok(txns) => Ok(txns.to_vec()),
-            Err(_) => anyhow::bail!("failed to get simulated transactions"),
-        }
+        token_account: Pubkey,
+        frozen: bool,
+    ) -> anyhow::Result<()> {
+        self.update_token_account(fork_id, token_account, |token_acc| {
+            token_acc.state = if frozen {
+                AccountState::Frozen
+            } else {
+                AccountState::Initialized
+            };
+        })
     }
-}
 
-/// Helper function to update the variables of a fork
-pub fn update_sysvars(svm: &mut LiteSVM) -> anyhow::Result<()> {
-    let client = RpcClient::new(DEFAULT_RPC_CLIENT.to_string());
-    let latest_blockhash = client.get_latest_blockhash()?;
-    let slot = client.get_slot()?;
-    let epochs = client.get_epoch_schedule()?;
+    /// Creates an initialized SPL mint with no supply at `mint`, or at a
+    /// random address if `None`, returning its address. Fails if an account
+    /// already exists there.
+    #[tracing::instrument(skip_all, fields(fork_id = %fork_id))]
+    pub fn create_mint(
+        &self,
+        fork_id: &Uuid,
+        mint: Option<Pubkey>,
+        decimals: u8,
+        mint_authority: Pubkey,
+        freeze_authority: Option<Pubkey>,
+    ) -> anyhow::Result<Pubkey> {
+        let Some(fork) = self.get_fork(fork_id) else {
+            anyhow::bail!("Fork not found");
+        };
+        let mint = mint.unwrap_or_else(|| Keypair::new().pubkey());
+        let to_v2 = |key: Pubkey| pubkey::Pubkey::new_from_array(key.to_bytes());
+
+        let mut svm = fork.svm.write_or_recover();
+        if svm.get_account(&mint).is_some() {
+            anyhow::bail!("Account {} already exists", mint);
+        }
+
+        let mint_state = Mint {
+            mint_authority: COption::Some(to_v2(mint_authority)),
+            freeze_authority: freeze_authority.map(to_v2).into(),
+            decimals,
+            is_initialized: true,
+            ..Default::default()
+        };
 
-    let mut slot_hashes = svm.get_sysvar::<SlotHashes>().clone();
-    if !slot_hashes.iter().any(|(_, h)| *h == latest_blockhash) {
-        slot_hashes.push((slot, latest_blockhash));
-        svm.set_sysvar(&SlotHashes::new(slot_hashes.as_ref()));
+        let token_program = Pubkey::new_from_array(*ID.as_array());
+        let mut account = Account::new(
+            svm.get_sysvar::<Rent>().minimum_balance(Mint::LEN),
+            Mint::LEN,
+            &token_program,
+        );
+        mint_state.pack_into_slice(&mut account.data);
+        svm.set_account(mint, account)?;
+        fork.track_accounts([&mint]);
+        fork.state_changed();
+        Ok(mint)
     }
 
-    let mut clock = svm.get_sysvar::<Clock>();
-    clock.slot = slot;
-    clock.unix_timestamp = Utc::now().timestamp();
-    svm.set_sysvar(&clock);
-    svm.set_sysvar(&epochs);
+    /// Creates an address lookup table holding `addresses` at `address`, or
+    /// at a random address if `None`, returning its address. The addresses
+    /// are usable by v0 transactions right away. Fails if an account already
+    /// exists there.
+    #[tracing::instrument(skip_all, fields(fork_id = %fork_id))]
+    pub fn create_lookup_table(
+        &self,
+        fork_id: &Uuid,
+        address: Option<Pubkey>,
+        authority: Option<Pubkey>,
+        addresses: Vec<Pubkey>,
+    ) -> anyhow::Result<Pubkey> {
+        let Some(fork) = self.get_fork(fork_id) else {
+            anyhow::bail!("Fork not found");
+        };
+        if addresses.len() > lookup_table::MAX_ADDRESSES {
+            anyhow::bail!(
+                "Lookup tables hold at most {} addresses",
+                lookup_table::MAX_ADDRESSES
+            );
+        }
+        let address = address.unwrap_or_else(|| Keypair::new().pubkey());
 
-    Ok(())
-}
+        let mut svm = fork.svm.write_or_recover();
+        if svm.get_account(&address).is_some() {
+            anyhow::bail!("Account {} already exists", address);
+        }
+        let slot = svm.get_sysvar::<Clock>().slot;
+        let data = LookupTable::new(authority, addresses, slot).to_data();
+        let account = Account {
+            lamports: svm.get_sysvar::<Rent>().minimum_balance(data.len()),
+            data,
+            owner: lookup_table::ADDRESS_LOOKUP_TABLE_PROGRAM,
+            executable: false,
+            rent_epoch: 0,
+        };
+        svm.set_account(address, account)?;
+        fork.track_accounts([&address]);
+        fork.state_changed();
+        Ok(address)
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use solana_sdk::signature::{Keypair, Signer};
-    use std::time::Duration;
+    /// Appends `addresses` to an active lookup table of the fork, topping
+    /// its lamports up to the rent-exempt minimum. Returns every address of
+    /// the table.
+    #[tracing::instrument(skip_all, fields(fork_id = %fork_id))]
+    pub fn extend_lookup_table(
+        &self,
+        fork_id: &Uuid,
+        address: Pubkey,
+        addresses: Vec<Pubkey>,
+    ) -> anyhow::Result<Vec<Pubkey>> {
+        let Some(fork) = self.get_fork(fork_id) else {
+            anyhow::bail!("Fork not found");
+        };
 
-    #[test]
-    fn test_fork_creation() {
-        let mut manager = ForkManager::new();
-        let fork_id = manager.create_fork().expect("Failed to create fork");
+        let mut svm = fork.svm.write_or_recover();
+        let Some(mut account) = svm.get_account(&address) else {
+            anyhow::bail!("Lookup table {} not found in fork", address);
+        };
+        let mut table = LookupTable::from_account(&account)
+            .map_err(|e| anyhow::anyhow!("Lookup table {}: {}", address, e))?;
+        if table.authority.is_none() {
+            anyhow::bail!("Lookup table {} is frozen", address);
+        }
+        if table.deactivation_slot != u64::MAX {
+            anyhow::bail!("Lookup table {} is deactivated", address);
+        }
+        if table.addresses.len() + addresses.len() > lookup_table::MAX_ADDRESSES {
+            anyhow::bail!(
+                "Lookup tables hold at most {} addresses",
+                lookup_table::MAX_ADDRESSES
+            );
+        }
 
-        assert!(manager.forks.contains_key(&fork_id));
+        table.extend(addresses, svm.get_sysvar::<Clock>().slot);
+        account.data = table.to_data();
+        let minimum_balance = svm.get_sysvar::<Rent>().minimum_balance(account.data.len());
+        account.lamports = account.lamports.max(minimum_balance);
+        svm.set_account(address, account)?;
+        fork.track_accounts([&address]);
+        fork.state_changed();
+        Ok(table.addresses)
     }
 
-    #[test]
-    fn test_get_fork() {
-        let mut manager = ForkManager::new();
-        let fork_id = manager.create_fork().expect("Failed to create fork");
-
-        let fork = manager.get_fork(&fork_id);
-        assert!(fork.is_some());
+    /// Sets the delegate of a token account and the amount it may transfer
+    #[tracing::instrument(skip_all, fields(fork_id = %fork_id))]
+    pub fn set_token_delegate(
+        &self,
+        fork_id: &Uuid,
+        token_account: Pubkey,
+        delegate: Pubkey,
+        amount: u64,
+    ) -> anyhow::Result<()> {
+        self.update_token_account(fork_id, token_account, |token_acc| {
+            token_acc.delegate =
+                COption::Some(pubkey::Pubkey::new_from_array(*delegate.as_array()));
+            token_acc.delegated_amount = amount;
+        })
     }
 
-    #[test]
-    fn test_delete_fork() {
-        let mut manager = ForkManager::new();
-        let fork_id = manager.create_fork().expect("Failed to create fork");
+    /// Helper function which unpacks an existing token account, applies
+    /// `update` on it and writes it back
+    fn update_token_account(
+        &self,
+        fork_id: &Uuid,
+        token_account: Pubkey,
+        update: impl FnOnce(&mut TokenAccount),
+    ) -> anyhow::Result<()> {
+        if let Some(fork) = self.get_fork(fork_id) {
+            let mut svm = fork.svm.write_or_recover();
 
-        let deleted = manager.delete_fork(&fork_id);
-        assert!(deleted);
-        assert!(!manager.forks.contains_key(&fork_id));
-    }
+            let Some(mut account) = svm.get_account(&token_account) else {
+                anyhow::bail!("Token account {} not found in fork", token_account);
+            };
+            let mut token_acc = unpack_token_account(&token_account, &account)?;
 
-    #[test]
-    fn test_cleanup_expired() {
-        let mut manager = ForkManager::new();
-        let fork_id = manager.create_fork().expect("Failed to create fork");
+            update(&mut token_acc);
+            token_acc.pack_into_slice(&mut account.data);
 
-        if let Some(fork) = manager.forks.get_mut(&fork_id) {
-            let fork_mut = Arc::get_mut(fork).unwrap();
-            fork_mut.expires_at = Instant::now() - Duration::from_secs(1);
+            svm.set_account(token_account, account)?;
+            fork.state_changed();
+            Ok(())
+        } else {
+            anyhow::bail!("Fork not found");
         }
-
-        assert_eq!(manager.forks.len(), 1);
-        manager.cleanup_expired();
-        assert_eq!(manager.forks.len(), 0);
     }
 
-    #[test]
-    fn test_set_lamports() {
-        let mut manager = ForkManager::new();
-        let fork_id = manager.create_fork().expect("Failed to create fork");
+    /// Reassigns an account to the `owner` program, keeping its lamports,
+    /// data and flags. An account missing from the fork is fetched from
+    /// mainnet first.
+    pub fn set_account_owner(
+        &self,
+        fork_id: &Uuid,
+        pubkey: Pubkey,
+        owner: Pubkey,
+    ) -> anyhow::Result<()> {
+        self.update_account(fork_id, pubkey, |account| account.owner = owner)
+    }
 
-        let keypair = Keypair::new();
-        let pubkey = keypair.pubkey();
-        let lamports = 1_000_000;
+    /// Flips the `executable` flag of an account, keeping the rest of it.
+    /// The runtime loads executable accounts when they are written, so only
+    /// accounts a loader owns holding a valid program can be marked
+    /// executable.
+    pub fn set_executable(
+        &self,
+        fork_id: &Uuid,
+        pubkey: Pubkey,
+        executable: bool,
+    ) -> anyhow::Result<()> {
+        if executable
+            && let Some(account) = self.find_account(fork_id, pubkey)?
+            && !LOADERS.contains(&account.owner)
+        {
+            anyhow::bail!(
+                "Account {} is owned by {}, not a loader program, it can't be executable",
+                pubkey,
+                account.owner
+            );
+        }
+        self.update_account(fork_id, pubkey, |account| account.executable = executable)
+            .map_err(|e| anyhow::anyhow!("Account {} can't be loaded as a program: {}", pubkey, e))
+    }
 
-        let result = manager.set_lamports(&fork_id, pubkey, lamports);
-        assert!(result.is_ok());
+    /// Helper function which applies `update` on an existing account and
+    /// writes it back
+    fn update_account(
+        &self,
+        fork_id: &Uuid,
+        pubkey: Pubkey,
+        update: impl FnOnce(&mut Account),
+    ) -> anyhow::Result<()> {
+        if self.find_account(fork_id, pubkey)?.is_none() {
+            anyhow::bail!("Account {} not found on mainnet or fork", pubkey);
+        }
+        let Some(fork) = self.get_fork(fork_id) else {
+            anyhow::bail!("Fork not found");
+        };
 
-        let account = manager
-            .get_account(&fork_id, pubkey)
-            .expect("Failed to get account");
-        assert_eq!(account.lamports, lamports);
+        let mut svm = fork.svm.write_or_recover();
+        // Read it again under the write lock, it may have changed meanwhile
+        let Some(mut account) = svm.get_account(&pubkey) else {
+            anyhow::bail!("Account {} not found in fork", pubkey);
+        };
+        update(&mut account);
+        svm.set_account(pubkey, account)?;
+        fork.track_accounts([&pubkey]);
+        fork.state_changed();
+        Ok(())
     }
 
-    #[test]
-    fn test_set_token_balance() {
-        let mut manager = ForkManager::new();
-        let fork_id = manager.create_fork().expect("Failed to create fork");
+    /// Helper function which makes sure `mint` is a valid SPL mint in the
+    /// fork, loading it from mainnet or creating it without supply if
+    /// missing. Returns the supply of an existing mint, `None` if it was
+    /// created.
+    fn resolve_mint(
+        &self,
+        fork: &Fork,
+        svm: &mut impl AccountStore,
+        mint: Pubkey,
+        decimals: Option<u8>,
+    ) -> anyhow::Result<Option<u64>> {
+        let token_program = Pubkey::new_from_array(*ID.as_array());
 
-        let mint = Pubkey::new_unique();
-        let user = Pubkey::new_unique();
-        let token_account = Pubkey::new_unique();
+        let mut account = svm.get_account(&mint);
+        if account.is_none()
+            && let Some(rpc) = &fork.rpc
+            && let Ok(Some(acc)) = rpc.get_account(&mint)
+        {
+            svm.set_account(mint, acc.clone())?;
+            fork.track_accounts([&mint]);
+            info!(mint = %mint, "loaded mainnet mint into fork");
+            account = Some(acc);
+        }
 
-        manager
-            .set_token_balance(&fork_id, token_account, mint, user, 1_000_000)
-            .expect("Failed to set token balance");
+        match account {
+            Some(acc) => {
+                if acc.owner != token_program {
+                    anyhow::bail!("Mint {} is not owned by the SPL token program", mint);
+                }
+                match Mint::unpack(&acc.data) {
+                    Ok(mint_state) => Ok(Some(mint_state.supply)),
+                    Err(_) => anyhow::bail!("Account {} is not an initialized SPL mint", mint),
+                }
+            }
+            None => {
+                let mint_state = Mint {
+                    decimals: decimals.unwrap_or(DEFAULT_MINT_DECIMALS),
+                    is_initialized: true,
+                    ..Default::default()
+                };
 
-        let account = manager.get_account(&fork_id, token_account).unwrap();
+                let mut acc = Account::new(
+                    svm.rent().minimum_balance(Mint::LEN),
+                    Mint::LEN,
+                    &token_program,
+                );
+                mint_state.pack_into_slice(&mut acc.data);
+
+                svm.set_account(mint, acc)?;
+                fork.track_accounts([&mint]);
+                info!(mint = %mint, "created missing mint");
+                Ok(None)
+            }
+        }
+    }
+
+    /// Gets the latest blockhash and current slot of a fork
+    pub fn get_blockhash(&self, fork_id: &Uuid) -> anyhow::Result<(Hash, u64)> {
+        if let Some(fork) = self.get_fork(fork_id) {
+            let svm = fork.svm.read_or_recover();
+            let blockhash = fork
+                .blockhash
+                .lock_or_recover()
+                .unwrap_or_else(|| svm.latest_blockhash());
+            Ok((blockhash, svm.get_sysvar::<Clock>().slot))
+        } else {
+            anyhow::bail!("Fork not found");
+        }
+    }
+
+    /// Sets the recent blockhash of a fork, recording it in `SlotHashes` at
+    /// the current slot. Blockhash checks are disabled in forks, so
+    /// transactions signed against any blockhash already execute: this lets
+    /// the fork report the blockhash externally-signed transactions use.
+    /// It is reported until the runtime's blockhash changes, when warping a
+    /// fork with deterministic blockhashes or advancing a durable nonce.
+    #[tracing::instrument(skip_all, fields(fork_id = %fork_id))]
+    pub fn set_blockhash(&self, fork_id: &Uuid, blockhash: Hash) -> anyhow::Result<()> {
+        if let Some(fork) = self.get_fork(fork_id) {
+            let mut svm = fork.svm.write_or_recover();
+            let slot = svm.get_sysvar::<Clock>().slot;
+            let mut slot_hashes = svm.get_sysvar::<SlotHashes>();
+            slot_hashes.add(slot, blockhash);
+            svm.set_sysvar(&slot_hashes);
+            *fork.blockhash.lock_or_recover() = Some(blockhash);
+            Ok(())
+        } else {
+            anyhow::bail!("Fork not found");
+        }
+    }
+
+    /// Most recent entries of the `SlotHashes` sysvar of a fork, newest
+    /// first, which [`ForkManager::set_blockhash`] adds to
+    pub fn slot_hashes(
+        &self,
+        fork_id: &Uuid,
+        limit: Option<usize>,
+    ) -> anyhow::Result<Vec<SlotHashEntry>> {
+        let Some(fork) = self.get_fork(fork_id) else {
+            anyhow::bail!("Fork not found");
+        };
+        let slot_hashes = fork.svm.read_or_recover().get_sysvar::<SlotHashes>();
+        Ok(slot_hashes
+            .iter()
+            .take(limit.unwrap_or(DEFAULT_SLOT_HASHES_LIMIT))
+            .map(|(slot, hash)| SlotHashEntry {
+                slot: *slot,
+                hash: hash.to_string(),
+            })
+            .collect())
+    }
+
+    /// Sets the `Rent` sysvar of a fork
+    #[tracing::instrument(skip_all, fields(fork_id = %fork_id))]
+    pub fn set_rent(
+        &self,
+        fork_id: &Uuid,
+        lamports_per_byte_year: u64,
+        exemption_threshold: f64,
+        burn_percent: u8,
+    ) -> anyhow::Result<()> {
+        if burn_percent > 100 {
+            anyhow::bail!("burn_percent must be at most 100");
+        }
+
+        if let Some(fork) = self.get_fork(fork_id) {
+            let mut svm = fork.svm.write_or_recover();
+            svm.set_sysvar(&Rent {
+                lamports_per_byte_year,
+                exemption_threshold,
+                burn_percent,
+            });
+            Ok(())
+        } else {
+            anyhow::bail!("Fork not found");
+        }
+    }
+
+    /// Moves the `Clock` sysvar of a fork to `epoch`. The epoch of a slot
+    /// is set by the fork's `EpochSchedule`, so the clock's slot moves to
+    /// the first slot of the epoch unless it already lies within it, and
+    /// `leader_schedule_epoch` is derived from that slot. Forks with a
+    /// deterministic blockhash advance it when the slot moves.
+    #[tracing::instrument(skip_all, fields(fork_id = %fork_id))]
+    pub fn set_epoch(&self, fork_id: &Uuid, epoch: u64) -> anyhow::Result<EpochInfo> {
+        let Some(fork) = self.get_fork(fork_id) else {
+            anyhow::bail!("Fork not found");
+        };
+
+        let mut svm = fork.svm.write_or_recover();
+        let schedule = svm.get_sysvar::<EpochSchedule>();
+        if epoch
+            .checked_add(1)
+            .and_then(|next| next.checked_mul(schedule.slots_per_epoch))
+            .is_none()
+        {
+            anyhow::bail!("Epoch {} is out of the slot range", epoch);
+        }
+        let first_slot = schedule.get_first_slot_in_epoch(epoch);
+        let last_slot = schedule.get_last_slot_in_epoch(epoch);
+
+        let mut clock = svm.get_sysvar::<Clock>();
+        let warped = !(first_slot..=last_slot).contains(&clock.slot);
+        if warped {
+            clock.slot = first_slot;
+        }
+        clock.epoch = epoch;
+        clock.leader_schedule_epoch = schedule.get_leader_schedule_epoch(clock.slot);
+        svm.set_sysvar(&clock);
+        if warped && fork.options.deterministic_blockhash {
+            // Derived from the previous blockhash only, so every fork
+            // warping through the same slots sees the same sequence
+            fork.expire_blockhash(&mut svm);
+            let mut slot_hashes = svm.get_sysvar::<SlotHashes>();
+            slot_hashes.add(clock.slot, svm.latest_blockhash());
+            svm.set_sysvar(&slot_hashes);
+        }
+        fork.state_changed();
+
+        Ok(EpochInfo {
+            epoch,
+            slot: clock.slot,
+            first_slot,
+            last_slot,
+            leader_schedule_epoch: clock.leader_schedule_epoch,
+        })
+    }
+
+    /// Returns the rent-exempt minimum balance of an account holding
+    /// `data_len` bytes, according to the fork's current `Rent` sysvar
+    pub fn minimum_balance(&self, fork_id: &Uuid, data_len: usize) -> anyhow::Result<u64> {
+        if let Some(fork) = self.get_fork(fork_id) {
+            let svm = fork.svm.read_or_recover();
+            Ok(svm.get_sysvar::<Rent>().minimum_balance(data_len))
+        } else {
+            anyhow::bail!("Fork not found");
+        }
+    }
+
+    pub fn get_account(&self, fork_id: &Uuid, pubkey: Pubkey) -> anyhow::Result<Account> {
+        self.get_account_with_rpc(fork_id, pubkey, None)
+    }
+
+    /// Gets an account like [`ForkManager::get_account`], fetching it from
+    /// `rpc_url` instead of the fork's endpoint
+    pub fn get_account_with_rpc(
+        &self,
+        fork_id: &Uuid,
+        pubkey: Pubkey,
+        rpc_url: Option<&str>,
+    ) -> anyhow::Result<Account> {
+        match self.find_account_with_rpc(fork_id, pubkey, rpc_url)? {
+            Some(acc) => Ok(acc),
+            None if self
+                .get_fork(fork_id)
+                .is_some_and(|fork| fork.rpc.is_none()) =>
+            {
+                anyhow::bail!("Account not found in offline fork")
+            }
+            None => anyhow::bail!("Account not found on mainnet or fork"),
+        }
+    }
+
+    /// Gets an account present in a fork, never fetching it from mainnet
+    pub fn get_local_account(&self, fork_id: &Uuid, pubkey: Pubkey) -> anyhow::Result<Account> {
+        let Some(fork) = self.get_fork(fork_id) else {
+            anyhow::bail!("Fork not found");
+        };
+        let account = fork.svm.read_or_recover().get_account(&pubkey);
+        account.ok_or_else(|| anyhow::anyhow!("Account not found in fork"))
+    }
+
+    /// Gets an account of a fork, fetching it from mainnet if it isn't
+    /// present locally. Returns `None` if it exists in neither.
+    pub fn find_account(&self, fork_id: &Uuid, pubkey: Pubkey) -> anyhow::Result<Option<Account>> {
+        self.find_account_with_rpc(fork_id, pubkey, None)
+    }
+
+    /// Finds an account like [`ForkManager::find_account`], fetching it from
+    /// `rpc_url` instead of the fork's endpoint
+    #[tracing::instrument(skip_all, fields(fork_id = %fork_id))]
+    pub fn find_account_with_rpc(
+        &self,
+        fork_id: &Uuid,
+        pubkey: Pubkey,
+        rpc_url: Option<&str>,
+    ) -> anyhow::Result<Option<Account>> {
+        let Some(fork) = self.get_fork(fork_id) else {
+            anyhow::bail!("Fork not found");
+        };
+
+        // Local reads only need the shared lock
+        if let Some(acc) = fork.svm.read_or_recover().get_account(&pubkey) {
+            info!(account = %pubkey, "account found locally");
+            return Ok(Some(acc));
+        }
+
+        let request_rpc = self.request_rpc(&fork, rpc_url)?;
+        let Some(rpc) = request_rpc.as_ref().or(fork.rpc.as_ref()) else {
+            return Ok(None);
+        };
+
+        let mut svm = fork.svm.write_or_recover();
+        // The account may have been written while the lock was released
+        if let Some(acc) = svm.get_account(&pubkey) {
+            return Ok(Some(acc));
+        }
+        let account = rpc.get_account(&pubkey)?;
+        if let Some(acc) = &account {
+            check_data_len(&pubkey, acc.data.len())?;
+            svm.set_account(pubkey, acc.clone())?;
+            fork.track_accounts([&pubkey]);
+            info!(account = %pubkey, "account found on mainnet");
+        }
+        Ok(account)
+    }
+
+    /// Tells whether an account exists in a fork. With `local_only` only the
+    /// fork's state is checked, otherwise a local miss is looked up (and
+    /// cached) from mainnet like [`ForkManager::find_account`].
+    pub fn account_exists(
+        &self,
+        fork_id: &Uuid,
+        pubkey: Pubkey,
+        local_only: bool,
+    ) -> anyhow::Result<bool> {
+        if !local_only {
+            return Ok(self.find_account(fork_id, pubkey)?.is_some());
+        }
+        let Some(fork) = self.get_fork(fork_id) else {
+            anyhow::bail!("Fork not found");
+        };
+        let exists = fork.svm.read_or_recover().get_account(&pubkey).is_some();
+        Ok(exists)
+    }
+
+    /// Watches an account of a fork, yielding its state (`None` if it
+    /// doesn't exist) first and then every time it changes.
+    ///
+    /// Only the fork's local state is read, missing accounts aren't fetched
+    /// from mainnet. The stream ends when the fork expires or is deleted.
+    pub fn watch_account(
+        &self,
+        fork_id: &Uuid,
+        pubkey: Pubkey,
+    ) -> anyhow::Result<impl Stream<Item = Option<Account>> + use<>> {
+        let Some(fork) = self.get_fork(fork_id) else {
+            anyhow::bail!("Fork not found");
+        };
+        let changes = fork.state_version.subscribe();
+        let deadline = tokio::time::Instant::from_std(fork.expires_at);
+        let fork = Arc::downgrade(&fork);
+
+        Ok(stream::unfold(
+            (fork, changes, None),
+            move |(fork, mut changes, last_hash)| async move {
+                loop {
+                    let account = {
+                        let strong = fork.upgrade()?;
+                        strong.svm.read_or_recover().get_account(&pubkey)
+                    };
+                    let hash = account_hash(account.as_ref());
+                    if last_hash != Some(hash) {
+                        return Some((account, (fork, changes, Some(hash))));
+                    }
+
+                    tokio::select! {
+                        changed = changes.changed() => changed.ok()?,
+                        _ = tokio::time::sleep_until(deadline) => return None,
+                    }
+                }
+            },
+        ))
+    }
+
+    /// Streams the transaction events of a fork. Buffered events published
+    /// after `last_event_id` are replayed first, so reconnecting subscribers
+    /// don't miss events. The stream ends when the fork is dropped.
+    ///
+    /// With `account_changes` the events carry the accounts each
+    /// transaction changed, computed while the stream is alive. Events are
+    /// published without waiting for subscribers, so slow ones don't slow
+    /// transactions down.
+    pub fn transaction_events(
+        &self,
+        fork_id: &Uuid,
+        last_event_id: Option<u64>,
+        account_changes: bool,
+    ) -> anyhow::Result<impl Stream<Item = TransactionEvent> + use<>> {
+        let Some(fork) = self.get_fork(fork_id) else {
+            anyhow::bail!("Fork not found");
+        };
+        let subscription = account_changes
+            .then(|| AccountChangeSubscription::new(&fork.account_change_subscribers));
+        let (missed, events) = fork.subscribe_events(last_event_id);
+
+        let live = stream::unfold(
+            (events, subscription),
+            |(mut events, subscription)| async move {
+                loop {
+                    match events.recv().await {
+                        Ok(event) => return Some((event, (events, subscription))),
+                        // Slow subscribers skip the events they missed
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => return None,
+                    }
+                }
+            },
+        );
+        Ok(stream::iter(missed).chain(live).map(move |mut event| {
+            if !account_changes {
+                event.account_changes = None;
+            }
+            event
+        }))
+    }
+
+    /// Gets all accounts owned by `program_id` which match every filter.
+    ///
+    /// Only accounts materialized in the fork (set locally, preloaded from
+    /// mainnet or touched by an executed transaction) are scanned, this does
+    /// not query all of mainnet.
+    #[tracing::instrument(skip_all, fields(fork_id = %fork_id))]
+    pub fn get_program_accounts(
+        &self,
+        fork_id: &Uuid,
+        program_id: Pubkey,
+        filters: Option<Vec<ProgramAccountsFilter>>,
+    ) -> anyhow::Result<Vec<(Pubkey, Account)>> {
+        if let Some(fork) = self.get_fork(fork_id) {
+            let svm = fork.svm.read_or_recover();
+            let filters = filters.unwrap_or_default();
+
+            let accounts = fork
+                .materialized_accounts(&svm)
+                .into_iter()
+                .filter(|(_, acc)| acc.owner == program_id)
+                .filter(|(_, acc)| filters.iter().all(|f| f.matches(&acc.data)))
+                .collect();
+            Ok(accounts)
+        } else {
+            anyhow::bail!("Fork not found");
+        }
+    }
+
+    /// Clones a program from mainnet into a fork, with its ProgramData
+    /// account and the accounts it owns matching `filters`. Fails without
+    /// writing anything when more than `limit` accounts match, as a partial
+    /// copy of a protocol's state is rarely consistent.
+    #[tracing::instrument(skip_all, fields(fork_id = %fork_id, program = %program_id))]
+    pub fn clone_program_accounts(
+        &self,
+        fork_id: &Uuid,
+        program_id: Pubkey,
+        filters: Option<Vec<ProgramAccountsFilter>>,
+        limit: Option<usize>,
+    ) -> anyhow::Result<ClonedProgram> {
+        let Some(fork) = self.get_fork(fork_id) else {
+            anyhow::bail!("Fork not found");
+        };
+        let Some(rpc) = &fork.rpc else {
+            anyhow::bail!("Offline forks can't clone mainnet accounts");
+        };
+
+        let Some(program) = rpc.get_account(&program_id)? else {
+            anyhow::bail!("Program {} not found on mainnet", program_id);
+        };
+        if !program.executable {
+            anyhow::bail!("Account {} is not a program", program_id);
+        }
+        // ProgramData goes first, the program is loaded from it
+        let mut cloned = Vec::new();
+        let program_data = programdata_address(&program);
+        if let Some(address) = program_data {
+            let Some(account) = rpc.get_account(&address)? else {
+                anyhow::bail!("ProgramData {} not found on mainnet", address);
+            };
+            cloned.push((address, account));
+        }
+        cloned.push((program_id, program));
+        info!("fetched program, fetching the accounts it owns");
+
+        let filters: Vec<RpcFilterType> = filters
+            .unwrap_or_default()
+            .iter()
+            .map(RpcFilterType::from)
+            .collect();
+        // Counted without their data first, so programs owning too many
+        // accounts are rejected before downloading them all
+        let limit = limit.unwrap_or(DEFAULT_CLONE_LIMIT);
+        let matching = rpc.count_program_accounts(&program_id, filters.clone())?;
+        if matching > limit {
+            anyhow::bail!(
+                "{} accounts owned by {} match, over the limit of {}",
+                matching,
+                program_id,
+                limit
+            );
+        }
+        let owned = rpc.get_program_accounts(&program_id, filters)?;
+        // Accounts may have been created since they were counted
+        if owned.len() > limit {
+            anyhow::bail!(
+                "{} accounts owned by {} match, over the limit of {}",
+                owned.len(),
+                program_id,
+                limit
+            );
+        }
+        let accounts = owned.len();
+        info!(
+            accounts,
+            "fetched program accounts, writing them to the fork"
+        );
+        cloned.extend(owned);
+        // Checked before writing, so an oversized account leaves no partial copy
+        for (pubkey, account) in &cloned {
+            check_data_len(pubkey, account.data.len())?;
+        }
+
+        let mut svm = fork.svm.write_or_recover();
+        for (written, (pubkey, account)) in cloned.iter().enumerate() {
+            svm.set_account(*pubkey, account.clone())?;
+            if (written + 1) % 1_000 == 0 {
+                info!(
+                    written = written + 1,
+                    total = cloned.len(),
+                    "cloning program accounts"
+                );
+            }
+        }
+        fork.track_accounts(cloned.iter().map(|(pubkey, _)| pubkey));
+        fork.state_changed();
+        info!(accounts, "cloned program");
+
+        Ok(ClonedProgram {
+            program_id: program_id.to_string(),
+            program_data: program_data.map(|address| address.to_string()),
+            accounts,
+        })
+    }
+
+    /// Exports all accounts materialized in a fork
+    pub fn export_accounts(&self, fork_id: &Uuid) -> anyhow::Result<Vec<ExportedAccount>> {
+        if let Some(fork) = self.get_fork(fork_id) {
+            let svm = fork.svm.read_or_recover();
+            Ok(fork
+                .materialized_accounts(&svm)
+                .iter()
+                .map(|(key, acc)| ExportedAccount::new(key, acc))
+                .collect())
+        } else {
+            anyhow::bail!("Fork not found");
+        }
+    }
+
+    /// Compares the accounts materialized in either of two forks, returning
+    /// a page of the ones which differ sorted by address
+    pub fn diff_forks(
+        &self,
+        left_id: &Uuid,
+        right_id: &Uuid,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> anyhow::Result<ForkDiff> {
+        let (Some(left), Some(right)) = (self.get_fork(left_id), self.get_fork(right_id)) else {
+            anyhow::bail!("Fork not found");
+        };
+        if left_id == right_id {
+            return Ok(ForkDiff {
+                total: 0,
+                accounts: Vec::new(),
+            });
+        }
+
+        let mut keys: Vec<Pubkey> = left.accounts.lock_or_recover().iter().copied().collect();
+        keys.extend(right.accounts.lock_or_recover().iter());
+        keys.sort();
+        keys.dedup();
+
+        // One fork is locked at a time: holding both could deadlock with a
+        // diff in the opposite order once writers queue on them
+        let left_accounts: Vec<Option<Account>> = {
+            let svm = left.svm.read_or_recover();
+            keys.iter().map(|key| svm.get_account(key)).collect()
+        };
+        let right_svm = right.svm.read_or_recover();
+        let diffs: Vec<AccountDiff> = keys
+            .into_iter()
+            .zip(left_accounts)
+            .filter_map(|(key, left)| AccountDiff::new(key, left, right_svm.get_account(&key)))
+            .collect();
+        Ok(ForkDiff {
+            total: diffs.len(),
+            accounts: diffs
+                .into_iter()
+                .skip(offset)
+                .take(limit.unwrap_or(DEFAULT_HISTORY_LIMIT))
+                .collect(),
+        })
+    }
+
+    /// Counts the accounts materialized in a fork, their lamports and the
+    /// transactions recorded on it
+    pub fn get_stats(&self, fork_id: &Uuid) -> anyhow::Result<ForkStats> {
+        let Some(fork) = self.get_fork(fork_id) else {
+            anyhow::bail!("Fork not found");
+        };
+
+        let (accounts, total_lamports) = {
+            let svm = fork.svm.read_or_recover();
+            fork.accounts
+                .lock_or_recover()
+                .iter()
+                .filter_map(|key| svm.get_account(key))
+                .fold((0, 0), |(count, lamports), acc| {
+                    (count + 1, lamports + u128::from(acc.lamports))
+                })
+        };
+
+        let (executed_transactions, executed_transactions_total) = {
+            let history = fork.executed_transactions.lock_or_recover();
+            (history.records.len(), history.recorded)
+        };
+        let (simulated_transactions, simulated_transactions_total) = {
+            let history = fork.simulated_transactions.lock_or_recover();
+            (history.records.len(), history.recorded)
+        };
+
+        Ok(ForkStats {
+            accounts,
+            total_lamports,
+            executed_transactions,
+            simulated_transactions,
+            executed_transactions_total,
+            simulated_transactions_total,
+            rpc_requests: fork.rpc.as_ref().map_or(0, MainnetRpc::requests),
+        })
+    }
+
+    /// Lists the runtime features active in a fork, sorted by id
+    pub fn get_features(&self, fork_id: &Uuid) -> anyhow::Result<Vec<ActiveFeature>> {
+        let Some(fork) = self.get_fork(fork_id) else {
+            anyhow::bail!("Fork not found");
+        };
+
+        let mut ids: Vec<&Pubkey> = fork.feature_set.active().keys().collect();
+        ids.sort();
+        Ok(ids
+            .into_iter()
+            .map(|id| ActiveFeature {
+                id: id.to_string(),
+                name: FEATURE_NAMES.get(id).map(|name| name.to_string()),
+            })
+            .collect())
+    }
+
+    /// Streams the accounts materialized in a fork sorted by address. Each
+    /// account is read when the stream reaches it, so the whole export is
+    /// never held in memory.
+    pub fn export_accounts_stream(
+        &self,
+        fork_id: &Uuid,
+    ) -> anyhow::Result<impl Stream<Item = ExportedAccount> + use<>> {
+        let Some(fork) = self.get_fork(fork_id) else {
+            anyhow::bail!("Fork not found");
+        };
+        let mut keys: Vec<Pubkey> = fork.accounts.lock_or_recover().iter().copied().collect();
+        keys.sort();
+
+        Ok(stream::iter(keys).filter_map(move |key| {
+            let account = fork.svm.read_or_recover().get_account(&key);
+            async move { account.map(|acc| ExportedAccount::new(&key, &acc)) }
+        }))
+    }
+
+    /// Loads exported accounts into a fork, all or nothing, returning the
+    /// number of accounts imported. Imported accounts are authoritative:
+    /// being present in the fork, transactions never fetch them from
+    /// mainnet unless asked to refresh them.
+    pub fn import_accounts(
+        &self,
+        fork_id: &Uuid,
+        accounts: Vec<ExportedAccount>,
+    ) -> anyhow::Result<usize> {
+        let result = self.setup(
+            fork_id,
+            ForkSetup {
+                accounts,
+                ..Default::default()
+            },
+        )?;
+        Ok(result.accounts.len())
+    }
+
+    /// Rebuilds a fork from its transaction log: creates a fork with the
+    /// same options and executes the given transactions on it in order,
+    /// by default the ones executed on the original fork.
+    ///
+    /// Only transactions are replayed, so state set through the setters
+    /// must come from the fork's options (`fund`, `preload`...) for the
+    /// replay to be faithful.
+    ///
+    /// Like [`ForkManager::create_fork_concurrently`], the manager is only
+    /// locked exclusively to insert the new fork, the replay itself running
+    /// under a shared lock.
+    pub fn replay_history(
+        manager: &RwLock<ForkManager>,
+        fork_id: &Uuid,
+        transactions: Option<Vec<VersionedTransaction>>,
+    ) -> anyhow::Result<ReplayResult> {
+        let Some(fork) = manager.read_or_recover().get_fork(fork_id) else {
+            anyhow::bail!("Fork not found");
+        };
+        let transactions = match transactions {
+            Some(transactions) => transactions,
+            None => {
+                let history = fork.executed_transactions.lock_or_recover();
+                let dropped = history.recorded - history.records.len() as u64;
+                if dropped > 0 {
+                    anyhow::bail!(
+                        "History is incomplete, {} transactions were dropped",
+                        dropped
+                    );
+                }
+                history
+                    .records
+                    .iter()
+                    .map(|record| {
+                        record.tx.clone().ok_or_else(|| {
+                            anyhow::anyhow!("Transaction {} wasn't retained", record.txn.signature)
+                        })
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?
+            }
+        };
+
+        let replay_id = ForkManager::create_fork_concurrently(manager, fork.options.clone(), None)?;
+        let manager = manager.read_or_recover();
+        let Some(replay) = manager.get_fork(&replay_id) else {
+            anyhow::bail!("Fork not found");
+        };
+        let mut svm = replay.svm.write_or_recover();
+        let mut failed = Vec::new();
+        for tx in &transactions {
+            manager.preload_missing_accounts(&replay, None, &mut svm, tx, false);
+            if let Err(e) = execute_locked(&replay, &mut svm, tx.clone()) {
+                failed.push(e.meta.signature.to_string());
+            }
+        }
+        Ok(ReplayResult {
+            fork_id: replay_id,
+            replayed: transactions.len(),
+            failed,
+        })
+    }
+
+    /// Writes the accounts of every active fork to `path` as a JSON object
+    /// keyed by fork id, returning the number of forks written
+    pub fn snapshot_to_file(&self, path: &std::path::Path) -> anyhow::Result<usize> {
+        let snapshot = self
+            .forks
+            .keys()
+            .map(|id| Ok((id.to_string(), self.export_accounts(id)?)))
+            .collect::<anyhow::Result<HashMap<_, _>>>()?;
+
+        let file = std::fs::File::create(path)?;
+        serde_json::to_writer(std::io::BufWriter::new(file), &snapshot)?;
+        Ok(snapshot.len())
+    }
+
+    /// Looks a transaction up by signature among the executed transactions
+    /// of a fork and, with `include_simulated`, the simulated ones. The
+    /// latest record wins when a signature was recorded several times, and
+    /// includes the transaction in `tx_base64`.
+    pub fn get_transaction(
+        &self,
+        fork_id: &Uuid,
+        signature: &Signature,
+        include_simulated: bool,
+    ) -> anyhow::Result<Option<TransactionRecord>> {
+        let Some(fork) = self.get_fork(fork_id) else {
+            anyhow::bail!("Fork not found");
+        };
+
+        let find = |history: &Mutex<TransactionHistory>| {
+            history
+                .lock_or_recover()
+                .records
+                .iter()
+                .rev()
+                .find(|record| record.txn.signature == *signature)
+                .cloned()
+        };
+        let mut record = find(&fork.executed_transactions);
+        if record.is_none() && include_simulated {
+            record = find(&fork.simulated_transactions);
+        }
+        if let Some(record) = &mut record {
+            record.encode_tx();
+        }
+        Ok(record)
+    }
+
+    /// Helper function applying the log overrides of a history query and
+    /// encoding the transactions it asks for
+    fn format_page(
+        &self,
+        mut page: TransactionPage,
+        query: &TransactionHistoryQuery,
+    ) -> TransactionPage {
+        let limits = self
+            .log_limits
+            .with_overrides(query.log_max_lines, query.log_max_bytes);
+        for record in &mut page.transactions {
+            limits.truncate(&mut record.txn.logs);
+            if query.include_tx {
+                record.encode_tx();
+            }
+        }
+        page
+    }
+
+    /// Gets a page of the executed transactions on a fork
+    pub fn get_executed_transactions(
+        &self,
+        fork_id: &Uuid,
+        query: &TransactionHistoryQuery,
+    ) -> anyhow::Result<TransactionPage> {
+        let Some(fork) = self.get_fork(fork_id) else {
+            anyhow::bail!("Fork not found");
+        };
+        let txns = fork.executed_transactions.lock_or_recover();
+        Ok(self.format_page(query.page(&txns.records), query))
+    }
+
+    /// Gets a page of the simulated transactions on a fork
+    pub fn get_simulated_transactions(
+        &self,
+        fork_id: &Uuid,
+        query: &TransactionHistoryQuery,
+    ) -> anyhow::Result<TransactionPage> {
+        let Some(fork) = self.get_fork(fork_id) else {
+            anyhow::bail!("Fork not found");
+        };
+        let txns = fork.simulated_transactions.lock_or_recover();
+        Ok(self.format_page(query.page(&txns.records), query))
+    }
+}
+
+/// Helper function which fetches accounts from mainnet in batches and
+/// stores them in the svm, failing if any of them doesn't exist
+fn preload_accounts(
+    svm: &mut LiteSVM,
+    rpc: &MainnetRpc,
+    keys: &[Pubkey],
+) -> anyhow::Result<Vec<Pubkey>> {
+    let mut missing = Vec::new();
+    for chunk in keys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        let accounts = rpc.get_multiple_accounts(chunk)?;
+        for (key, account) in chunk.iter().zip(accounts) {
+            match account {
+                Some(acc) => {
+                    // Sanity check, mainnet accounts can't be larger
+                    check_data_len(key, acc.data.len())?;
+                    svm.set_account(*key, acc)?;
+                    info!(account = %key, "preloaded mainnet account into fork");
+                }
+                None => missing.push(key.to_string()),
+            }
+        }
+    }
+
+    if !missing.is_empty() {
+        anyhow::bail!("Accounts not found on mainnet: {}", missing.join(", "));
+    }
+    Ok(keys.to_vec())
+}
+
+/// Helper function returning the ProgramData address of an upgradeable
+/// program, stored after the `Program` variant tag of its state
+fn programdata_address(program: &Account) -> Option<Pubkey> {
+    if program.owner != UPGRADEABLE_LOADER || program.data.get(..4)? != 2u32.to_le_bytes() {
+        return None;
+    }
+    Pubkey::try_from(program.data.get(4..36)?).ok()
+}
+
+/// Helper function deriving the associated token account of an owner for
+/// a mint of `token_program`, and its bump seed, like
+/// `get_associated_token_address_with_program_id`
+fn derive_associated_token_address(
+    owner: &Pubkey,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[owner.as_ref(), token_program.as_ref(), mint.as_ref()],
+        &ASSOCIATED_TOKEN_PROGRAM,
+    )
+}
+
+/// Helper function creating a runtime whose builtins, precompiles and
+/// program runtime are loaded according to `feature_set`, which
+/// `LiteSVM::new` loads with every feature enabled
+fn new_svm(feature_set: &FeatureSet) -> LiteSVM {
+    LiteSVM::default()
+        .with_feature_set(feature_set.clone())
+        .with_builtins()
+        .with_lamports(1_000_000u64.wrapping_mul(LAMPORTS_PER_SOL))
+        .with_sysvars()
+        .with_precompiles()
+        .with_default_programs()
+}
+
+/// Helper function rejecting account data larger than the runtime allows
+fn check_data_len(pubkey: &Pubkey, len: usize) -> anyhow::Result<()> {
+    if len > MAX_ACCOUNT_DATA_LEN {
+        anyhow::bail!(
+            "Data of {} is {} bytes long, exceeding the maximum account size of {} bytes",
+            pubkey,
+            len,
+            MAX_ACCOUNT_DATA_LEN
+        );
+    }
+    Ok(())
+}
+
+/// Helper function to parse a base58 address with a descriptive error
+fn parse_pubkey(value: &str) -> anyhow::Result<Pubkey> {
+    value
+        .parse::<Pubkey>()
+        .map_err(|_| anyhow::anyhow!("Invalid pubkey {}", value))
+}
+
+/// Helper function executing a transaction on a fork whose svm is locked,
+/// recording it
+#[allow(clippy::result_large_err)] // Same as `LiteSVM::send_transaction`
+fn execute_locked(
+    fork: &Fork,
+    svm: &mut LiteSVM,
+    tx: VersionedTransaction,
+) -> Result<TransactionMetadata, FailedTransactionMetadata> {
+    fork.track_accounts(tx.message.static_account_keys());
+    if let Err(err) = check_durable_nonce(fork, svm, &tx) {
+        return Err(record_rejected(
+            fork,
+            svm,
+            TransactionKind::Executed,
+            tx,
+            err,
+        ));
+    }
+    let before = fork.writable_accounts(svm, &tx);
+    let waived = waive_fee(fork, svm, &tx);
+    let result = svm.send_transaction(tx.clone());
+    fork.state_changed();
+    // Transactions rejected before fees are charged must not keep the
+    // credited fee
+    if let Err(e) = &result
+        && let Some((payer, account)) = waived
+        && rejected_before_fees(&e.err)
+    {
+        restore_payer(svm, payer, account);
+    }
+    // Transactions failing after fees are charged still change the payer
+    let changes = before.map(|before| account_changes(before, |key| svm.get_account(key)));
+    fork.record_transaction(TransactionKind::Executed, tx, result.clone(), changes);
+    result
+}
+
+/// Helper function simulating a transaction on a fork whose svm is locked,
+/// recording it. Its durable nonce is checked like when executing it.
+#[allow(clippy::result_large_err)] // Same as `LiteSVM::simulate_transaction`
+fn simulate_locked(
+    fork: &Fork,
+    svm: &mut LiteSVM,
+    tx: VersionedTransaction,
+) -> Result<SimulatedTransactionInfo, FailedTransactionMetadata> {
+    if let Err(err) = check_durable_nonce(fork, svm, &tx) {
+        return Err(record_rejected(
+            fork,
+            svm,
+            TransactionKind::Simulated,
+            tx,
+            err,
+        ));
+    }
+    let before = fork.writable_accounts(svm, &tx);
+    let waived = waive_fee(fork, svm, &tx);
+    let result = svm.simulate_transaction(tx.clone());
+    if let Some((payer, account)) = waived {
+        restore_payer(svm, payer, account);
+    }
+    match &result {
+        Ok(res) => {
+            // Simulations leave the fork untouched, their changes are the
+            // post-simulation states
+            let changes = before.map(|before| {
+                account_changes(before, |key| {
+                    res.post_accounts
+                        .iter()
+                        .find(|(pubkey, _)| pubkey == key)
+                        .map(|(_, account)| Account::from(account.clone()))
+                        .or_else(|| svm.get_account(key))
+                })
+            });
+            fork.record_transaction(
+                TransactionKind::Simulated,
+                tx,
+                Ok(res.meta.clone()),
+                changes,
+            )
+        }
+        Err(e) => {
+            let unchanged = before.map(|_| Vec::new());
+            fork.record_transaction(TransactionKind::Simulated, tx, Err(e.clone()), unchanged)
+        }
+    }
+    result
+}
+
+/// Helper function recording a transaction rejected before reaching the
+/// runtime, which leaves its accounts unchanged
+fn record_rejected(
+    fork: &Fork,
+    svm: &LiteSVM,
+    kind: TransactionKind,
+    tx: VersionedTransaction,
+    err: TransactionError,
+) -> FailedTransactionMetadata {
+    let meta = TransactionMetadata {
+        signature: tx.signatures.first().copied().unwrap_or_default(),
+        ..Default::default()
+    };
+    let failed = FailedTransactionMetadata { err, meta };
+    let unchanged = fork.writable_accounts(svm, &tx).map(|_| Vec::new());
+    fork.record_transaction(kind, tx, Err(failed.clone()), unchanged);
+    failed
+}
+
+/// Helper function diffing accounts snapshotted by
+/// [`Fork::writable_accounts`] against their state after a transaction
+fn account_changes(
+    before: Vec<(Pubkey, Option<Account>)>,
+    after: impl Fn(&Pubkey) -> Option<Account>,
+) -> Vec<AccountDiff> {
+    before
+        .into_iter()
+        .filter_map(|(pubkey, account)| {
+            let changed = after(&pubkey);
+            AccountDiff::new(pubkey, account, changed)
+        })
+        .collect()
+}
+
+/// Helper function returning the nonce account of a durable nonce
+/// transaction, whose first instruction advances it
+fn nonce_account(message: &VersionedMessage) -> Option<Pubkey> {
+    let keys = message.static_account_keys();
+    let ix = message.instructions().first()?;
+    let advances_nonce = keys.get(ix.program_id_index as usize)
+        == Some(&solana_system_interface::program::ID)
+        && ix.data.get(..4) == Some(&ADVANCE_NONCE_ACCOUNT[..]);
+    if !advances_nonce {
+        return None;
+    }
+    keys.get(*ix.accounts.first()? as usize).copied()
+}
+
+/// Helper function checking the nonce account of a durable nonce
+/// transaction, which LiteSVM only does along with blockhash checks.
+///
+/// LiteSVM's blockhash never changes on its own, so when the nonce was
+/// advanced from the current blockhash it is expired first, letting the
+/// transaction advance the nonce again.
+fn check_durable_nonce(
+    fork: &Fork,
+    svm: &mut LiteSVM,
+    tx: &VersionedTransaction,
+) -> Result<(), TransactionError> {
+    let Some(nonce) = nonce_account(&tx.message) else {
+        return Ok(());
+    };
+    let data = svm
+        .get_account(&nonce)
+        .filter(|acc| acc.owner == solana_system_interface::program::ID)
+        .and_then(|acc| bincode::deserialize::<NonceVersions>(&acc.data).ok())
+        .and_then(|versions| match versions.state() {
+            NonceState::Initialized(data) => Some(data.clone()),
+            NonceState::Uninitialized => None,
+        })
+        .ok_or(TransactionError::BlockhashNotFound)?;
+    if !fork.options.validation.blockhash_check
+        && data.blockhash() != *tx.message.recent_blockhash()
+    {
+        return Err(TransactionError::BlockhashNotFound);
+    }
+
+    if data.durable_nonce == DurableNonce::from_blockhash(&svm.latest_blockhash()) {
+        fork.expire_blockhash(svm);
+    }
+    Ok(())
+}
+
+/// Helper function verifying the signatures of every signer of a
+/// transaction but the impersonated ones, which must all be signers
+fn verify_unimpersonated_signatures(
+    tx: &VersionedTransaction,
+    impersonate: &[Pubkey],
+) -> anyhow::Result<bool> {
+    let signers =
+        &tx.message.static_account_keys()[..(tx.message.header().num_required_signatures as usize)];
+    if let Some(pubkey) = impersonate.iter().find(|pubkey| !signers.contains(pubkey)) {
+        anyhow::bail!(
+            "Impersonated account {} doesn't sign the transaction",
+            pubkey
+        );
+    }
+    if tx.signatures.len() != signers.len() {
+        return Ok(false);
+    }
+    Ok(tx
+        .verify_with_results()
+        .into_iter()
+        .zip(signers)
+        .all(|(verified, signer)| verified || impersonate.contains(signer)))
+}
+
+/// Helper function toggling the signature verification of a runtime, which
+/// LiteSVM only exposes as a builder method
+fn set_sigverify(svm: &mut LiteSVM, sigverify: bool) {
+    let owned = std::mem::take(svm);
+    *svm = owned.with_sigverify(sigverify);
+}
+
+/// Runtime whose signature verification is disabled until it is dropped,
+/// so a panicking simulation can't leave it disabled
+struct SigverifySkipped<'a>(&'a mut LiteSVM);
+
+impl<'a> SigverifySkipped<'a> {
+    fn new(svm: &'a mut LiteSVM) -> Self {
+        set_sigverify(svm, false);
+        SigverifySkipped(svm)
+    }
+}
+
+impl Drop for SigverifySkipped<'_> {
+    fn drop(&mut self) {
+        set_sigverify(self.0, true);
+    }
+}
+
+impl std::ops::Deref for SigverifySkipped<'_> {
+    type Target = LiteSVM;
+
+    fn deref(&self) -> &LiteSVM {
+        self.0
+    }
+}
+
+impl std::ops::DerefMut for SigverifySkipped<'_> {
+    fn deref_mut(&mut self) -> &mut LiteSVM {
+        self.0
+    }
+}
+
+/// Helper function crediting the fee payer with the fee of a transaction
+/// when the fork doesn't check fees, computed like the runtime charges it.
+/// Returns the payer's account before the credit, so it can be restored
+/// when the fee isn't charged.
+fn waive_fee(
+    fork: &Fork,
+    svm: &mut LiteSVM,
+    tx: &VersionedTransaction,
+) -> Option<(Pubkey, Account)> {
+    if fork.options.validation.fee_check {
+        return None;
+    }
+    let payer = *tx.message.static_account_keys().first()?;
+    let account = svm.get_account(&payer)?;
+    // LiteSVM only charges the base fee, prioritization fees aren't collected
+    let fee = fork.estimate_fee(tx).base_fee;
+    let mut credited = account.clone();
+    credited.lamports = credited.lamports.saturating_add(fee);
+    svm.set_account(payer, credited).ok()?;
+    Some((payer, account))
+}
+
+/// Helper function restoring the fee payer credited by [`waive_fee`]
+fn restore_payer(svm: &mut LiteSVM, payer: Pubkey, account: Account) {
+    if let Err(e) = svm.set_account(payer, account) {
+        warn!(error = ?e, %payer, "error in restoring the fee payer");
+    }
+}
+
+/// Storage of accounts, either a fork's runtime or writes staged on top of it
+trait AccountStore {
+    fn get_account(&self, pubkey: &Pubkey) -> Option<Account>;
+    fn set_account(&mut self, pubkey: Pubkey, account: Account) -> anyhow::Result<()>;
+    fn rent(&self) -> Rent;
+}
+
+impl AccountStore for LiteSVM {
+    fn get_account(&self, pubkey: &Pubkey) -> Option<Account> {
+        LiteSVM::get_account(self, pubkey)
+    }
+
+    fn set_account(&mut self, pubkey: Pubkey, account: Account) -> anyhow::Result<()> {
+        Ok(LiteSVM::set_account(self, pubkey, account)?)
+    }
+
+    fn rent(&self) -> Rent {
+        self.get_sysvar::<Rent>()
+    }
+}
+
+/// Account writes staged on top of a fork's runtime without modifying it,
+/// so a multi-entry write only reaches the fork once all its entries
+/// succeeded
+struct StagedAccounts<'a> {
+    svm: &'a LiteSVM,
+    writes: HashMap<Pubkey, Account>,
+}
+
+impl<'a> StagedAccounts<'a> {
+    fn new(svm: &'a LiteSVM) -> Self {
+        StagedAccounts {
+            svm,
+            writes: HashMap::new(),
+        }
+    }
+
+    /// Checks that the staged executable accounts and `programs` load,
+    /// which the runtime only does when they are written. They are loaded
+    /// into an empty runtime with the fork's features.
+    fn verify_loadable(
+        &self,
+        feature_set: &FeatureSet,
+        programs: &[(Pubkey, Vec<u8>)],
+    ) -> anyhow::Result<()> {
+        let executable: Vec<_> = self
+            .writes
+            .iter()
+            .filter(|(_, account)| account.executable)
+            .collect();
+        if executable.is_empty() && programs.is_empty() {
+            return Ok(());
+        }
+
+        let mut scratch = new_svm(feature_set);
+        for (pubkey, account) in executable {
+            // Upgradeable programs are loaded from their ProgramData
+            if let Some(address) = programdata_address(account)
+                && let Some(program_data) = self.get_account(&address)
+            {
+                scratch.set_account(address, program_data)?;
+            }
+            scratch
+                .set_account(*pubkey, account.clone())
+                .map_err(|e| anyhow::anyhow!("Program {} can't be loaded: {}", pubkey, e))?;
+        }
+        for (program_id, bytes) in programs {
+            scratch
+                .add_program(*program_id, bytes)
+                .map_err(|e| anyhow::anyhow!("Program {} can't be loaded: {}", program_id, e))?;
+        }
+        Ok(())
+    }
+
+    /// Staged writes, executable accounts last so the ProgramData they are
+    /// loaded from is written first
+    fn into_writes(self) -> Vec<(Pubkey, Account)> {
+        let mut writes: Vec<_> = self.writes.into_iter().collect();
+        writes.sort_by_key(|(_, account)| account.executable);
+        writes
+    }
+}
+
+impl AccountStore for StagedAccounts<'_> {
+    fn get_account(&self, pubkey: &Pubkey) -> Option<Account> {
+        self.writes
+            .get(pubkey)
+            .cloned()
+            .or_else(|| self.svm.get_account(pubkey))
+    }
+
+    fn set_account(&mut self, pubkey: Pubkey, account: Account) -> anyhow::Result<()> {
+        self.writes.insert(pubkey, account);
+        Ok(())
+    }
+
+    fn rent(&self) -> Rent {
+        self.svm.get_sysvar::<Rent>()
+    }
+}
+
+/// Helper function which changes the supply of a mint by the difference
+/// between the `previous` and `amount` balances of one of its token accounts
+fn update_mint_supply(
+    svm: &mut impl AccountStore,
+    mint: Pubkey,
+    previous: u64,
+    amount: u64,
+) -> anyhow::Result<()> {
+    if previous == amount {
+        return Ok(());
+    }
+    let mut account = svm
+        .get_account(&mint)
+        .ok_or_else(|| anyhow::anyhow!("Mint {} not found", mint))?;
+    let mut mint_state = Mint::unpack(&account.data)
+        .map_err(|_| anyhow::anyhow!("Account {} is not an initialized SPL mint", mint))?;
+    mint_state.supply = if amount > previous {
+        mint_state
+            .supply
+            .checked_add(amount - previous)
+            .ok_or_else(|| {
+                anyhow::anyhow!("Amount {} overflows the supply of mint {}", amount, mint)
+            })?
+    } else {
+        mint_state.supply.saturating_sub(previous - amount)
+    };
+    mint_state.pack_into_slice(&mut account.data);
+    svm.set_account(mint, account)?;
+    Ok(())
+}
+
+/// Helper function telling whether a transaction failed before its fee
+/// was charged
+fn rejected_before_fees(err: &TransactionError) -> bool {
+    matches!(
+        err,
+        TransactionError::SignatureFailure
+            | TransactionError::BlockhashNotFound
+            | TransactionError::AlreadyProcessed
+            | TransactionError::SanitizeFailure
+            | TransactionError::AccountNotFound
+            | TransactionError::InsufficientFundsForFee
+    )
+}
+
+/// Helper function which unpacks an SPL token account, failing with a
+/// descriptive error for accounts of other programs, uninitialized accounts
+/// and data of the wrong length
+fn unpack_token_account(pubkey: &Pubkey, account: &Account) -> anyhow::Result<TokenAccount> {
+    if account.owner != Pubkey::new_from_array(*ID.as_array()) {
+        anyhow::bail!("Account {} is not owned by the SPL token program", pubkey);
+    }
+    TokenAccount::unpack(&account.data)
+        .map_err(|_| anyhow::anyhow!("Account at {} is not an initialized token account", pubkey))
+}
+
+/// Helper function to hash the state of an account, used to detect changes
+fn account_hash(account: Option<&Account>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    if let Some(acc) = account {
+        acc.lamports.hash(&mut hasher);
+        acc.owner.as_ref().hash(&mut hasher);
+        acc.data.hash(&mut hasher);
+        acc.executable.hash(&mut hasher);
+        acc.rent_epoch.hash(&mut hasher);
+    }
+    account.is_some().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Sysvars which were successfully refreshed from mainnet by [`update_sysvars`]
+#[derive(Deserialize, Serialize, Clone, Copy, Default, Debug)]
+pub struct SysvarRefresh {
+    pub slot_hashes: bool,
+    pub clock: bool,
+    pub epoch_schedule: bool,
+}
+
+impl SysvarRefresh {
+    /// Whether every sysvar was refreshed
+    pub fn is_complete(&self) -> bool {
+        self.slot_hashes && self.clock && self.epoch_schedule
+    }
+}
+
+/// Helper function to update the variables of a fork.
+///
+/// Each sysvar is refreshed independently so a failed fetch only leaves
+/// the sysvars depending on it stale. With `deterministic_blockhash` the
+/// runtime's own blockhash is recorded in `SlotHashes` instead of the live
+/// one.
+pub fn update_sysvars(
+    svm: &mut LiteSVM,
+    client: &MainnetRpc,
+    deterministic_blockhash: bool,
+) -> SysvarRefresh {
+    let mut refreshed = SysvarRefresh::default();
+
+    let slot = match client.get_slot() {
+        Ok(slot) => Some(slot),
+        Err(e) => {
+            warn!(error = ?e, "error in fetching slot");
+            None
+        }
+    };
+
+    let latest_blockhash = if deterministic_blockhash {
+        Ok(svm.latest_blockhash())
+    } else {
+        client.get_latest_blockhash()
+    };
+    match (slot, latest_blockhash) {
+        (Some(slot), Ok(latest_blockhash)) => {
+            let mut slot_hashes = svm.get_sysvar::<SlotHashes>().clone();
+            if !slot_hashes.iter().any(|(_, h)| *h == latest_blockhash) {
+                slot_hashes.push((slot, latest_blockhash));
+                svm.set_sysvar(&SlotHashes::new(slot_hashes.as_ref()));
+            }
+            refreshed.slot_hashes = true;
+        }
+        (_, Err(e)) => warn!(error = ?e, "error in fetching latest blockhash"),
+        (None, Ok(_)) => {}
+    }
+
+    if let Some(slot) = slot {
+        let mut clock = svm.get_sysvar::<Clock>();
+        clock.slot = slot;
+        clock.unix_timestamp = Utc::now().timestamp();
+        svm.set_sysvar(&clock);
+        refreshed.clock = true;
+    }
+
+    match client.get_epoch_schedule() {
+        Ok(epochs) => {
+            svm.set_sysvar(&epochs);
+            refreshed.epoch_schedule = true;
+        }
+        Err(e) => warn!(error = ?e, "error in fetching epoch schedule"),
+    }
+
+    refreshed
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use solana_client::rpc_request::RpcRequest;
+    use solana_sdk::{
+        instruction::{AccountMeta, Instruction},
+        message::{AddressLookupTableAccount, Message, v0},
+        transaction::Transaction,
+    };
+    use solana_system_interface::instruction as system_instruction;
+    use std::time::Duration;
+
+    pub(crate) fn offline_fork(manager: &mut ForkManager) -> Uuid {
+        manager
+            .create_fork_with_options(ForkOptions {
+                offline: true,
+                ..Default::default()
+            })
+            .expect("Failed to create fork")
+    }
+
+    fn latest_blockhash(manager: &ForkManager, fork_id: &Uuid) -> Hash {
+        manager
+            .get_fork(fork_id)
+            .unwrap()
+            .svm
+            .read_or_recover()
+            .latest_blockhash()
+    }
+
+    fn transfer_tx(
+        manager: &ForkManager,
+        fork_id: &Uuid,
+        from: &Keypair,
+        to: &Pubkey,
+        lamports: u64,
+    ) -> VersionedTransaction {
+        let ix = system_instruction::transfer(&from.pubkey(), to, lamports);
+        let blockhash = latest_blockhash(manager, fork_id);
+        Transaction::new_signed_with_payer(&[ix], Some(&from.pubkey()), &[from], blockhash).into()
+    }
+
+    fn token_transfer_tx(
+        manager: &ForkManager,
+        fork_id: &Uuid,
+        source: &Pubkey,
+        destination: &Pubkey,
+        authority: &Keypair,
+        amount: u64,
+    ) -> VersionedTransaction {
+        let mut data = vec![3];
+        data.extend_from_slice(&amount.to_le_bytes());
+        let ix = Instruction::new_with_bytes(
+            Pubkey::new_from_array(*ID.as_array()),
+            &data,
+            vec![
+                AccountMeta::new(*source, false),
+                AccountMeta::new(*destination, false),
+                AccountMeta::new_readonly(authority.pubkey(), true),
+            ],
+        );
+        let blockhash = latest_blockhash(manager, fork_id);
+        Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&authority.pubkey()),
+            &[authority],
+            blockhash,
+        )
+        .into()
+    }
+
+    /// Creates a funded owner with a token account holding `amount` tokens
+    /// of `mint`, along with an empty token account for another owner
+    fn token_accounts(
+        manager: &ForkManager,
+        fork_id: &Uuid,
+        mint: &Pubkey,
+        amount: u64,
+    ) -> (Keypair, Pubkey, Pubkey) {
+        let owner = Keypair::new();
+        let source = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+        manager
+            .set_lamports(fork_id, owner.pubkey(), 1_000_000_000, false)
+            .unwrap();
+        manager
+            .set_token_balance(
+                fork_id,
+                source,
+                *mint,
+                owner.pubkey(),
+                amount,
+                TokenBalanceOptions::default(),
+            )
+            .unwrap();
+        manager
+            .set_token_balance(
+                fork_id,
+                destination,
+                *mint,
+                Pubkey::new_unique(),
+                0,
+                TokenBalanceOptions::default(),
+            )
+            .unwrap();
+        (owner, source, destination)
+    }
+
+    #[test]
+    fn test_fork_creation() {
+        let mut manager = ForkManager::new();
+        let fork_id = manager.create_fork().expect("Failed to create fork");
+
+        assert!(manager.forks.contains_key(&fork_id));
+    }
+
+    #[test]
+    fn test_get_fork() {
+        let mut manager = ForkManager::new();
+        let fork_id = manager.create_fork().expect("Failed to create fork");
+
+        let fork = manager.get_fork(&fork_id);
+        assert!(fork.is_some());
+    }
+
+    #[test]
+    fn test_delete_fork() {
+        let mut manager = ForkManager::new();
+        let fork_id = manager.create_fork().expect("Failed to create fork");
+
+        let deleted = manager.delete_fork(&fork_id);
+        assert_eq!(deleted, DeleteForkResult::Deleted);
+        assert!(!manager.forks.contains_key(&fork_id));
+    }
+
+    #[test]
+    fn test_delete_fork_not_found() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+
+        assert_eq!(
+            manager.delete_fork(&Uuid::new_v4()),
+            DeleteForkResult::NotFound
+        );
+        assert_eq!(manager.delete_fork(&fork_id), DeleteForkResult::Deleted);
+        // Deleting a deleted fork again isn't reported as expired
+        assert_eq!(manager.delete_fork(&fork_id), DeleteForkResult::NotFound);
+    }
+
+    #[test]
+    fn test_delete_forks() {
+        let mut manager = ForkManager::new();
+        let first = offline_fork(&mut manager);
+        let second = offline_fork(&mut manager);
+        let missing = Uuid::new_v4();
+
+        let result = manager.delete_forks(&[first, missing, second]);
+        assert_eq!(result.deleted, 2);
+        assert_eq!(result.not_found, 1);
+        assert_eq!(result.results[1].id, missing);
+        assert_eq!(result.results[1].result, DeleteForkResult::NotFound);
+        assert_eq!(result.results[2].result, DeleteForkResult::Deleted);
+        assert!(manager.forks.is_empty());
+    }
+
+    #[test]
+    fn test_delete_fork_expired() {
+        let mut manager = ForkManager::new();
+        let swept = offline_fork(&mut manager);
+        let unswept = offline_fork(&mut manager);
+
+        for fork in manager.forks.values_mut() {
+            Arc::get_mut(fork).unwrap().expires_at = Instant::now() - Duration::from_secs(1);
+        }
+
+        // Keep the second fork away from the cleanup task so only the first
+        // one is swept
+        let unswept_fork = manager.forks.remove(&unswept).unwrap();
+        manager.cleanup_expired();
+        assert_eq!(manager.delete_fork(&swept), DeleteForkResult::Expired);
+
+        // Forks which expired but weren't swept yet
+        manager.forks.insert(unswept, unswept_fork);
+        assert_eq!(manager.delete_fork(&unswept), DeleteForkResult::Expired);
+        assert!(manager.forks.is_empty());
+    }
+
+    #[test]
+    fn test_fork_limit() {
+        let mut manager = ForkManager::new().with_max_forks(2);
+        let first = manager.create_fork().expect("Failed to create fork");
+        manager.create_fork().expect("Failed to create fork");
+
+        let err = manager.create_fork().unwrap_err();
+        assert!(err.to_string().contains("Fork limit reached (2/2)"));
+
+        assert_eq!(manager.delete_fork(&first), DeleteForkResult::Deleted);
+        assert!(manager.create_fork().is_ok());
+    }
+
+    #[test]
+    fn test_create_fork_concurrently() {
+        let manager = Arc::new(RwLock::new(ForkManager::new().with_max_forks(4)));
+        let options = ForkOptions {
+            offline: true,
+            ..Default::default()
+        };
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let manager = Arc::clone(&manager);
+                let options = options.clone();
+                std::thread::spawn(move || {
+                    ForkManager::create_fork_concurrently(&manager, options, None)
+                })
+            })
+            .collect();
+        let created = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .filter(Result::is_ok)
+            .count();
+
+        // The limit still holds when forks are built in parallel
+        assert_eq!(created, 4);
+        assert_eq!(manager.read_or_recover().forks.len(), 4);
+    }
+
+    #[test]
+    fn test_concurrent_reads() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let funded = Pubkey::new_unique();
+        manager
+            .set_lamports(&fork_id, funded, 1_000_000, false)
+            .unwrap();
+
+        // Reads don't wait for other readers to release the fork
+        let fork = manager.get_fork(&fork_id).unwrap();
+        let _reader = fork.svm.read_or_recover();
+        std::thread::scope(|scope| {
+            let account = scope.spawn(|| manager.get_account(&fork_id, funded).unwrap());
+            let stats = scope.spawn(|| manager.get_stats(&fork_id).unwrap());
+            assert_eq!(account.join().unwrap().lamports, 1_000_000);
+            assert_eq!(stats.join().unwrap().total_lamports, 1_000_000);
+        });
+    }
+
+    /// Reads of a large account from several threads at once, measured
+    /// against the same reads serialized as behind an exclusive lock. Run it
+    /// with `cargo test --release test_concurrent_reads_throughput --
+    /// --ignored --nocapture`
+    #[test]
+    #[ignore = "benchmark"]
+    fn test_concurrent_reads_throughput() {
+        const THREADS: usize = 8;
+        const READS: usize = 2_000;
+
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let pubkey = Pubkey::new_unique();
+        let data = vec![7; 64 * 1024];
+        let account = Account {
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            owner: Pubkey::new_unique(),
+            executable: false,
+            rent_epoch: 0,
+        };
+        let fork = manager.get_fork(&fork_id).unwrap();
+        fork.svm
+            .write_or_recover()
+            .set_account(pubkey, account)
+            .unwrap();
+
+        let exclusive = Mutex::new(());
+        let run = |serialize: bool| {
+            let start = Instant::now();
+            std::thread::scope(|scope| {
+                for _ in 0..THREADS {
+                    scope.spawn(|| {
+                        for _ in 0..READS {
+                            let _guard = serialize.then(|| exclusive.lock_or_recover());
+                            manager.get_local_account(&fork_id, pubkey).unwrap();
+                        }
+                    });
+                }
+            });
+            start.elapsed()
+        };
+        let serialized = run(true);
+        let concurrent = run(false);
+        println!(
+            "{} reads: {serialized:?} serialized, {concurrent:?} concurrent",
+            THREADS * READS
+        );
+        assert!(concurrent < serialized);
+    }
+
+    #[test]
+    fn test_idempotency_key() {
+        let manager = RwLock::new(ForkManager::new());
+        let options = ForkOptions {
+            offline: true,
+            ..Default::default()
+        };
+
+        let first =
+            ForkManager::create_fork_concurrently(&manager, options.clone(), Some("key")).unwrap();
+        let retried =
+            ForkManager::create_fork_concurrently(&manager, options.clone(), Some("key")).unwrap();
+        assert_eq!(first, retried);
+        assert_eq!(manager.read_or_recover().forks.len(), 1);
+
+        let other = ForkManager::create_fork_concurrently(&manager, options.clone(), Some("other"))
+            .unwrap();
+        assert_ne!(first, other);
+
+        // A deleted fork isn't returned for its key anymore
+        manager.write_or_recover().delete_fork(&first);
+        let recreated =
+            ForkManager::create_fork_concurrently(&manager, options, Some("key")).unwrap();
+        assert_ne!(first, recreated);
+    }
+
+    #[test]
+    fn test_cleanup_expired() {
+        let mut manager = ForkManager::new();
+        let fork_id = manager.create_fork().expect("Failed to create fork");
+
+        if let Some(fork) = manager.forks.get_mut(&fork_id) {
+            let fork_mut = Arc::get_mut(fork).unwrap();
+            fork_mut.expires_at = Instant::now() - Duration::from_secs(1);
+        }
+
+        assert_eq!(manager.forks.len(), 1);
+        assert!(manager.last_cleanup().is_none());
+        assert_eq!(manager.cleanup_expired(), 1);
+        assert_eq!(manager.forks.len(), 0);
+        assert_eq!(manager.last_cleanup().unwrap().swept, 1);
+    }
+
+    #[test]
+    fn test_set_lamports() {
+        let mut manager = ForkManager::new();
+        let fork_id = manager.create_fork().expect("Failed to create fork");
+
+        let keypair = Keypair::new();
+        let pubkey = keypair.pubkey();
+        let lamports = 1_000_000;
+
+        let result = manager.set_lamports(&fork_id, pubkey, lamports, false);
+        assert!(result.is_ok());
+
+        let account = manager
+            .get_account(&fork_id, pubkey)
+            .expect("Failed to get account");
+        assert_eq!(account.lamports, lamports);
+    }
+
+    #[test]
+    fn test_set_lamports_validation() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+
+        // Wrapped negative values are rejected
+        let pubkey = Pubkey::new_unique();
+        assert!(
+            manager
+                .set_lamports(&fork_id, pubkey, -1i64 as u64, false)
+                .is_err()
+        );
+        assert!(manager.set_lamports(&fork_id, pubkey, 1, false).is_ok());
+
+        // Data-bearing accounts keep their rent-exempt minimum unless forced
+        let token_account = Pubkey::new_unique();
+        manager
+            .set_token_balance(
+                &fork_id,
+                token_account,
+                Pubkey::new_unique(),
+                pubkey,
+                1,
+                TokenBalanceOptions::default(),
+            )
+            .unwrap();
+        let minimum = manager
+            .minimum_balance(&fork_id, TokenAccount::LEN)
+            .unwrap();
+        assert!(
+            manager
+                .set_lamports(&fork_id, token_account, minimum - 1, false)
+                .is_err()
+        );
+        assert!(
+            manager
+                .set_lamports(&fork_id, token_account, minimum, false)
+                .is_ok()
+        );
+        assert!(
+            manager
+                .set_lamports(&fork_id, token_account, 0, true)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_set_token_balance_check_supply() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let mint = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+
+        // The created mint's supply is the first amount
+        manager
+            .set_token_balance(
+                &fork_id,
+                Pubkey::new_unique(),
+                mint,
+                owner,
+                100,
+                TokenBalanceOptions {
+                    check_supply: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let result = manager.set_token_balance(
+            &fork_id,
+            Pubkey::new_unique(),
+            mint,
+            owner,
+            101,
+            TokenBalanceOptions {
+                check_supply: true,
+                ..Default::default()
+            },
+        );
+        assert!(result.is_err());
+        let result = manager.set_token_balance(
+            &fork_id,
+            Pubkey::new_unique(),
+            mint,
+            owner,
+            101,
+            TokenBalanceOptions::default(),
+        );
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_set_token_balance() {
+        let mut manager = ForkManager::new();
+        let fork_id = manager.create_fork().expect("Failed to create fork");
+
+        let mint = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+        let token_account = Pubkey::new_unique();
+
+        manager
+            .set_token_balance(
+                &fork_id,
+                token_account,
+                mint,
+                user,
+                1_000_000,
+                TokenBalanceOptions::default(),
+            )
+            .expect("Failed to set token balance");
+
+        let account = manager.get_account(&fork_id, token_account).unwrap();
         let unpacked = TokenAccount::unpack(&account.data).unwrap();
 
         assert_eq!(
-            unpacked.owner,
-            pubkey::Pubkey::new_from_array(*user.as_array())
+            unpacked.owner,
+            pubkey::Pubkey::new_from_array(*user.as_array())
+        );
+        assert_eq!(
+            unpacked.mint,
+            pubkey::Pubkey::new_from_array(*mint.as_array())
+        );
+        assert_eq!(unpacked.amount, 1_000_000);
+    }
+
+    #[test]
+    fn test_set_token_balance_rent_exempt() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        manager.set_rent(&fork_id, 10_000, 2.0, 50).unwrap();
+        let mint = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+
+        let token_account = Pubkey::new_unique();
+        manager
+            .set_token_balance(
+                &fork_id,
+                token_account,
+                mint,
+                owner,
+                1,
+                TokenBalanceOptions::default(),
+            )
+            .unwrap();
+        let account = manager.get_account(&fork_id, token_account).unwrap();
+        let minimum = manager
+            .minimum_balance(&fork_id, TokenAccount::LEN)
+            .unwrap();
+        assert_eq!(account.lamports, minimum);
+        assert!(minimum > 1_000_000);
+
+        let custom = Pubkey::new_unique();
+        manager
+            .set_token_balance(
+                &fork_id,
+                custom,
+                mint,
+                owner,
+                1,
+                TokenBalanceOptions {
+                    lamports: Some(5),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(manager.get_account(&fork_id, custom).unwrap().lamports, 5);
+    }
+
+    #[test]
+    fn test_set_account_owner() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let token_account = Pubkey::new_unique();
+        manager
+            .set_token_balance(
+                &fork_id,
+                token_account,
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                42,
+                TokenBalanceOptions::default(),
+            )
+            .unwrap();
+        let before = manager.get_account(&fork_id, token_account).unwrap();
+
+        let program = Pubkey::new_unique();
+        manager
+            .set_account_owner(&fork_id, token_account, program)
+            .unwrap();
+        let after = manager.get_account(&fork_id, token_account).unwrap();
+        assert_eq!(after.owner, program);
+        assert_eq!(after.lamports, before.lamports);
+        assert_eq!(after.data, before.data);
+        assert_eq!(after.executable, before.executable);
+
+        let err = manager
+            .set_account_owner(&fork_id, Pubkey::new_unique(), program)
+            .unwrap_err();
+        assert!(err.to_string().contains("not found"));
+    }
+
+    #[test]
+    fn test_panic_does_not_poison_other_forks() {
+        let mut manager = ForkManager::new();
+        let broken = offline_fork(&mut manager);
+        let healthy = offline_fork(&mut manager);
+        let manager = Arc::new(RwLock::new(manager));
+
+        // Panic while holding both the manager and a fork's runtime
+        let panicking = Arc::clone(&manager);
+        let _ = std::thread::spawn(move || {
+            let manager = panicking.write().unwrap();
+            let fork = manager.get_fork(&broken).unwrap();
+            let _svm = fork.svm.write().unwrap();
+            panic!("transaction crashed the runtime");
+        })
+        .join();
+        assert!(manager.is_poisoned());
+
+        let pubkey = Pubkey::new_unique();
+        for fork_id in [healthy, broken] {
+            manager
+                .read_or_recover()
+                .set_lamports(&fork_id, pubkey, 1_000_000, false)
+                .unwrap();
+            let account = manager
+                .read_or_recover()
+                .get_account(&fork_id, pubkey)
+                .unwrap();
+            assert_eq!(account.lamports, 1_000_000);
+        }
+    }
+
+    #[test]
+    fn test_fee_schedule() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let schedule = manager.fee_schedule(&fork_id).unwrap();
+        assert_eq!(
+            schedule.lamports_per_signature,
+            fees::LAMPORTS_PER_SIGNATURE
+        );
+        assert!(schedule.fees_charged);
+
+        let free = manager
+            .create_fork_with_options(ForkOptions {
+                offline: true,
+                validation: ValidationOptions {
+                    fee_check: false,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(!manager.fee_schedule(&free).unwrap().fees_charged);
+        assert!(manager.fee_schedule(&Uuid::new_v4()).is_err());
+    }
+
+    #[test]
+    fn test_set_executable() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let token_program = Pubkey::new_from_array(*ID.as_array());
+
+        manager
+            .set_executable(&fork_id, token_program, false)
+            .unwrap();
+        let account = manager.get_account(&fork_id, token_program).unwrap();
+        assert!(!account.executable);
+        manager
+            .set_executable(&fork_id, token_program, true)
+            .unwrap();
+        let program = manager.get_account(&fork_id, token_program).unwrap();
+        assert!(program.executable);
+        assert_eq!(program.data, account.data);
+        assert_eq!(program.owner, account.owner);
+
+        // The runtime can't load an account no loader owns
+        let system = Pubkey::new_unique();
+        manager
+            .set_lamports(&fork_id, system, 1_000_000, false)
+            .unwrap();
+        let err = manager.set_executable(&fork_id, system, true).unwrap_err();
+        assert!(err.to_string().contains("not a loader program"));
+        // Nor a loader account which doesn't hold a program
+        manager
+            .set_account_owner(&fork_id, system, LOADERS[2])
+            .unwrap();
+        assert!(manager.set_executable(&fork_id, system, true).is_err());
+        let account = manager.get_account(&fork_id, system).unwrap();
+        assert!(!account.executable);
+        assert_eq!(account.lamports, 1_000_000);
+    }
+
+    #[test]
+    fn test_token_rent_source() {
+        let mut manager = ForkManager::new();
+        let fork_id = manager
+            .create_fork_with_options(ForkOptions {
+                offline: true,
+                token_rent_source: TokenRentSource::Owner,
+                ..Default::default()
+            })
+            .unwrap();
+        let owner = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        manager
+            .set_lamports(&fork_id, owner, 1_000_000_000, false)
+            .unwrap();
+        let minimum = manager
+            .minimum_balance(&fork_id, TokenAccount::LEN)
+            .unwrap();
+
+        let token_account = Pubkey::new_unique();
+        manager
+            .set_token_balance(
+                &fork_id,
+                token_account,
+                mint,
+                owner,
+                1,
+                TokenBalanceOptions::default(),
+            )
+            .unwrap();
+        let balance = |pubkey| manager.get_account(&fork_id, pubkey).unwrap().lamports;
+        assert_eq!(balance(owner), 1_000_000_000 - minimum);
+        assert_eq!(balance(token_account), minimum);
+
+        // Existing token accounts are only updated
+        manager
+            .set_token_balance(
+                &fork_id,
+                token_account,
+                mint,
+                owner,
+                2,
+                TokenBalanceOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(balance(owner), 1_000_000_000 - minimum);
+
+        // Owners which can't afford the rent are rejected
+        let poor = Pubkey::new_unique();
+        let err = manager
+            .set_token_balance(
+                &fork_id,
+                Pubkey::new_unique(),
+                mint,
+                poor,
+                1,
+                TokenBalanceOptions::default(),
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("rent payer"));
+    }
+
+    #[test]
+    fn test_set_token_balance_creates_mint() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+
+        let mint = Pubkey::new_unique();
+        let token_account = Pubkey::new_unique();
+        manager
+            .set_token_balance(
+                &fork_id,
+                token_account,
+                mint,
+                Pubkey::new_unique(),
+                500,
+                TokenBalanceOptions {
+                    decimals: Some(6),
+                    ..Default::default()
+                },
+            )
+            .expect("Failed to set token balance");
+
+        let mint_account = manager.get_account(&fork_id, mint).unwrap();
+        let mint_state = Mint::unpack(&mint_account.data).unwrap();
+        assert_eq!(mint_state.decimals, 6);
+        assert_eq!(mint_state.supply, 500);
+        assert!(mint_state.is_initialized);
+
+        // The supply follows the balances, as minting and burning would
+        let supply = |manager: &ForkManager| {
+            let mint_account = manager.get_account(&fork_id, mint).unwrap();
+            Mint::unpack(&mint_account.data).unwrap().supply
+        };
+        manager
+            .set_token_balance(
+                &fork_id,
+                token_account,
+                mint,
+                Pubkey::new_unique(),
+                200,
+                TokenBalanceOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(supply(&manager), 200);
+        manager
+            .set_token_balance(
+                &fork_id,
+                Pubkey::new_unique(),
+                mint,
+                Pubkey::new_unique(),
+                50,
+                TokenBalanceOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(supply(&manager), 250);
+
+        // An existing account which isn't a mint is rejected
+        let not_a_mint = Pubkey::new_unique();
+        manager
+            .set_lamports(&fork_id, not_a_mint, 1_000_000, false)
+            .unwrap();
+        let result = manager.set_token_balance(
+            &fork_id,
+            Pubkey::new_unique(),
+            not_a_mint,
+            Pubkey::new_unique(),
+            1,
+            TokenBalanceOptions::default(),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_create_mint() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let authority = Pubkey::new_unique();
+        let freeze_authority = Pubkey::new_unique();
+
+        let mint = manager
+            .create_mint(&fork_id, None, 6, authority, Some(freeze_authority))
+            .unwrap();
+        let account = manager.get_account(&fork_id, mint).unwrap();
+        let state = Mint::unpack(&account.data).unwrap();
+        assert_eq!(state.decimals, 6);
+        assert_eq!(state.supply, 0);
+        assert_eq!(
+            state.mint_authority,
+            COption::Some(pubkey::Pubkey::new_from_array(authority.to_bytes()))
+        );
+        assert!(state.freeze_authority.is_some());
+
+        // Token balances can be set on it, and the address can't be reused
+        let token_account = Pubkey::new_unique();
+        manager
+            .set_token_balance(
+                &fork_id,
+                token_account,
+                mint,
+                authority,
+                0,
+                TokenBalanceOptions {
+                    check_supply: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert!(
+            manager
+                .create_mint(&fork_id, Some(mint), 6, authority, None)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_freeze_token_account() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let mint = Pubkey::new_unique();
+        let (owner, source, destination) = token_accounts(&manager, &fork_id, &mint, 100);
+
+        manager
+            .set_token_account_state(&fork_id, source, true)
+            .unwrap();
+        let account = manager.get_account(&fork_id, source).unwrap();
+        let token_acc = TokenAccount::unpack(&account.data).unwrap();
+        assert_eq!(token_acc.state, AccountState::Frozen);
+
+        let tx = token_transfer_tx(&manager, &fork_id, &source, &destination, &owner, 10);
+        assert!(manager.execute_transaction(&fork_id, tx).is_err());
+
+        manager
+            .set_token_account_state(&fork_id, source, false)
+            .unwrap();
+        let tx = token_transfer_tx(&manager, &fork_id, &source, &destination, &owner, 11);
+        assert!(manager.execute_transaction(&fork_id, tx).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_token_accounts() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let mint = Pubkey::new_unique();
+        let (owner, source, _destination) = token_accounts(&manager, &fork_id, &mint, 100);
+        let balance = manager.get_token_balance(&fork_id, source).unwrap();
+        assert_eq!(balance.amount, 100);
+        assert_eq!(balance.owner, owner.pubkey().to_string());
+        assert!(!balance.frozen);
+
+        // A mint, then an empty account, both owned by the token program
+        let token_program = Pubkey::new_from_array(*ID.as_array());
+        let empty = Pubkey::new_unique();
+        manager
+            .get_fork(&fork_id)
+            .unwrap()
+            .svm
+            .write_or_recover()
+            .set_account(empty, Account::new(1_000_000, 0, &token_program))
+            .unwrap();
+        for pubkey in [mint, empty] {
+            let expected = format!("Account at {} is not an initialized token account", pubkey);
+            let err = manager.get_token_balance(&fork_id, pubkey).unwrap_err();
+            assert_eq!(err.to_string(), expected);
+            let err = manager
+                .set_token_account_state(&fork_id, pubkey, true)
+                .unwrap_err();
+            assert_eq!(err.to_string(), expected);
+        }
+
+        let err = manager
+            .get_token_balance(&fork_id, owner.pubkey())
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("not owned by the SPL token program")
+        );
+    }
+
+    #[test]
+    fn test_lookup_tables() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let payer = Keypair::new();
+        manager
+            .set_lamports(&fork_id, payer.pubkey(), 1_000_000_000, false)
+            .unwrap();
+        let recipients = [Pubkey::new_unique(), Pubkey::new_unique()];
+
+        let table = manager
+            .create_lookup_table(&fork_id, None, Some(payer.pubkey()), vec![recipients[0]])
+            .unwrap();
+        let addresses = manager
+            .extend_lookup_table(&fork_id, table, vec![recipients[1]])
+            .unwrap();
+        assert_eq!(addresses, recipients);
+
+        // Both recipients are loaded from the table
+        let transfers: Vec<_> = recipients
+            .iter()
+            .map(|recipient| system_instruction::transfer(&payer.pubkey(), recipient, 1_000_000))
+            .collect();
+        let message = v0::Message::try_compile(
+            &payer.pubkey(),
+            &transfers,
+            &[AddressLookupTableAccount {
+                key: table,
+                addresses,
+            }],
+            latest_blockhash(&manager, &fork_id),
+        )
+        .unwrap();
+        assert_eq!(message.address_table_lookups[0].writable_indexes, [0, 1]);
+        let tx = VersionedTransaction::try_new(VersionedMessage::V0(message), &[&payer]).unwrap();
+        manager.execute_transaction(&fork_id, tx).unwrap();
+        for recipient in recipients {
+            let account = manager.get_local_account(&fork_id, recipient).unwrap();
+            assert_eq!(account.lamports, 1_000_000);
+        }
+
+        let frozen = manager
+            .create_lookup_table(&fork_id, None, None, vec![recipients[0]])
+            .unwrap();
+        assert!(
+            manager
+                .extend_lookup_table(&fork_id, frozen, vec![recipients[1]])
+                .is_err()
+        );
+        assert!(
+            manager
+                .create_lookup_table(&fork_id, Some(table), None, vec![])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_set_token_delegate() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let mint = Pubkey::new_unique();
+        let (_owner, source, destination) = token_accounts(&manager, &fork_id, &mint, 100);
+
+        let delegate = Keypair::new();
+        manager
+            .set_lamports(&fork_id, delegate.pubkey(), 1_000_000_000, false)
+            .unwrap();
+        manager
+            .set_token_delegate(&fork_id, source, delegate.pubkey(), 50)
+            .unwrap();
+
+        let tx = token_transfer_tx(&manager, &fork_id, &source, &destination, &delegate, 30);
+        assert!(manager.execute_transaction(&fork_id, tx).is_ok());
+
+        // Only 20 of the delegated amount are left
+        let tx = token_transfer_tx(&manager, &fork_id, &source, &destination, &delegate, 21);
+        assert!(manager.execute_transaction(&fork_id, tx).is_err());
+
+        let account = manager.get_account(&fork_id, destination).unwrap();
+        assert_eq!(TokenAccount::unpack(&account.data).unwrap().amount, 30);
+    }
+
+    #[test]
+    fn test_setup() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+
+        let funded = Pubkey::new_unique();
+        let token_account = Pubkey::new_unique();
+        let raw = Pubkey::new_unique();
+        let result = manager
+            .setup(
+                &fork_id,
+                ForkSetup {
+                    lamports: vec![LamportsSetup {
+                        pubkey: funded.to_string(),
+                        lamports: 1_000_000,
+                        allow_rent_paying: false,
+                    }],
+                    token_balances: vec![TokenBalanceSetup {
+                        token_account: token_account.to_string(),
+                        mint: Pubkey::new_unique().to_string(),
+                        owner: funded.to_string(),
+                        amount: 7,
+                        decimals: Some(2),
+                        check_supply: false,
+                        lamports: None,
+                    }],
+                    accounts: vec![ExportedAccount::new(
+                        &raw,
+                        &Account::new(5_000_000, 4, &Pubkey::new_unique()),
+                    )],
+                    programs: vec![],
+                },
+            )
+            .unwrap();
+        assert_eq!(result.lamports, vec![funded.to_string()]);
+        assert_eq!(result.token_balances, vec![token_account.to_string()]);
+
+        assert_eq!(
+            manager.get_account(&fork_id, funded).unwrap().lamports,
+            1_000_000
+        );
+        let account = manager.get_account(&fork_id, token_account).unwrap();
+        assert_eq!(TokenAccount::unpack(&account.data).unwrap().amount, 7);
+        assert_eq!(manager.get_account(&fork_id, raw).unwrap().data.len(), 4);
+    }
+
+    #[test]
+    fn test_associated_token_address() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let (owner, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        let token_program = Pubkey::new_from_array(*ID.as_array());
+        let legacy = manager
+            .associated_token_address(&fork_id, &owner, &mint, None)
+            .unwrap();
+        let (address, bump) = Pubkey::find_program_address(
+            &[owner.as_ref(), token_program.as_ref(), mint.as_ref()],
+            &ASSOCIATED_TOKEN_PROGRAM,
+        );
+        assert_eq!(legacy.address, address.to_string());
+        assert_eq!(legacy.bump, bump);
+        assert_eq!(
+            manager
+                .associated_token_address(&fork_id, &owner, &mint, Some(token_program))
+                .unwrap(),
+            legacy
+        );
+
+        // Token-2022 accounts live at other addresses
+        let token_2022 = manager
+            .associated_token_address(&fork_id, &owner, &mint, Some(TOKEN_2022_PROGRAM))
+            .unwrap();
+        assert_ne!(token_2022.address, legacy.address);
+
+        assert!(
+            manager
+                .associated_token_address(&fork_id, &owner, &mint, Some(Pubkey::new_unique()))
+                .is_err()
+        );
+        assert!(
+            manager
+                .associated_token_address(&Uuid::new_v4(), &owner, &mint, None)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_set_token_balances() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+
+        let owner = Pubkey::new_unique();
+        let (usdc, bonk) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let explicit = Pubkey::new_unique();
+        let entry = |token_account: Option<Pubkey>, mint: Pubkey, amount: u64| TokenBalanceEntry {
+            token_account: token_account.map(|key| key.to_string()),
+            mint: mint.to_string(),
+            owner: owner.to_string(),
+            amount,
+            token_program: None,
+        };
+        let addresses = manager
+            .set_token_balances(
+                &fork_id,
+                vec![entry(None, usdc, 5), entry(Some(explicit), bonk, 9)],
+            )
+            .unwrap();
+        let ata = manager
+            .associated_token_address(&fork_id, &owner, &usdc, None)
+            .unwrap()
+            .address
+            .parse::<Pubkey>()
+            .unwrap();
+        assert_eq!(addresses, vec![ata.to_string(), explicit.to_string()]);
+        for (address, mint, amount) in [(ata, usdc, 5), (explicit, bonk, 9)] {
+            let balance = manager.get_token_balance(&fork_id, address).unwrap();
+            assert_eq!(balance.mint, mint.to_string());
+            assert_eq!(balance.owner, owner.to_string());
+            assert_eq!(balance.amount, amount);
+        }
+
+        // Nothing is applied when an entry is invalid
+        let unapplied = Pubkey::new_unique();
+        let unsupported = TokenBalanceEntry {
+            token_program: Some(Pubkey::new_unique().to_string()),
+            ..entry(None, usdc, 1)
+        };
+        assert!(
+            manager
+                .set_token_balances(&fork_id, vec![entry(Some(unapplied), usdc, 1), unsupported])
+                .is_err()
+        );
+        // Nor when applying one fails, here because the mint isn't one
+        assert!(
+            manager
+                .set_token_balances(
+                    &fork_id,
+                    vec![entry(Some(unapplied), usdc, 1), entry(None, ata, 1)]
+                )
+                .is_err()
+        );
+        assert!(manager.get_account(&fork_id, unapplied).is_err());
+    }
+
+    #[test]
+    fn test_oversized_accounts() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let owner = Pubkey::new_unique();
+
+        let largest = Pubkey::new_unique();
+        let account = Account::new(1, MAX_ACCOUNT_DATA_LEN, &owner);
+        let imported = manager
+            .import_accounts(&fork_id, vec![ExportedAccount::new(&largest, &account)])
+            .unwrap();
+        assert_eq!(imported, 1);
+
+        let oversized = Pubkey::new_unique();
+        let account = Account::new(1, MAX_ACCOUNT_DATA_LEN + 1, &owner);
+        let err = manager
+            .import_accounts(&fork_id, vec![ExportedAccount::new(&oversized, &account)])
+            .unwrap_err();
+        assert!(err.to_string().contains("maximum account size"));
+        assert!(manager.get_account(&fork_id, oversized).is_err());
+
+        // Far larger payloads are rejected before being decoded
+        let huge = ExportedAccount {
+            data_base64: "A".repeat(2 * MAX_ACCOUNT_DATA_LEN),
+            ..ExportedAccount::new(&oversized, &Account::new(1, 0, &owner))
+        };
+        assert!(huge.to_account().is_err());
+    }
+
+    #[test]
+    fn test_setup_is_atomic() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+
+        // An existing account which isn't a mint fails the token balance
+        // section after the lamports section was applied
+        let not_a_mint = Pubkey::new_unique();
+        manager
+            .set_lamports(&fork_id, not_a_mint, 1_000_000, false)
+            .unwrap();
+
+        let funded = Pubkey::new_unique();
+        let result = manager.setup(
+            &fork_id,
+            ForkSetup {
+                lamports: vec![LamportsSetup {
+                    pubkey: funded.to_string(),
+                    lamports: 1_000_000,
+                    allow_rent_paying: false,
+                }],
+                token_balances: vec![TokenBalanceSetup {
+                    token_account: Pubkey::new_unique().to_string(),
+                    mint: not_a_mint.to_string(),
+                    owner: funded.to_string(),
+                    amount: 1,
+                    decimals: None,
+                    check_supply: false,
+                    lamports: None,
+                }],
+                ..Default::default()
+            },
+        );
+        assert!(result.is_err());
+        assert!(manager.get_account(&fork_id, funded).is_err());
+
+        // Invalid entries are rejected before anything is applied
+        let result = manager.setup(
+            &fork_id,
+            ForkSetup {
+                lamports: vec![LamportsSetup {
+                    pubkey: "invalid".to_string(),
+                    lamports: 1,
+                    allow_rent_paying: false,
+                }],
+                ..Default::default()
+            },
+        );
+        assert!(result.is_err());
+
+        // Programs which don't load fail the setup before anything is
+        // written to the fork
+        let result = manager.setup(
+            &fork_id,
+            ForkSetup {
+                lamports: vec![LamportsSetup {
+                    pubkey: funded.to_string(),
+                    lamports: 1_000_000,
+                    allow_rent_paying: false,
+                }],
+                programs: vec![ProgramSetup {
+                    program_id: Pubkey::new_unique().to_string(),
+                    program_base64: engine::general_purpose::STANDARD.encode(b"not an elf"),
+                }],
+                ..Default::default()
+            },
+        );
+        assert!(result.is_err());
+        assert!(manager.get_account(&fork_id, funded).is_err());
+    }
+
+    #[test]
+    fn test_get_program_accounts() {
+        let mut manager = ForkManager::new();
+        let fork_id = manager.create_fork().expect("Failed to create fork");
+
+        let mint = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let token_account = Pubkey::new_unique();
+        manager
+            .set_token_balance(
+                &fork_id,
+                token_account,
+                mint,
+                owner,
+                42,
+                TokenBalanceOptions::default(),
+            )
+            .expect("Failed to set token balance");
+        manager
+            .set_lamports(&fork_id, Pubkey::new_unique(), 1_000_000, false)
+            .expect("Failed to set lamports");
+
+        let token_program = Pubkey::new_from_array(*ID.as_array());
+        // The token account and its auto-created mint
+        let accounts = manager
+            .get_program_accounts(&fork_id, token_program, None)
+            .unwrap();
+        assert_eq!(accounts.len(), 2);
+
+        let filters = vec![ProgramAccountsFilter::DataSize(TokenAccount::LEN)];
+        let accounts = manager
+            .get_program_accounts(&fork_id, token_program, Some(filters))
+            .unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].0, token_account);
+
+        let filters = vec![
+            ProgramAccountsFilter::DataSize(TokenAccount::LEN),
+            ProgramAccountsFilter::Memcmp(Memcmp {
+                offset: 0,
+                bytes: mint.to_bytes().to_vec(),
+            }),
+        ];
+        let accounts = manager
+            .get_program_accounts(&fork_id, token_program, Some(filters))
+            .unwrap();
+        assert_eq!(accounts.len(), 1);
+
+        // The owner is stored at offset 32 of token accounts, mints are
+        // skipped as their data differs there
+        let filters: Vec<ProgramAccountsFilter> = serde_json::from_value(serde_json::json!([
+            { "memcmp": { "offset": 32, "bytes": owner.to_string() } },
+        ]))
+        .unwrap();
+        let accounts = manager
+            .get_program_accounts(&fork_id, token_program, Some(filters))
+            .unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].0, token_account);
+
+        // Offsets past the end of the data don't match
+        let filters = vec![ProgramAccountsFilter::Memcmp(Memcmp {
+            offset: TokenAccount::LEN,
+            bytes: vec![0],
+        })];
+        let accounts = manager
+            .get_program_accounts(&fork_id, token_program, Some(filters))
+            .unwrap();
+        assert!(accounts.is_empty());
+
+        let filters = vec![ProgramAccountsFilter::DataSize(0)];
+        let accounts = manager
+            .get_program_accounts(&fork_id, token_program, Some(filters))
+            .unwrap();
+        assert!(accounts.is_empty());
+    }
+
+    #[test]
+    fn test_program_accounts_filter_json() {
+        let bytes = vec![1, 2, 3, 255];
+        let filters: Vec<ProgramAccountsFilter> = serde_json::from_value(serde_json::json!([
+            { "dataSize": 165 },
+            { "memcmp": { "offset": 4, "bytes": bs58::encode(&bytes).into_string() } },
+            { "memcmp": { "offset": 8, "bytes": "AQID/w==", "encoding": "base64" } },
+        ]))
+        .unwrap();
+
+        assert!(matches!(filters[0], ProgramAccountsFilter::DataSize(165)));
+        for (filter, offset) in filters[1..].iter().zip([4, 8]) {
+            let ProgramAccountsFilter::Memcmp(memcmp) = filter else {
+                panic!("expected a memcmp filter");
+            };
+            assert_eq!(memcmp.offset, offset);
+            assert_eq!(memcmp.bytes, bytes);
+        }
+
+        let invalid = serde_json::json!([{ "memcmp": { "offset": 0, "bytes": "0OIl" } }]);
+        assert!(serde_json::from_value::<Vec<ProgramAccountsFilter>>(invalid).is_err());
+    }
+
+    #[test]
+    fn test_offline_fork() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+
+        let payer = Keypair::new();
+        let recipient = Pubkey::new_unique();
+        manager
+            .set_lamports(&fork_id, payer.pubkey(), 1_000_000_000, false)
+            .unwrap();
+
+        let tx = transfer_tx(&manager, &fork_id, &payer, &recipient, 1_000_000);
+        manager
+            .execute_transaction(&fork_id, tx)
+            .expect("Failed to execute transfer");
+
+        let account = manager.get_account(&fork_id, recipient).unwrap();
+        assert_eq!(account.lamports, 1_000_000);
+
+        let missing = manager.get_account(&fork_id, Pubkey::new_unique());
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn test_disabled_features() {
+        let feature_id = *FeatureSet::all_enabled().active().keys().next().unwrap();
+
+        let mut manager = ForkManager::new();
+        let fork_id = manager
+            .create_fork_with_options(ForkOptions {
+                offline: true,
+                disabled_features: vec![feature_id.to_string()],
+                ..Default::default()
+            })
+            .expect("Failed to create fork");
+
+        let fork = manager.get_fork(&fork_id).unwrap();
+        assert!(!fork.feature_set.is_active(&feature_id));
+        assert_eq!(
+            fork.feature_set.active().len(),
+            FeatureSet::all_enabled().active().len() - 1
+        );
+
+        let invalid = manager.create_fork_with_options(ForkOptions {
+            offline: true,
+            disabled_features: vec!["not-a-pubkey".to_string()],
+            ..Default::default()
+        });
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn test_disabled_feature_unloads_precompile() {
+        let secp256r1_program =
+            Pubkey::from_str_const("Secp256r1SigVerify1111111111111111111111111");
+        let mut manager = ForkManager::new();
+        let enabled = offline_fork(&mut manager);
+        let disabled = manager
+            .create_fork_with_options(ForkOptions {
+                offline: true,
+                disabled_features: vec![
+                    agave_feature_set::enable_secp256r1_precompile::id().to_string(),
+                ],
+                ..Default::default()
+            })
+            .unwrap();
+
+        let precompile = manager
+            .get_local_account(&enabled, secp256r1_program)
+            .unwrap();
+        assert!(precompile.executable);
+        assert!(
+            manager
+                .get_local_account(&disabled, secp256r1_program)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_disabled_feature_changes_execution() {
+        // The secp256r1 precompile only exists while its feature is active,
+        // invoking it otherwise is rejected before fees are charged
+        let secp256r1_program =
+            Pubkey::from_str_const("Secp256r1SigVerify1111111111111111111111111");
+        let fee_paid = |disabled_features: Vec<String>| {
+            let mut manager = ForkManager::new();
+            let fork_id = manager
+                .create_fork_with_options(ForkOptions {
+                    offline: true,
+                    disabled_features,
+                    ..Default::default()
+                })
+                .unwrap();
+            let payer = Keypair::new();
+            manager
+                .set_lamports(&fork_id, payer.pubkey(), 1_000_000_000, false)
+                .unwrap();
+
+            // Claims one signature without providing it, failing after the
+            // fee was charged when the precompile exists
+            let ix = Instruction::new_with_bytes(secp256r1_program, &[1, 0], vec![]);
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&payer.pubkey()),
+                &[&payer],
+                latest_blockhash(&manager, &fork_id),
+            );
+            assert!(manager.execute_transaction(&fork_id, tx.into()).is_err());
+            1_000_000_000
+                - manager
+                    .get_account(&fork_id, payer.pubkey())
+                    .unwrap()
+                    .lamports
+        };
+
+        assert_eq!(fee_paid(vec![]), 10_000);
+        assert_eq!(
+            fee_paid(vec![
+                agave_feature_set::enable_secp256r1_precompile::id().to_string()
+            ]),
+            0
+        );
+    }
+
+    #[test]
+    fn test_fund_on_creation() {
+        let payer = Pubkey::new_unique();
+        let mut manager = ForkManager::new();
+        let fork_id = manager
+            .create_fork_with_options(ForkOptions {
+                offline: true,
+                fund: vec![LamportsSetup {
+                    pubkey: payer.to_string(),
+                    lamports: 5_000_000_000,
+                    allow_rent_paying: false,
+                }],
+                ..Default::default()
+            })
+            .unwrap();
+        let account = manager.get_account(&fork_id, payer).unwrap();
+        assert_eq!(account.lamports, 5_000_000_000);
+        assert_eq!(manager.get_stats(&fork_id).unwrap().accounts, 1);
+
+        let invalid = manager.create_fork_with_options(ForkOptions {
+            offline: true,
+            fund: vec![LamportsSetup {
+                pubkey: "invalid".to_string(),
+                lamports: 1,
+                allow_rent_paying: false,
+            }],
+            ..Default::default()
+        });
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn test_list_forks() {
+        let mut manager = ForkManager::new();
+        let unnamed = offline_fork(&mut manager);
+        let named = manager
+            .create_fork_with_options(ForkOptions {
+                offline: true,
+                name: Some("swap tests".to_string()),
+                labels: BTreeMap::from([("suite".to_string(), "swap".to_string())]),
+                ..Default::default()
+            })
+            .unwrap();
+
+        let forks = manager.list_forks(None);
+        assert_eq!(forks.len(), 2);
+        assert_eq!(forks[0].id, unnamed);
+        assert_eq!(forks[1].id, named);
+        assert_eq!(forks[1].name.as_deref(), Some("swap tests"));
+
+        let ids = |label| -> Vec<Uuid> {
+            manager
+                .list_forks(Some(label))
+                .into_iter()
+                .map(|fork| fork.id)
+                .collect()
+        };
+        assert_eq!(ids("suite"), vec![named]);
+        assert_eq!(ids("suite=swap"), vec![named]);
+        assert!(ids("suite=lending").is_empty());
+        assert!(ids("other").is_empty());
+    }
+
+    #[test]
+    fn test_history_capacity() {
+        let mut manager = ForkManager::new().with_history_capacity(3);
+        let fork_id = offline_fork(&mut manager);
+        let custom_id = manager
+            .create_fork_with_options(ForkOptions {
+                offline: true,
+                history_capacity: Some(1),
+                ..Default::default()
+            })
+            .unwrap();
+
+        for (id, capacity) in [(fork_id, 3), (custom_id, 1)] {
+            let fork = manager.get_fork(&id).unwrap();
+            for _ in 0..5 {
+                fork.record_transaction(
+                    TransactionKind::Executed,
+                    VersionedTransaction::default(),
+                    Ok(TransactionMetadata {
+                        signature: Signature::new_unique(),
+                        ..Default::default()
+                    }),
+                    None,
+                );
+            }
+            let history = fork.executed_transactions.lock_or_recover();
+            assert_eq!(history.records.len(), capacity);
+            assert_eq!(history.recorded, 5);
+            drop(history);
+
+            let stats = manager.get_stats(&id).unwrap();
+            assert_eq!(stats.executed_transactions, capacity);
+            assert_eq!(stats.executed_transactions_total, 5);
+        }
+    }
+
+    #[test]
+    fn test_history_limit_is_clamped() {
+        let records: VecDeque<_> = (0..MAX_HISTORY_LIMIT + 1)
+            .map(|_| TransactionRecord {
+                txn: TransactionMetadata::default(),
+                tx: None,
+                tx_base64: None,
+                time: Utc::now(),
+                success: true,
+            })
+            .collect();
+        let query = TransactionHistoryQuery {
+            limit: Some(usize::MAX),
+            ..Default::default()
+        };
+        let page = query.page(&records);
+        assert_eq!(page.total, MAX_HISTORY_LIMIT + 1);
+        assert_eq!(page.transactions.len(), MAX_HISTORY_LIMIT);
+    }
+
+    #[test]
+    fn test_simulate_unsigned() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let payer = Keypair::new();
+        manager
+            .set_lamports(&fork_id, payer.pubkey(), 1_000_000_000, false)
+            .unwrap();
+        let mut tx = transfer_tx(&manager, &fork_id, &payer, &Pubkey::new_unique(), 1_000);
+        tx.signatures = vec![Signature::default()];
+
+        let unsigned = SimulateOptions {
+            allow_unsigned: true,
+            ..Default::default()
+        };
+        let simulated = manager
+            .simulate_transaction_detailed(&fork_id, tx.clone(), unsigned)
+            .unwrap();
+        assert!(simulated.is_ok());
+
+        // Signatures are checked again afterwards
+        let rejected = manager
+            .simulate_transaction_detailed(&fork_id, tx.clone(), Default::default())
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(rejected.err, TransactionError::SignatureFailure);
+        assert!(manager.execute_transaction(&fork_id, tx.clone()).is_err());
+
+        // Even when the simulation panics
+        let fork = manager.get_fork(&fork_id).unwrap();
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let mut svm = fork.svm.write().unwrap();
+            let _skipped = SigverifySkipped::new(&mut svm);
+            panic!("simulation crashed the runtime");
+        }));
+        assert!(panicked.is_err());
+        let err = manager.execute_transaction(&fork_id, tx).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<TransactionError>(),
+            Some(&TransactionError::SignatureFailure)
+        );
+    }
+
+    #[test]
+    fn test_simulate_impersonated() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        // An account whose key the user doesn't hold
+        let whale = Pubkey::new_unique();
+        let payer = Keypair::new();
+        for pubkey in [whale, payer.pubkey()] {
+            manager
+                .set_lamports(&fork_id, pubkey, 1_000_000_000_000, false)
+                .unwrap();
+        }
+        let recipient = Pubkey::new_unique();
+        let message = Message::new_with_blockhash(
+            &[system_instruction::transfer(
+                &whale,
+                &recipient,
+                500_000_000_000,
+            )],
+            Some(&payer.pubkey()),
+            &latest_blockhash(&manager, &fork_id),
+        );
+        let mut tx = Transaction::new_unsigned(message);
+        tx.partial_sign(&[&payer], tx.message.recent_blockhash);
+        let tx = VersionedTransaction::from(tx);
+
+        let impersonate = |impersonate: Vec<Pubkey>| SimulateOptions {
+            impersonate,
+            ..Default::default()
+        };
+        let simulated = manager
+            .simulate_transaction_detailed(&fork_id, tx.clone(), impersonate(vec![whale]))
+            .unwrap()
+            .unwrap();
+        assert!(
+            simulated
+                .meta
+                .logs
+                .iter()
+                .any(|log| log.ends_with("success"))
+        );
+        // Simulations don't change the fork
+        assert_eq!(
+            manager.get_account(&fork_id, whale).unwrap().lamports,
+            1_000_000_000_000
+        );
+
+        // The other signers must still sign
+        let mut forged = tx.clone();
+        forged.signatures[0] = Signature::default();
+        let rejected = manager
+            .simulate_transaction_detailed(&fork_id, forged, impersonate(vec![whale]))
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(rejected.err, TransactionError::SignatureFailure);
+
+        // Only signers can be impersonated, and signatures are checked
+        // again afterwards
+        assert!(
+            manager
+                .simulate_transaction_detailed(&fork_id, tx.clone(), impersonate(vec![recipient]))
+                .is_err()
+        );
+        let rejected = manager
+            .simulate_transaction_detailed(&fork_id, tx, Default::default())
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(rejected.err, TransactionError::SignatureFailure);
+    }
+
+    #[test]
+    fn test_compute_fit() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let payer = Keypair::new();
+        manager
+            .set_lamports(&fork_id, payer.pubkey(), 1_000_000_000, false)
+            .unwrap();
+
+        let tx = transfer_tx(&manager, &fork_id, &payer, &Pubkey::new_unique(), 1_000);
+        let simulated = manager
+            .simulate_transaction_detailed(&fork_id, tx.clone(), Default::default())
+            .unwrap()
+            .unwrap();
+        let fit = manager
+            .compute_fit(&fork_id, tx, Default::default())
+            .unwrap();
+        assert!(fit.fits);
+        assert_eq!(
+            fit.compute_units_consumed,
+            simulated.meta.compute_units_consumed
+        );
+        assert_eq!(
+            fit.compute_budget,
+            fees::MAX_BUILTIN_ALLOCATION_COMPUTE_UNIT_LIMIT
+        );
+
+        let set_limit = |units: u32| {
+            let mut data = vec![2];
+            data.extend_from_slice(&units.to_le_bytes());
+            Instruction::new_with_bytes(
+                Pubkey::from_str_const("ComputeBudget111111111111111111111111111111"),
+                &data,
+                vec![],
+            )
+        };
+        let transfer = || system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1);
+        let fit_of = |instructions: &[Instruction]| {
+            let tx = Transaction::new_signed_with_payer(
+                instructions,
+                Some(&payer.pubkey()),
+                &[&payer],
+                latest_blockhash(&manager, &fork_id),
+            );
+            manager.compute_fit(&fork_id, tx.into(), Default::default())
+        };
+
+        // Without a requested limit, each builtin instruction gets its own
+        let fit = fit_of(&[transfer(), transfer()]).unwrap();
+        assert!(fit.fits);
+        assert_eq!(
+            fit.compute_budget,
+            2 * fees::MAX_BUILTIN_ALLOCATION_COMPUTE_UNIT_LIMIT
+        );
+
+        // Requested limits are capped to the maximum
+        let fit = fit_of(&[set_limit(u32::MAX), transfer()]).unwrap();
+        assert!(fit.fits);
+        assert_eq!(fit.compute_budget, fees::MAX_COMPUTE_UNIT_LIMIT);
+
+        // A budget too small for the transfer
+        let fit = fit_of(&[set_limit(10), transfer()]).unwrap();
+        assert!(!fit.fits);
+        assert_eq!(fit.compute_budget, 10);
+        assert!(fit.compute_units_consumed <= 10);
+
+        // Failures other than the budget are errors
+        let overdraft =
+            system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 10_000_000_000);
+        assert!(fit_of(&[overdraft]).is_err());
+    }
+
+    #[test]
+    fn test_return_data() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let payer = Keypair::new();
+        manager
+            .set_lamports(&fork_id, payer.pubkey(), 1_000_000_000, false)
+            .unwrap();
+        let mint = manager
+            .create_mint(&fork_id, None, 6, payer.pubkey(), None)
+            .unwrap();
+
+        // `GetAccountDataSize` returns the size of the mint's token accounts
+        let token_program = Pubkey::new_from_array(*ID.as_array());
+        let get_size = Instruction::new_with_bytes(
+            token_program,
+            &[21],
+            vec![AccountMeta::new_readonly(mint, false)],
+        );
+        let tx = Transaction::new_signed_with_payer(
+            &[get_size],
+            Some(&payer.pubkey()),
+            &[&payer],
+            latest_blockhash(&manager, &fork_id),
+        );
+        let meta = manager.execute_transaction(&fork_id, tx.into()).unwrap();
+        assert_eq!(
+            ReturnData::from_meta(&meta),
+            Some(ReturnData {
+                program_id: token_program.to_string(),
+                data_base64: engine::general_purpose::STANDARD
+                    .encode((TokenAccount::LEN as u64).to_le_bytes()),
+            })
+        );
+
+        let tx = transfer_tx(&manager, &fork_id, &payer, &Pubkey::new_unique(), 1_000);
+        let meta = manager.execute_transaction(&fork_id, tx).unwrap();
+        assert_eq!(ReturnData::from_meta(&meta), None);
+    }
+
+    #[test]
+    fn test_find_pda() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let program_id = Pubkey::new_unique();
+
+        let seeds = vec![b"vault".to_vec(), Pubkey::new_unique().to_bytes().to_vec()];
+        let pda = manager.find_pda(&fork_id, &program_id, &seeds).unwrap();
+        let (address, bump) = Pubkey::find_program_address(&[&seeds[0], &seeds[1]], &program_id);
+        assert_eq!(pda.address, address.to_string());
+        assert_eq!(pda.bump, bump);
+
+        let too_long = vec![vec![0; MAX_SEED_LEN + 1]];
+        assert!(manager.find_pda(&fork_id, &program_id, &too_long).is_err());
+        let too_many = vec![vec![0]; MAX_SEEDS];
+        assert!(manager.find_pda(&fork_id, &program_id, &too_many).is_err());
+    }
+
+    #[test]
+    fn test_scoped() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let other = offline_fork(&mut manager);
+
+        let scoped = manager.scoped(&fork_id);
+        assert!(scoped.get_fork(&other).is_none());
+        assert!(manager.scoped(&Uuid::new_v4()).forks.is_empty());
+
+        // The fork is shared, writes through either manager reach it
+        let pubkey = Pubkey::new_unique();
+        scoped
+            .set_lamports(&fork_id, pubkey, 1_000_000, false)
+            .unwrap();
+        assert_eq!(
+            manager.get_account(&fork_id, pubkey).unwrap().lamports,
+            1_000_000
+        );
+
+        // Deleting the fork meanwhile doesn't interrupt the scoped manager
+        assert_eq!(manager.delete_fork(&fork_id), DeleteForkResult::Deleted);
+        assert!(scoped.get_account(&fork_id, pubkey).is_ok());
+    }
+
+    #[test]
+    fn test_diff_forks() {
+        let mut manager = ForkManager::new();
+        let left = offline_fork(&mut manager);
+        let right = offline_fork(&mut manager);
+        let same = Pubkey::new_unique();
+        let changed = Pubkey::new_unique();
+        let only_left = Pubkey::new_unique();
+        for fork_id in [&left, &right] {
+            manager.set_lamports(fork_id, same, 1_000, false).unwrap();
+        }
+        manager.set_lamports(&left, changed, 1_000, false).unwrap();
+        manager.set_lamports(&right, changed, 2_000, false).unwrap();
+        manager
+            .set_lamports(&left, only_left, 1_000, false)
+            .unwrap();
+
+        let diff = manager.diff_forks(&left, &right, 0, None).unwrap();
+        assert_eq!(diff.total, 2);
+        let changed_diff = diff
+            .accounts
+            .iter()
+            .find(|acc| acc.pubkey == changed.to_string())
+            .unwrap();
+        assert_eq!(changed_diff.fields, vec!["lamports"]);
+        assert_eq!(changed_diff.right_lamports, Some(2_000));
+        let only_left_diff = diff
+            .accounts
+            .iter()
+            .find(|acc| acc.pubkey == only_left.to_string())
+            .unwrap();
+        assert_eq!(only_left_diff.fields, vec!["exists"]);
+        assert_eq!(only_left_diff.right_lamports, None);
+
+        let page = manager.diff_forks(&left, &right, 1, Some(5)).unwrap();
+        assert_eq!(page.total, 2);
+        assert_eq!(page.accounts.len(), 1);
+        assert_eq!(manager.diff_forks(&left, &left, 0, None).unwrap().total, 0);
+        assert!(manager.diff_forks(&left, &Uuid::new_v4(), 0, None).is_err());
+    }
+
+    #[test]
+    fn test_account_exists() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let funded = Pubkey::new_unique();
+        manager
+            .set_lamports(&fork_id, funded, 1_000_000, false)
+            .unwrap();
+
+        assert!(manager.account_exists(&fork_id, funded, true).unwrap());
+        assert!(
+            !manager
+                .account_exists(&fork_id, Pubkey::new_unique(), true)
+                .unwrap()
+        );
+        assert!(
+            !manager
+                .account_exists(&fork_id, Pubkey::new_unique(), false)
+                .unwrap()
+        );
+        assert!(
+            manager
+                .account_exists(&Uuid::new_v4(), funded, true)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_imported_accounts_are_not_fetched() {
+        let mainnet_account = serde_json::json!({
+            "context": { "slot": 1 },
+            "value": {
+                "lamports": 1u64,
+                "data": ["", "base64"],
+                "owner": "11111111111111111111111111111111",
+                "executable": false,
+                "rentEpoch": 0,
+                "space": 0,
+            },
+        });
+        let mocks = [(RpcRequest::GetAccountInfo, mainnet_account)].into();
+        let mut manager = ForkManager::new();
+        let fork_id = Uuid::new_v4();
+        let fork = Fork::new(
+            Arc::new(RwLock::new(LiteSVM::new())),
+            ForkOptions::default(),
+            FeatureSet::all_enabled(),
+            Some(MainnetRpc::mock(mocks)),
+        );
+        manager.insert_fork(fork_id, fork).unwrap();
+
+        let payer = Keypair::new();
+        let recipient = Pubkey::new_unique();
+        let system = Pubkey::default();
+        let imported = [(payer.pubkey(), 1_000_000_000), (recipient, 1_000_000)]
+            .iter()
+            .map(|(pubkey, lamports)| {
+                ExportedAccount::new(pubkey, &Account::new(*lamports, 0, &system))
+            })
+            .collect();
+        assert_eq!(manager.import_accounts(&fork_id, imported).unwrap(), 2);
+
+        let tx = transfer_tx(&manager, &fork_id, &payer, &recipient, 1_000);
+        manager.execute_transaction(&fork_id, tx).unwrap();
+        assert_eq!(manager.get_stats(&fork_id).unwrap().rpc_requests, 0);
+        assert_eq!(
+            manager.get_account(&fork_id, recipient).unwrap().lamports,
+            1_001_000
+        );
+    }
+
+    #[test]
+    fn test_replay_history() {
+        let mut manager = ForkManager::new();
+        let payer = Keypair::new();
+        let fork_id = manager
+            .create_fork_with_options(ForkOptions {
+                offline: true,
+                fund: vec![LamportsSetup {
+                    pubkey: payer.pubkey().to_string(),
+                    lamports: 1_000_000_000,
+                    allow_rent_paying: false,
+                }],
+                ..Default::default()
+            })
+            .unwrap();
+        let recipient = Pubkey::new_unique();
+        for _ in 0..2 {
+            let tx = transfer_tx(&manager, &fork_id, &payer, &recipient, 1_000_000);
+            manager.execute_transaction(&fork_id, tx).unwrap();
+            // Distinct blockhashes keep the transactions distinct
+            manager
+                .get_fork(&fork_id)
+                .unwrap()
+                .svm
+                .write_or_recover()
+                .expire_blockhash();
+        }
+
+        let manager = RwLock::new(manager);
+        let replay = ForkManager::replay_history(&manager, &fork_id, None).unwrap();
+        let manager = manager.into_inner().unwrap();
+        assert_ne!(replay.fork_id, fork_id);
+        assert_eq!(replay.replayed, 2);
+        assert!(replay.failed.is_empty());
+        for pubkey in [payer.pubkey(), recipient] {
+            assert_eq!(
+                manager
+                    .get_account(&replay.fork_id, pubkey)
+                    .unwrap()
+                    .lamports,
+                manager.get_account(&fork_id, pubkey).unwrap().lamports
+            );
+        }
+    }
+
+    #[test]
+    fn test_durable_nonce() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let payer = Keypair::new();
+        manager
+            .set_lamports(&fork_id, payer.pubkey(), 1_000_000_000, false)
+            .unwrap();
+
+        // A nonce account whose nonce comes from the current blockhash
+        let nonce = Pubkey::new_unique();
+        let durable_nonce = DurableNonce::from_blockhash(&latest_blockhash(&manager, &fork_id));
+        let state = NonceVersions::new(NonceState::Initialized(solana_nonce::state::Data::new(
+            payer.pubkey(),
+            durable_nonce,
+            5_000,
+        )));
+        let data = bincode::serialize(&state).unwrap();
+        let mut account = Account::new(
+            manager.minimum_balance(&fork_id, data.len()).unwrap(),
+            data.len(),
+            &solana_system_interface::program::ID,
+        );
+        account.data = data;
+        manager
+            .import_accounts(&fork_id, vec![ExportedAccount::new(&nonce, &account)])
+            .unwrap();
+        let stored_nonce = |manager: &ForkManager| {
+            let account = manager.get_account(&fork_id, nonce).unwrap();
+            match bincode::deserialize::<NonceVersions>(&account.data)
+                .unwrap()
+                .state()
+            {
+                NonceState::Initialized(data) => data.blockhash(),
+                NonceState::Uninitialized => panic!("nonce should be initialized"),
+            }
+        };
+        let nonce_tx = |blockhash: Hash, lamports: u64| -> VersionedTransaction {
+            let ixs = [
+                system_instruction::advance_nonce_account(&nonce, &payer.pubkey()),
+                system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), lamports),
+            ];
+            Transaction::new_signed_with_payer(&ixs, Some(&payer.pubkey()), &[&payer], blockhash)
+                .into()
+        };
+
+        // Simulations check the nonce like executions, leaving it untouched
+        let first = stored_nonce(&manager);
+        assert!(
+            manager
+                .simulate_transaction(&fork_id, nonce_tx(first, 1_000_000))
+                .is_ok()
+        );
+        assert_eq!(stored_nonce(&manager), first);
+
+        // Each transaction advances the nonce, the next one using the new one
+        manager
+            .execute_transaction(&fork_id, nonce_tx(first, 1_000_000))
+            .unwrap();
+        let second = stored_nonce(&manager);
+        assert_ne!(second, first);
+        manager
+            .execute_transaction(&fork_id, nonce_tx(second, 1_000_000))
+            .unwrap();
+        assert_ne!(stored_nonce(&manager), second);
+
+        // A used nonce is rejected
+        let err = manager
+            .execute_transaction(&fork_id, nonce_tx(first, 2_000_000))
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<TransactionError>(),
+            Some(&TransactionError::BlockhashNotFound)
+        );
+        let err = manager
+            .simulate_transaction(&fork_id, nonce_tx(first, 2_000_000))
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<TransactionError>(),
+            Some(&TransactionError::BlockhashNotFound)
+        );
+    }
+
+    #[test]
+    fn test_get_local_account() {
+        let mainnet_account = serde_json::json!({
+            "context": { "slot": 1 },
+            "value": {
+                "lamports": 1_000u64,
+                "data": ["", "base64"],
+                "owner": "11111111111111111111111111111111",
+                "executable": false,
+                "rentEpoch": 0,
+                "space": 0,
+            },
+        });
+        let mocks = [(RpcRequest::GetAccountInfo, mainnet_account)].into();
+        let mut manager = ForkManager::new();
+        let fork_id = Uuid::new_v4();
+        let fork = Fork::new(
+            Arc::new(RwLock::new(LiteSVM::new())),
+            ForkOptions::default(),
+            FeatureSet::all_enabled(),
+            Some(MainnetRpc::mock(mocks)),
+        );
+        manager.insert_fork(fork_id, fork).unwrap();
+        let pubkey = Pubkey::new_unique();
+
+        // Local misses don't reach mainnet
+        let err = manager.get_local_account(&fork_id, pubkey).unwrap_err();
+        assert_eq!(err.to_string(), "Account not found in fork");
+        assert_eq!(manager.get_stats(&fork_id).unwrap().rpc_requests, 0);
+
+        // By default they are fetched, and then found locally
+        let fetched = manager.get_account(&fork_id, pubkey).unwrap();
+        assert_eq!(fetched.lamports, 1_000);
+        assert_eq!(manager.get_stats(&fork_id).unwrap().rpc_requests, 1);
+        let local = manager.get_local_account(&fork_id, pubkey).unwrap();
+        assert_eq!(local.lamports, 1_000);
+
+        // Accounts found in the fork never reach the mock sender again,
+        // every miss does
+        for _ in 0..3 {
+            manager.get_account(&fork_id, pubkey).unwrap();
+        }
+        let fork = manager.get_fork(&fork_id).unwrap();
+        let rpc = fork.rpc.as_ref().unwrap();
+        assert_eq!(rpc.requests(), 1);
+        assert!(manager.get_account(&fork_id, Pubkey::new_unique()).is_err());
+        assert_eq!(rpc.requests(), 2);
+        assert_eq!(manager.get_stats(&fork_id).unwrap().rpc_requests, 2);
+    }
+
+    #[test]
+    fn test_get_features() {
+        let feature_id = *FeatureSet::all_enabled().active().keys().next().unwrap();
+
+        let mut manager = ForkManager::new();
+        let fork_id = manager
+            .create_fork_with_options(ForkOptions {
+                offline: true,
+                disabled_features: vec![feature_id.to_string()],
+                ..Default::default()
+            })
+            .unwrap();
+
+        let features = manager.get_features(&fork_id).unwrap();
+        assert_eq!(features.len(), FeatureSet::all_enabled().active().len() - 1);
+        assert!(features.iter().all(|f| f.id != feature_id.to_string()));
+        assert!(features.iter().any(|f| f.name.is_some()));
+        assert!(manager.get_features(&Uuid::new_v4()).is_err());
+    }
+
+    #[test]
+    fn test_waived_fee_matches_charged_fee() {
+        let mut manager = ForkManager::new();
+        let charged = offline_fork(&mut manager);
+        let waived = manager
+            .create_fork_with_options(ForkOptions {
+                offline: true,
+                validation: ValidationOptions {
+                    fee_check: false,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .unwrap();
+
+        // A prioritization fee on top of the signature fee, which the
+        // runtime doesn't collect
+        let payer = Keypair::new();
+        let mut price = vec![3];
+        price.extend_from_slice(&1_000_000u64.to_le_bytes());
+        let set_price = Instruction::new_with_bytes(
+            Pubkey::from_str_const("ComputeBudget111111111111111111111111111111"),
+            &price,
+            vec![],
+        );
+        let transfer = system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1_000);
+        let tx: VersionedTransaction = Transaction::new_signed_with_payer(
+            &[set_price, transfer],
+            Some(&payer.pubkey()),
+            &[&payer],
+            latest_blockhash(&manager, &charged),
+        )
+        .into();
+        let fee = manager.estimate_fee(&charged, &tx).unwrap();
+        assert!(fee.prioritization_fee > 0);
+
+        for fork_id in [charged, waived] {
+            manager
+                .set_lamports(&fork_id, payer.pubkey(), 1_000_000_000, false)
+                .unwrap();
+            assert!(manager.execute_transaction(&fork_id, tx.clone()).is_ok());
+        }
+        let balance = |fork_id| {
+            manager
+                .get_account(fork_id, payer.pubkey())
+                .unwrap()
+                .lamports
+        };
+        assert_eq!(balance(&charged), 1_000_000_000 - 1_000 - fee.base_fee);
+        assert_eq!(balance(&waived), 1_000_000_000 - 1_000);
+    }
+
+    #[test]
+    fn test_validation_options() {
+        let mut manager = ForkManager::new();
+        let lenient = manager
+            .create_fork_with_options(ForkOptions {
+                offline: true,
+                validation: ValidationOptions {
+                    sigverify: false,
+                    fee_check: false,
+                    blockhash_check: false,
+                },
+                ..Default::default()
+            })
+            .unwrap();
+
+        // Fees are waived and the blockhash isn't checked
+        let payer = Keypair::new();
+        manager
+            .set_lamports(&lenient, payer.pubkey(), 1_000_000_000, false)
+            .unwrap();
+        let ix = system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1_000_000);
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&payer.pubkey()),
+            &[&payer],
+            Hash::new_unique(),
+        );
+        assert!(manager.execute_transaction(&lenient, tx.into()).is_ok());
+        let account = manager.get_account(&lenient, payer.pubkey()).unwrap();
+        assert_eq!(account.lamports, 999_000_000);
+
+        let strict = manager
+            .create_fork_with_options(ForkOptions {
+                offline: true,
+                validation: ValidationOptions {
+                    blockhash_check: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .unwrap();
+        manager
+            .set_lamports(&strict, payer.pubkey(), 1_000_000_000, false)
+            .unwrap();
+        let ix = system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1_000_000);
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&payer.pubkey()),
+            &[&payer],
+            Hash::new_unique(),
+        );
+        assert!(manager.execute_transaction(&strict, tx.into()).is_err());
+        let tx = transfer_tx(&manager, &strict, &payer, &Pubkey::new_unique(), 1_000_000);
+        assert!(manager.execute_transaction(&strict, tx).is_ok());
+
+        assert!(
+            ValidationOptions::default()
+                .inconsistencies(false)
+                .is_empty()
+        );
+        let unsigned_strict = ValidationOptions {
+            sigverify: false,
+            blockhash_check: true,
+            ..Default::default()
+        };
+        assert_eq!(unsigned_strict.inconsistencies(true).len(), 1);
+        assert_eq!(unsigned_strict.inconsistencies(false).len(), 2);
+    }
+
+    #[test]
+    fn test_simulate_refresh_accounts() {
+        let mainnet_account = serde_json::json!({
+            "context": { "slot": 1 },
+            "value": {
+                "lamports": 2_000_000_000u64,
+                "data": ["", "base64"],
+                "owner": "11111111111111111111111111111111",
+                "executable": false,
+                "rentEpoch": 0,
+                "space": 0,
+            },
+        });
+        let mocks = [(RpcRequest::GetAccountInfo, mainnet_account)].into();
+
+        let mut manager = ForkManager::new();
+        let fork_id = Uuid::new_v4();
+        let fork = Fork::new(
+            Arc::new(RwLock::new(LiteSVM::new())),
+            ForkOptions::default(),
+            FeatureSet::all_enabled(),
+            Some(MainnetRpc::mock(mocks)),
+        );
+        manager.insert_fork(fork_id, fork).unwrap();
+        let payer = Keypair::new();
+        manager
+            .set_lamports(&fork_id, payer.pubkey(), 1_000_000_000, false)
+            .unwrap();
+        // Every account is cached, so only the refresh hits the mock
+        let recipient = Pubkey::new_unique();
+        manager
+            .set_lamports(&fork_id, recipient, 1_000_000_000, false)
+            .unwrap();
+        let tx = transfer_tx(&manager, &fork_id, &payer, &recipient, 1_000);
+
+        // The cached copy of the fee payer is used by default
+        let _ = manager.simulate_transaction_detailed(&fork_id, tx.clone(), Default::default());
+        let cached = manager.get_account(&fork_id, payer.pubkey()).unwrap();
+        assert_eq!(cached.lamports, 1_000_000_000);
+
+        let refresh = SimulateOptions {
+            refresh_accounts: true,
+            ..Default::default()
+        };
+        let _ = manager.simulate_transaction_detailed(&fork_id, tx, refresh);
+        let refreshed = manager.get_account(&fork_id, payer.pubkey()).unwrap();
+        assert_eq!(refreshed.lamports, 2_000_000_000);
+        // The payer and the recipient, programs aren't refreshed
+        assert_eq!(manager.get_stats(&fork_id).unwrap().rpc_requests, 2);
+    }
+
+    #[test]
+    fn test_request_rpc_url() {
+        let mainnet_account = serde_json::json!({
+            "context": { "slot": 1 },
+            "value": {
+                "lamports": 2_000_000_000u64,
+                "data": ["", "base64"],
+                "owner": "11111111111111111111111111111111",
+                "executable": false,
+                "rentEpoch": 0,
+                "space": 0,
+            },
+        });
+        let mocks = [(RpcRequest::GetAccountInfo, mainnet_account)].into();
+
+        // Nothing listens on this port, so fetches through it fail at once
+        let mut manager = ForkManager::new()
+            .with_rpc_policy(RpcPolicy {
+                timeout: Duration::from_secs(1),
+                max_retries: 0,
+                backoff: Duration::from_millis(1),
+            })
+            .with_allowed_rpc_urls(vec!["http://127.0.0.1:1".to_string()]);
+        let fork_id = Uuid::new_v4();
+        let fork = Fork::new(
+            Arc::new(RwLock::new(LiteSVM::new())),
+            ForkOptions::default(),
+            FeatureSet::all_enabled(),
+            Some(MainnetRpc::mock(mocks)),
+        );
+        manager.insert_fork(fork_id, fork).unwrap();
+
+        let unreachable = Some("http://127.0.0.1:1");
+        assert!(
+            manager
+                .find_account_with_rpc(&fork_id, Pubkey::new_unique(), unreachable)
+                .is_err()
+        );
+        assert_eq!(manager.get_stats(&fork_id).unwrap().rpc_requests, 0);
+
+        // Endpoints outside of the allowlist are rejected before any fetch
+        let err = manager
+            .find_account_with_rpc(&fork_id, Pubkey::new_unique(), Some("http://10.0.0.1"))
+            .unwrap_err();
+        assert!(err.to_string().contains("RPC_URL_ALLOWLIST"), "{}", err);
+        let payer = Keypair::new();
+        let tx = VersionedTransaction::from(Transaction::new_signed_with_payer(
+            &[system_instruction::transfer(
+                &payer.pubkey(),
+                &Pubkey::new_unique(),
+                1,
+            )],
+            Some(&payer.pubkey()),
+            &[&payer],
+            Hash::default(),
+        ));
+        assert!(
+            manager
+                .send_transaction_with_rpc(&fork_id, tx, Some("http://10.0.0.1"))
+                .is_err()
+        );
+        assert_eq!(manager.get_stats(&fork_id).unwrap().rpc_requests, 0);
+
+        // Invalid URLs fall back to the fork's endpoint
+        let account = manager
+            .find_account_with_rpc(&fork_id, Pubkey::new_unique(), Some("not a url"))
+            .unwrap();
+        assert_eq!(account.unwrap().lamports, 2_000_000_000);
+        assert_eq!(manager.get_stats(&fork_id).unwrap().rpc_requests, 1);
+
+        // Offline forks never fetch
+        let offline = offline_fork(&mut manager);
+        assert!(
+            manager
+                .find_account_with_rpc(&offline, Pubkey::new_unique(), unreachable)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_clone_program_accounts() {
+        let program_id = Pubkey::new_unique();
+        let owned = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let mock_account = |owner: &Pubkey, executable: bool| {
+            serde_json::json!({
+                "lamports": 1_000_000u64,
+                "data": ["AQID", "base64"],
+                "owner": owner.to_string(),
+                "executable": executable,
+                "rentEpoch": 0,
+                "space": 3,
+            })
+        };
+        // A builtin, so the fork doesn't load it as an ELF
+        let native_loader = LOADERS[4];
+        let program_accounts = serde_json::json!(
+            owned
+                .iter()
+                .map(|pubkey| serde_json::json!({
+                    "pubkey": pubkey.to_string(),
+                    "account": mock_account(&program_id, false),
+                }))
+                .collect::<Vec<_>>()
+        );
+        // The accounts are counted, then fetched
+        let mocks = || -> solana_rpc_client::mock_sender::MocksMap {
+            [
+                (
+                    RpcRequest::GetAccountInfo,
+                    serde_json::json!({
+                        "context": { "slot": 1 },
+                        "value": mock_account(&native_loader, true),
+                    }),
+                ),
+                (RpcRequest::GetProgramAccounts, program_accounts.clone()),
+                (RpcRequest::GetProgramAccounts, program_accounts.clone()),
+            ]
+            .into_iter()
+            .collect()
+        };
+
+        let mut manager = ForkManager::new();
+        let mut mock_fork = || {
+            let fork_id = Uuid::new_v4();
+            let fork = Fork::new(
+                Arc::new(RwLock::new(LiteSVM::new())),
+                ForkOptions::default(),
+                FeatureSet::all_enabled(),
+                Some(MainnetRpc::mock_map(mocks())),
+            );
+            manager.insert_fork(fork_id, fork).unwrap();
+            fork_id
+        };
+        let (bounded, unbounded) = (mock_fork(), mock_fork());
+
+        let err = manager
+            .clone_program_accounts(&bounded, program_id, None, Some(1))
+            .unwrap_err();
+        assert!(err.to_string().contains("over the limit of 1"));
+        assert!(manager.get_local_account(&bounded, owned[0]).is_err());
+        // The program and the count, the accounts weren't downloaded
+        assert_eq!(manager.get_stats(&bounded).unwrap().rpc_requests, 2);
+
+        let cloned = manager
+            .clone_program_accounts(&unbounded, program_id, None, None)
+            .unwrap();
+        assert_eq!(cloned.accounts, 2);
+        assert_eq!(cloned.program_data, None);
+        assert_eq!(manager.get_stats(&unbounded).unwrap().rpc_requests, 3);
+        for pubkey in owned {
+            let account = manager.get_local_account(&unbounded, pubkey).unwrap();
+            assert_eq!(account.owner, program_id);
+            assert_eq!(account.data, vec![1, 2, 3]);
+        }
+        assert!(
+            manager
+                .get_local_account(&unbounded, program_id)
+                .unwrap()
+                .executable
+        );
+
+        let offline = offline_fork(&mut manager);
+        assert!(
+            manager
+                .clone_program_accounts(&offline, program_id, None, None)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_programdata_address() {
+        let program_data = Pubkey::new_unique();
+        let mut data = 2u32.to_le_bytes().to_vec();
+        data.extend_from_slice(program_data.as_ref());
+        let program = Account {
+            lamports: 1,
+            data,
+            owner: UPGRADEABLE_LOADER,
+            executable: true,
+            rent_epoch: 0,
+        };
+        assert_eq!(programdata_address(&program), Some(program_data));
+
+        let legacy = Account {
+            owner: LOADERS[1],
+            ..program
+        };
+        assert_eq!(programdata_address(&legacy), None);
+    }
+
+    #[test]
+    fn test_get_blockhash() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+
+        let (blockhash, slot) = manager.get_blockhash(&fork_id).unwrap();
+        assert_eq!(blockhash, latest_blockhash(&manager, &fork_id));
+        assert_eq!(slot, 0);
+
+        assert!(manager.get_blockhash(&Uuid::new_v4()).is_err());
+    }
+
+    #[test]
+    fn test_set_blockhash() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+
+        let blockhash = Hash::new_unique();
+        manager.set_blockhash(&fork_id, blockhash).unwrap();
+        assert_eq!(manager.get_blockhash(&fork_id).unwrap().0, blockhash);
+
+        let fork = manager.get_fork(&fork_id).unwrap();
+        let slot_hashes = fork.svm.read_or_recover().get_sysvar::<SlotHashes>();
+        assert_eq!(slot_hashes.get(&0), Some(&blockhash));
+
+        // Blockhashes set after moving the clock are listed first
+        let slot = manager.set_epoch(&fork_id, 2).unwrap().slot;
+        let later = Hash::new_unique();
+        manager.set_blockhash(&fork_id, later).unwrap();
+        let entries = manager.slot_hashes(&fork_id, None).unwrap();
+        assert_eq!(
+            entries[..2],
+            [
+                SlotHashEntry {
+                    slot,
+                    hash: later.to_string(),
+                },
+                SlotHashEntry {
+                    slot: 0,
+                    hash: blockhash.to_string(),
+                },
+            ]
+        );
+        assert_eq!(manager.slot_hashes(&fork_id, Some(1)).unwrap().len(), 1);
+
+        // Transactions signed against it execute
+        let payer = Keypair::new();
+        manager
+            .set_lamports(&fork_id, payer.pubkey(), 1_000_000_000, false)
+            .unwrap();
+        let ix = system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1_000_000);
+        let tx =
+            Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer], blockhash);
+        assert!(manager.execute_transaction(&fork_id, tx.into()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_watch_account() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let pubkey = Pubkey::new_unique();
+
+        let mut watch = Box::pin(manager.watch_account(&fork_id, pubkey).unwrap());
+        assert!(watch.next().await.unwrap().is_none());
+
+        manager
+            .set_lamports(&fork_id, pubkey, 1_000_000, false)
+            .unwrap();
+        let account = watch.next().await.unwrap().unwrap();
+        assert_eq!(account.lamports, 1_000_000);
+
+        // Changes to other accounts don't produce an update
+        manager
+            .set_lamports(&fork_id, Pubkey::new_unique(), 5, false)
+            .unwrap();
+        manager
+            .set_lamports(&fork_id, pubkey, 2_000_000, false)
+            .unwrap();
+        let account = watch.next().await.unwrap().unwrap();
+        assert_eq!(account.lamports, 2_000_000);
+
+        // The stream ends once the fork is gone
+        manager.delete_fork(&fork_id);
+        assert!(watch.next().await.is_none());
+    }
+
+    #[test]
+    fn test_transaction_events() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let payer = Keypair::new();
+        manager
+            .set_lamports(&fork_id, payer.pubkey(), 1_000_000_000, false)
+            .unwrap();
+
+        let fork = manager.get_fork(&fork_id).unwrap();
+        let (missed, mut events) = fork.subscribe_events(None);
+        assert!(missed.is_empty());
+
+        for _ in 0..2 {
+            let tx = transfer_tx(&manager, &fork_id, &payer, &Pubkey::new_unique(), 1_000_000);
+            manager.execute_transaction(&fork_id, tx).unwrap();
+        }
+
+        let first = events.try_recv().unwrap();
+        assert_eq!(first.id, 1);
+        assert_eq!(first.kind, TransactionKind::Executed);
+        assert!(first.success);
+        assert!(first.compute_units_consumed > 0);
+        assert_eq!(events.try_recv().unwrap().id, 2);
+
+        // Reconnecting replays the events published after the last one seen
+        let (missed, _) = fork.subscribe_events(Some(1));
+        assert_eq!(missed.len(), 1);
+        assert_eq!(missed[0].id, 2);
+    }
+
+    #[tokio::test]
+    async fn test_account_change_events() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let payer = Keypair::new();
+        manager
+            .set_lamports(&fork_id, payer.pubkey(), 1_000_000_000, false)
+            .unwrap();
+        let fork = manager.get_fork(&fork_id).unwrap();
+
+        let mut plain = Box::pin(manager.transaction_events(&fork_id, None, false).unwrap());
+        let tx = transfer_tx(&manager, &fork_id, &payer, &Pubkey::new_unique(), 1_000_000);
+        manager.execute_transaction(&fork_id, tx).unwrap();
+        // Nothing is computed without a subscriber asking for changes
+        assert!(plain.next().await.unwrap().account_changes.is_none());
+        assert!(
+            fork.recent_events.lock_or_recover()[0]
+                .account_changes
+                .is_none()
         );
+
+        let mut verbose = Box::pin(manager.transaction_events(&fork_id, None, true).unwrap());
+        let recipient = Pubkey::new_unique();
+        let tx = transfer_tx(&manager, &fork_id, &payer, &recipient, 2_000_000);
+        manager.execute_transaction(&fork_id, tx).unwrap();
+        let changes = verbose.next().await.unwrap().account_changes.unwrap();
+        // The payer and the recipient, not the readonly system program
+        assert_eq!(changes.len(), 2);
+        let created = changes
+            .iter()
+            .find(|diff| diff.pubkey == recipient.to_string())
+            .unwrap();
+        assert_eq!(created.fields, vec!["exists"]);
+        assert_eq!(created.left_lamports, None);
+        assert_eq!(created.right_lamports, Some(2_000_000));
+        // Other subscribers don't receive them
+        assert!(plain.next().await.unwrap().account_changes.is_none());
+
+        drop(verbose);
+        assert_eq!(fork.account_change_subscribers.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_transaction_history() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let payer = Keypair::new();
+        manager
+            .set_lamports(&fork_id, payer.pubkey(), 1_000_000_000, false)
+            .unwrap();
+
+        let start = Utc::now();
+        for lamports in [1_000_000, 2_000_000, 3_000_000] {
+            let tx = transfer_tx(&manager, &fork_id, &payer, &Pubkey::new_unique(), lamports);
+            manager.execute_transaction(&fork_id, tx).unwrap();
+        }
+        // Transferring more than the balance fails
+        let tx = transfer_tx(&manager, &fork_id, &payer, &Pubkey::new_unique(), u64::MAX);
+        assert!(manager.execute_transaction(&fork_id, tx).is_err());
+
+        let all = manager
+            .get_executed_transactions(&fork_id, &TransactionHistoryQuery::default())
+            .unwrap();
+        assert_eq!(all.total, 4);
+        assert!(all.transactions.is_sorted_by_key(|record| record.time));
+
+        let page = manager
+            .get_executed_transactions(
+                &fork_id,
+                &TransactionHistoryQuery {
+                    success: Some(true),
+                    offset: 1,
+                    limit: Some(1),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(page.transactions.len(), 1);
         assert_eq!(
-            unpacked.mint,
-            pubkey::Pubkey::new_from_array(*mint.as_array())
+            page.transactions[0].txn.signature,
+            all.transactions[1].txn.signature
         );
-        assert_eq!(unpacked.amount, 1_000_000);
+
+        let failed = manager
+            .get_executed_transactions(
+                &fork_id,
+                &TransactionHistoryQuery {
+                    success: Some(false),
+                    since: Some(start),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(failed.total, 1);
+        assert!(!failed.transactions[0].success);
+
+        let future = manager
+            .get_executed_transactions(
+                &fork_id,
+                &TransactionHistoryQuery {
+                    since: Some(Utc::now() + chrono::Duration::hours(1)),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(future.total, 0);
     }
 
     #[test]
-    fn test_mainnet_fallback() {
+    fn test_log_limits() {
+        let mut manager = ForkManager::new().with_log_limits(LogLimits {
+            max_lines: 1,
+            ..Default::default()
+        });
+        let fork_id = offline_fork(&mut manager);
+        let payer = Keypair::new();
+        manager
+            .set_lamports(&fork_id, payer.pubkey(), 1_000_000_000, false)
+            .unwrap();
+        // Two transfers, logging two lines each
+        let ixs = [Pubkey::new_unique(), Pubkey::new_unique()]
+            .map(|to| system_instruction::transfer(&payer.pubkey(), &to, 1_000_000));
+        let tx = Transaction::new_signed_with_payer(
+            &ixs,
+            Some(&payer.pubkey()),
+            &[&payer],
+            latest_blockhash(&manager, &fork_id),
+        );
+        manager.execute_transaction(&fork_id, tx.into()).unwrap();
+
+        // Recorded logs are capped by the manager's limits
+        let page = manager
+            .get_executed_transactions(&fork_id, &TransactionHistoryQuery::default())
+            .unwrap();
+        let logs = &page.transactions[0].txn.logs;
+        assert_eq!(logs.len(), 2);
+        assert_eq!(logs[1], "...(3 lines truncated)");
+
+        // Queries can lower them further, still counting every dropped line
+        let page = manager
+            .get_executed_transactions(
+                &fork_id,
+                &TransactionHistoryQuery {
+                    log_max_lines: Some(0),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            page.transactions[0].txn.logs,
+            vec!["...(4 lines truncated)"]
+        );
+    }
+
+    #[test]
+    fn test_transaction_history_fork_not_found() {
+        let manager = ForkManager::new();
+        let query = TransactionHistoryQuery::default();
+        assert!(
+            manager
+                .get_executed_transactions(&Uuid::new_v4(), &query)
+                .is_err()
+        );
+        assert!(
+            manager
+                .get_simulated_transactions(&Uuid::new_v4(), &query)
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_import_accounts() {
         let mut manager = ForkManager::new();
-        let fork_id = manager.create_fork().expect("Failed to create fork");
+        let source = offline_fork(&mut manager);
+        let target = offline_fork(&mut manager);
+
+        let funded = Pubkey::new_unique();
+        manager
+            .set_lamports(&source, funded, 1_000_000, false)
+            .unwrap();
+        manager
+            .set_token_balance(
+                &source,
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                funded,
+                5,
+                TokenBalanceOptions::default(),
+            )
+            .unwrap();
+
+        let exported: Vec<_> = manager
+            .export_accounts_stream(&source)
+            .unwrap()
+            .collect()
+            .await;
+        // The funded account, the token account and its mint
+        assert_eq!(exported.len(), 3);
+
+        assert_eq!(manager.import_accounts(&target, exported).unwrap(), 3);
+        assert_eq!(
+            manager.get_account(&target, funded).unwrap().lamports,
+            1_000_000
+        );
+        assert_eq!(manager.export_accounts(&target).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_send_transaction() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let payer = Keypair::new();
+        let recipient = Pubkey::new_unique();
+        manager
+            .set_lamports(&fork_id, payer.pubkey(), 1_000_000_000, false)
+            .unwrap();
+
+        let tx = transfer_tx(&manager, &fork_id, &payer, &recipient, 1_000_000);
+        let result = manager.send_transaction(&fork_id, tx).unwrap();
+        assert!(result.error.is_none());
+        assert!(result.execution.is_some());
+        assert_eq!(
+            manager.get_account(&fork_id, recipient).unwrap().lamports,
+            1_000_000
+        );
+
+        // The simulation fails, so nothing is executed
+        let tx = transfer_tx(&manager, &fork_id, &payer, &recipient, 10_000_000_000);
+        let balance = manager
+            .get_account(&fork_id, payer.pubkey())
+            .unwrap()
+            .lamports;
+        let result = manager.send_transaction(&fork_id, tx).unwrap();
+        assert!(result.error.is_some());
+        assert!(result.execution.is_none());
+        assert_eq!(
+            manager
+                .get_account(&fork_id, payer.pubkey())
+                .unwrap()
+                .lamports,
+            balance
+        );
+        let executed = manager
+            .get_executed_transactions(&fork_id, &TransactionHistoryQuery::default())
+            .unwrap();
+        assert_eq!(executed.total, 1);
+
+        assert!(
+            manager
+                .send_transaction(
+                    &Uuid::new_v4(),
+                    transfer_tx(&manager, &fork_id, &payer, &recipient, 1)
+                )
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_get_transaction() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let payer = Keypair::new();
+        manager
+            .set_lamports(&fork_id, payer.pubkey(), 1_000_000_000, false)
+            .unwrap();
+
+        let executed = transfer_tx(&manager, &fork_id, &payer, &Pubkey::new_unique(), 1_000_000);
+        let simulated = transfer_tx(&manager, &fork_id, &payer, &Pubkey::new_unique(), 2_000_000);
+        manager
+            .execute_transaction(&fork_id, executed.clone())
+            .unwrap();
+        manager
+            .simulate_transaction(&fork_id, simulated.clone())
+            .unwrap();
+
+        let record = manager
+            .get_transaction(&fork_id, &executed.signatures[0], false)
+            .unwrap()
+            .unwrap();
+        assert!(record.success);
+        assert_eq!(record.txn.signature, executed.signatures[0]);
+        let tx_bytes = engine::general_purpose::STANDARD
+            .decode(record.tx_base64.unwrap())
+            .unwrap();
+        let decoded: VersionedTransaction = bincode::deserialize(&tx_bytes).unwrap();
+        assert_eq!(decoded, executed);
+
+        // History pages only include transactions when asked to
+        let page = manager
+            .get_executed_transactions(&fork_id, &TransactionHistoryQuery::default())
+            .unwrap();
+        assert!(page.transactions[0].tx_base64.is_none());
+        let query = TransactionHistoryQuery {
+            include_tx: true,
+            ..Default::default()
+        };
+        let page = manager.get_executed_transactions(&fork_id, &query).unwrap();
+        assert!(page.transactions[0].tx_base64.is_some());
+
+        let signature = simulated.signatures[0];
+        assert!(
+            manager
+                .get_transaction(&fork_id, &signature, false)
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            manager
+                .get_transaction(&fork_id, &signature, true)
+                .unwrap()
+                .is_some()
+        );
+        assert!(
+            manager
+                .get_transaction(&Uuid::new_v4(), &signature, true)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_get_stats() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let payer = Keypair::new();
+        manager
+            .set_lamports(&fork_id, payer.pubkey(), 1_000_000_000, false)
+            .unwrap();
+
+        let stats = manager.get_stats(&fork_id).unwrap();
+        assert_eq!(stats.accounts, 1);
+        assert_eq!(stats.total_lamports, 1_000_000_000);
+
+        let recipient = Pubkey::new_unique();
+        let tx = transfer_tx(&manager, &fork_id, &payer, &recipient, 1_000_000);
+        manager.simulate_transaction(&fork_id, tx.clone()).unwrap();
+        manager.execute_transaction(&fork_id, tx).unwrap();
+
+        let stats = manager.get_stats(&fork_id).unwrap();
+        // The payer, the recipient and the system program
+        assert_eq!(stats.accounts, 3);
+        assert_eq!(stats.executed_transactions, 1);
+        assert_eq!(stats.simulated_transactions, 1);
+        // Offline forks never contact mainnet
+        assert_eq!(stats.rpc_requests, 0);
+        assert!(manager.get_stats(&Uuid::new_v4()).is_err());
+    }
+
+    #[test]
+    fn test_set_rent() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+
+        manager.set_rent(&fork_id, 1_000, 2.0, 50).unwrap();
+
+        let fork = manager.get_fork(&fork_id).unwrap();
+        let svm = fork.svm.read_or_recover();
+        let rent = svm.get_sysvar::<Rent>();
+        assert_eq!(rent.lamports_per_byte_year, 1_000);
+        assert_eq!(rent.burn_percent, 50);
+        // (128 bytes of account metadata + 100 bytes of data) * 1000 * 2.0
+        assert_eq!(svm.minimum_balance_for_rent_exemption(100), 456_000);
+
+        assert!(manager.set_rent(&fork_id, 1_000, 2.0, 101).is_err());
+    }
+
+    #[test]
+    fn test_set_epoch() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+
+        let info = manager.set_epoch(&fork_id, 12).unwrap();
+        let fork = manager.get_fork(&fork_id).unwrap();
+        let (clock, schedule) = {
+            let svm = fork.svm.read_or_recover();
+            (svm.get_sysvar::<Clock>(), svm.get_sysvar::<EpochSchedule>())
+        };
+        assert_eq!(clock.epoch, 12);
+        assert_eq!(clock.slot, info.slot);
+        assert_eq!(schedule.get_epoch(clock.slot), 12);
+        assert_eq!(
+            clock.leader_schedule_epoch,
+            schedule.get_leader_schedule_epoch(clock.slot)
+        );
+        assert!((info.first_slot..=info.last_slot).contains(&info.slot));
+
+        // The slot is kept when it already lies within the epoch
+        assert_eq!(manager.set_epoch(&fork_id, 12).unwrap(), info);
+        assert!(manager.set_epoch(&fork_id, u64::MAX).is_err());
+    }
+
+    #[test]
+    fn test_deterministic_blockhash() {
+        let mut manager = ForkManager::new();
+        let options = ForkOptions {
+            offline: true,
+            deterministic_blockhash: true,
+            ..Default::default()
+        };
+        let first = manager.create_fork_with_options(options.clone()).unwrap();
+        let second = manager.create_fork_with_options(options).unwrap();
+        let genesis = manager.get_blockhash(&first).unwrap().0;
+        assert_eq!(manager.get_blockhash(&second).unwrap().0, genesis);
+
+        // Both advance to the same blockhash when warping
+        manager.set_epoch(&first, 3).unwrap();
+        manager.set_epoch(&second, 3).unwrap();
+        let warped = manager.get_blockhash(&first).unwrap();
+        assert_ne!(warped.0, genesis);
+        assert_eq!(manager.get_blockhash(&second).unwrap(), warped);
+        assert_eq!(
+            manager.slot_hashes(&first, Some(1)).unwrap(),
+            manager.slot_hashes(&second, Some(1)).unwrap()
+        );
+
+        // A blockhash set by the user is replaced by the next warp's one
+        manager.set_blockhash(&first, Hash::new_unique()).unwrap();
+        manager.set_epoch(&first, 5).unwrap();
+        manager.set_epoch(&second, 5).unwrap();
+        assert_eq!(
+            manager.get_blockhash(&first).unwrap(),
+            manager.get_blockhash(&second).unwrap()
+        );
+
+        // The live blockhash isn't fetched when refreshing sysvars
+        let rpc = MainnetRpc::mock(Default::default());
+        let mut svms = [LiteSVM::new(), LiteSVM::new()];
+        for svm in &mut svms {
+            assert!(update_sysvars(svm, &rpc, true).slot_hashes);
+        }
+        assert_eq!(rpc.requests(), 4);
+        let [a, b] = svms.map(|svm| svm.get_sysvar::<SlotHashes>());
+        assert_eq!(a.first(), b.first());
+        assert_eq!(a.first().unwrap().1, LiteSVM::new().latest_blockhash());
+    }
+
+    #[test]
+    fn test_minimum_balance() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+
+        assert_eq!(
+            manager.minimum_balance(&fork_id, 0).unwrap(),
+            Rent::default().minimum_balance(0)
+        );
+
+        manager.set_rent(&fork_id, 1_000, 2.0, 50).unwrap();
+        assert_eq!(manager.minimum_balance(&fork_id, 100).unwrap(), 456_000);
+        assert!(manager.minimum_balance(&Uuid::new_v4(), 0).is_err());
+    }
+
+    #[test]
+    fn test_artificial_delay() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        assert_eq!(manager.artificial_delay(&fork_id), Duration::ZERO);
+
+        let options: ForkOptions = serde_json::from_value(serde_json::json!({
+            "offline": true,
+            "artificial_delay_ms": 250,
+        }))
+        .unwrap();
+        let delayed = manager.create_fork_with_options(options).unwrap();
+        assert_eq!(
+            manager.artificial_delay(&delayed),
+            Duration::from_millis(250)
+        );
+        assert_eq!(manager.artificial_delay(&Uuid::new_v4()), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_spl_minimum_balances() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let rent = Rent::default();
+        assert_eq!(
+            manager
+                .minimum_balance(&fork_id, TokenAccount::LEN)
+                .unwrap(),
+            rent.minimum_balance(TokenAccount::LEN)
+        );
+        assert_eq!(
+            manager.minimum_balance(&fork_id, Mint::LEN).unwrap(),
+            rent.minimum_balance(Mint::LEN)
+        );
+
+        // Both follow the fork's rent, (128 + len) bytes * 1_000 * 2 years
+        manager.set_rent(&fork_id, 1_000, 2.0, 50).unwrap();
+        assert_eq!(
+            manager
+                .minimum_balance(&fork_id, TokenAccount::LEN)
+                .unwrap(),
+            586_000
+        );
+        assert_eq!(
+            manager.minimum_balance(&fork_id, Mint::LEN).unwrap(),
+            420_000
+        );
+    }
+
+    #[test]
+    fn test_preload_accounts() {
+        let address = Pubkey::new_unique();
+        let missing = Pubkey::new_unique();
+        let mainnet_account = serde_json::json!({
+            "lamports": 1_000_000u64,
+            "data": ["AQID", "base64"],
+            "owner": "11111111111111111111111111111111",
+            "executable": false,
+            "rentEpoch": 0,
+            "space": 3,
+        });
+        let rpc = |accounts: serde_json::Value| {
+            MainnetRpc::mock(
+                [(
+                    RpcRequest::GetMultipleAccounts,
+                    serde_json::json!({ "context": { "slot": 1 }, "value": accounts }),
+                )]
+                .into(),
+            )
+        };
+
+        let mut svm = LiteSVM::new();
+        let preloaded = preload_accounts(
+            &mut svm,
+            &rpc(serde_json::json!([mainnet_account])),
+            &[address],
+        )
+        .unwrap();
+        assert_eq!(preloaded, vec![address]);
+        let account = svm.get_account(&address).unwrap();
+        assert_eq!(account.lamports, 1_000_000);
+        assert_eq!(account.data, vec![1, 2, 3]);
+
+        let err = preload_accounts(
+            &mut LiteSVM::new(),
+            &rpc(serde_json::json!([mainnet_account, null])),
+            &[address, missing],
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains(&missing.to_string()));
+    }
+
+    #[test]
+    fn test_snapshot_to_file() {
+        let mut manager = ForkManager::new();
+        let fork_id = offline_fork(&mut manager);
+        let pubkey = Pubkey::new_unique();
+        manager
+            .set_lamports(&fork_id, pubkey, 1_000_000, false)
+            .unwrap();
+
+        let path = std::env::temp_dir().join(format!("snapshot-{}.json", fork_id));
+        assert_eq!(manager.snapshot_to_file(&path).unwrap(), 1);
+
+        let file = std::fs::File::open(&path).unwrap();
+        let snapshot: HashMap<String, Vec<ExportedAccount>> =
+            serde_json::from_reader(file).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let accounts = &snapshot[&fork_id.to_string()];
+        assert_eq!(accounts.len(), 1);
+        let (key, account) = accounts[0].to_account().unwrap();
+        assert_eq!(key, pubkey);
+        assert_eq!(account.lamports, 1_000_000);
+    }
+
+    #[test]
+    fn test_fixture() {
+        let pubkey = Pubkey::new_unique();
+        let fixture = vec![ExportedAccount::new(
+            &pubkey,
+            &Account {
+                lamports: 1_000_000,
+                data: vec![1, 2, 3],
+                owner: Pubkey::new_unique(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        )];
+        let path = std::env::temp_dir().join(format!("fixture-{}.json", pubkey));
+        std::fs::write(&path, serde_json::to_vec(&fixture).unwrap()).unwrap();
+        let accounts = ForkManager::load_fixture(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
 
-        // A well-known system account (system program)
-        let address = Pubkey::from_str_const("7nZrcnwtxqGeSsYgyaTZrwrwDFEe39CVwxcGgZhBjgLa");
+        let mut manager = ForkManager::new().with_fixture(accounts);
+        for _ in 0..2 {
+            let fork_id = offline_fork(&mut manager);
+            let account = manager.get_local_account(&fork_id, pubkey).unwrap();
+            assert_eq!(account.lamports, 1_000_000);
+            assert_eq!(account.data, vec![1, 2, 3]);
+            assert_eq!(manager.get_stats(&fork_id).unwrap().accounts, 1);
+        }
+    }
+
+    #[test]
+    fn test_mainnet_fallback() {
+        let mainnet_account = serde_json::json!({
+            "context": { "slot": 1 },
+            "value": {
+                "lamports": 1_000_000u64,
+                "data": ["", "base64"],
+                "owner": "Stake11111111111111111111111111111111111111",
+                "executable": false,
+                "rentEpoch": 0,
+                "space": 0,
+            },
+        });
+        let mut manager = ForkManager::new();
+        let fork_id = Uuid::new_v4();
+        let fork = Fork::new(
+            Arc::new(RwLock::new(LiteSVM::new())),
+            ForkOptions::default(),
+            FeatureSet::all_enabled(),
+            Some(MainnetRpc::mock(
+                [(RpcRequest::GetAccountInfo, mainnet_account)].into(),
+            )),
+        );
+        manager.insert_fork(fork_id, fork).unwrap();
+        let address = Pubkey::new_unique();
 
         // Should fetch from mainnet and cache
         let acc = manager.get_account(&fork_id, address).unwrap();
         assert!(acc.owner != Pubkey::default());
+        assert_eq!(acc.lamports, 1_000_000);
 
         // Should now be cached locally
         let acc2 = manager.get_account(&fork_id, address).unwrap();
         assert_eq!(acc.lamports, acc2.lamports);
+        assert_eq!(manager.get_stats(&fork_id).unwrap().rpc_requests, 1);
     }
 }
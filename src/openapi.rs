@@ -0,0 +1,1195 @@
+//! OpenAPI 3 description of the HTTP API, served at `GET /openapi.json`.
+//!
+//! The document is written by hand from the route table below and the
+//! request/response structs, keep both in sync when changing a route.
+
+use serde_json::{Map, Value, json};
+
+/// What a route answers with
+enum Data {
+    /// [`ApiResponse`](simulation_engine::types::ApiResponse) whose `data`
+    /// is the named schema
+    Schema(&'static str),
+    /// `ApiResponse` whose `data` is a JSON type (`string`, `boolean`, ...)
+    Type(&'static str),
+    /// `ApiResponse` whose `data` is an array of the named schema
+    Array(&'static str),
+    /// Anything else, with its content type
+    Raw(&'static str),
+    /// A WebSocket upgrade
+    Upgrade,
+}
+
+/// A query parameter: name, JSON type and description
+type Param = (&'static str, &'static str, &'static str);
+
+struct Route {
+    method: &'static str,
+    path: &'static str,
+    summary: &'static str,
+    /// Schema of the JSON body, if any
+    body: Option<&'static str>,
+    query: &'static [Param],
+    data: Data,
+}
+
+/// Routes whose body may be empty
+const OPTIONAL_BODIES: &[&str] = &["/forks", "/forks/{id}/replay_history"];
+
+const LOCAL_ONLY: Param = (
+    "local_only",
+    "boolean",
+    "Only read the fork's state, never fetching from mainnet",
+);
+const ENCODING: Param = ("encoding", "string", "`base64` (default) or `jsonParsed`");
+
+const ROUTES: &[Route] = &[
+    Route {
+        method: "get",
+        path: "/health",
+        summary: "Number of active forks and outcome of the latest cleanup",
+        body: None,
+        query: &[],
+        data: Data::Schema("HealthResponse"),
+    },
+    Route {
+        method: "get",
+        path: "/openapi.json",
+        summary: "This document",
+        body: None,
+        query: &[],
+        data: Data::Raw("application/json"),
+    },
+    Route {
+        method: "get",
+        path: "/forks",
+        summary: "List live forks",
+        body: None,
+        query: &[(
+            "label",
+            "string",
+            "`key` or `key=value`, only listing the forks with that label",
+        )],
+        data: Data::Array("ForkSummary"),
+    },
+    Route {
+        method: "post",
+        path: "/forks",
+        summary: "Create a fork, with default options when the body is empty",
+        body: Some("ForkOptions"),
+        query: &[],
+        data: Data::Type("string"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/diff",
+        summary: "List the accounts differing between two forks",
+        body: Some("DiffForksRequest"),
+        query: &[],
+        data: Data::Schema("ForkDiff"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/delete_batch",
+        summary: "Delete a batch of forks",
+        body: Some("ForkIds"),
+        query: &[],
+        data: Data::Schema("BatchDeleteResult"),
+    },
+    Route {
+        method: "delete",
+        path: "/forks/{id}",
+        summary: "Delete a fork",
+        body: None,
+        query: &[],
+        data: Data::Type("string"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/execute",
+        summary: "Execute a transaction",
+        body: Some("ExecuteRequest"),
+        query: &[],
+        data: Data::Schema("TransactionMetadata"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/send",
+        summary: "Simulate a transaction, then execute it if the simulation succeeded",
+        body: Some("ExecuteRequest"),
+        query: &[],
+        data: Data::Schema("SendResult"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/simulate",
+        summary: "Simulate a transaction",
+        body: Some("SimulateRequest"),
+        query: &[],
+        data: Data::Schema("TransactionMetadata"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/simulate_and_match",
+        summary: "Simulate a transaction and match its logs against a pattern",
+        body: Some("SimulateMatchRequest"),
+        query: &[],
+        data: Data::Schema("LogMatch"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/simulate_compute",
+        summary: "Simulate a transaction and only report whether it fits in its compute budget",
+        body: Some("ComputeFitRequest"),
+        query: &[],
+        data: Data::Schema("ComputeFit"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/estimate_fee",
+        summary: "Compute the fee of a transaction without executing it",
+        body: Some("TransactionRequest"),
+        query: &[],
+        data: Data::Schema("FeeEstimate"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/decode",
+        summary: "Break a transaction down without executing it",
+        body: Some("TransactionRequest"),
+        query: &[],
+        data: Data::Type("object"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/touched_accounts",
+        summary: "List the accounts a transaction reads and writes, resolving its lookup tables",
+        body: Some("TransactionRequest"),
+        query: &[],
+        data: Data::Array("TouchedAccount"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/set_lamports",
+        summary: "Set the SOL balance of an account",
+        body: Some("SetLamportsRequest"),
+        query: &[],
+        data: Data::Type("string"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/set_token_balance",
+        summary: "Set the balance of a token account",
+        body: Some("SetTokenBalanceRequest"),
+        query: &[],
+        data: Data::Type("string"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/set_token_balances",
+        summary: "Set the balances of many token accounts at once",
+        body: Some("TokenBalanceEntries"),
+        query: &[],
+        data: Data::Type("array"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/setup",
+        summary: "Apply balances, accounts and programs in one call",
+        body: Some("ForkSetup"),
+        query: &[],
+        data: Data::Type("object"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/freeze_token",
+        summary: "Freeze or thaw a token account",
+        body: Some("FreezeTokenRequest"),
+        query: &[],
+        data: Data::Type("string"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/set_token_delegate",
+        summary: "Set the delegate of a token account",
+        body: Some("SetTokenDelegateRequest"),
+        query: &[],
+        data: Data::Type("string"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/create_mint",
+        summary: "Create an SPL mint, returning its address",
+        body: Some("CreateMintRequest"),
+        query: &[],
+        data: Data::Type("string"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/create_lookup_table",
+        summary: "Create an address lookup table, returning its address",
+        body: Some("CreateLookupTableRequest"),
+        query: &[],
+        data: Data::Type("string"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/extend_lookup_table",
+        summary: "Append addresses to a lookup table, returning all of its addresses",
+        body: Some("ExtendLookupTableRequest"),
+        query: &[],
+        data: Data::Type("array"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/find_pda",
+        summary: "Derive a program address",
+        body: Some("FindPdaRequest"),
+        query: &[],
+        data: Data::Schema("ProgramAddress"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/associated_token_address",
+        summary: "Derive the associated token account of an owner for a mint",
+        body: Some("AssociatedTokenAddressRequest"),
+        query: &[],
+        data: Data::Schema("ProgramAddress"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/set_owner",
+        summary: "Reassign an account to another program",
+        body: Some("SetOwnerRequest"),
+        query: &[],
+        data: Data::Type("string"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/set_executable",
+        summary: "Mark an account executable or not",
+        body: Some("SetExecutableRequest"),
+        query: &[],
+        data: Data::Type("string"),
+    },
+    Route {
+        method: "get",
+        path: "/forks/{id}/blockhash",
+        summary: "Latest blockhash and slot of the fork",
+        body: None,
+        query: &[],
+        data: Data::Schema("BlockhashResponse"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/set_blockhash",
+        summary: "Set the recent blockhash of the fork",
+        body: Some("SetBlockhashRequest"),
+        query: &[],
+        data: Data::Type("string"),
+    },
+    Route {
+        method: "get",
+        path: "/forks/{id}/stats",
+        summary: "Accounts, lamports, transactions and RPC requests of the fork",
+        body: None,
+        query: &[],
+        data: Data::Type("object"),
+    },
+    Route {
+        method: "get",
+        path: "/forks/{id}/fees",
+        summary: "Fee parameters of the fork",
+        body: None,
+        query: &[],
+        data: Data::Schema("FeeSchedule"),
+    },
+    Route {
+        method: "get",
+        path: "/forks/{id}/features",
+        summary: "Runtime features active in the fork",
+        body: None,
+        query: &[],
+        data: Data::Type("array"),
+    },
+    Route {
+        method: "get",
+        path: "/forks/{id}/export",
+        summary: "Every account materialized in the fork, as JSON or NDJSON",
+        body: None,
+        query: &[],
+        data: Data::Raw("application/json"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/import",
+        summary: "Load accounts produced by `export`",
+        body: Some("ExportedAccounts"),
+        query: &[],
+        data: Data::Type("integer"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/replay_history",
+        summary: "Re-execute the fork's transactions on a new fork",
+        body: Some("ReplayHistoryRequest"),
+        query: &[],
+        data: Data::Schema("ReplayResult"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/rent",
+        summary: "Override the Rent sysvar",
+        body: Some("SetRentRequest"),
+        query: &[],
+        data: Data::Type("string"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/set_epoch",
+        summary: "Move the Clock sysvar to an epoch",
+        body: Some("SetEpochRequest"),
+        query: &[],
+        data: Data::Schema("EpochInfo"),
+    },
+    Route {
+        method: "get",
+        path: "/forks/{id}/slot_hashes",
+        summary: "Most recent entries of the SlotHashes sysvar, newest first",
+        body: None,
+        query: &[(
+            "limit",
+            "integer",
+            "Maximum number of entries returned, 32 by default",
+        )],
+        data: Data::Array("SlotHashEntry"),
+    },
+    Route {
+        method: "get",
+        path: "/forks/{id}/minimum_balance",
+        summary: "Rent-exempt minimum balance of an account",
+        body: None,
+        query: &[("data_len", "integer", "Size of the account data")],
+        data: Data::Type("integer"),
+    },
+    Route {
+        method: "get",
+        path: "/forks/{id}/minimum_balance/token_account",
+        summary: "Rent-exempt minimum balance of an SPL token account",
+        body: None,
+        query: &[],
+        data: Data::Type("integer"),
+    },
+    Route {
+        method: "get",
+        path: "/forks/{id}/minimum_balance/mint",
+        summary: "Rent-exempt minimum balance of an SPL mint",
+        body: None,
+        query: &[],
+        data: Data::Type("integer"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/get_account",
+        summary: "Current state of an account",
+        body: Some("GetAccountRequest"),
+        query: &[ENCODING, LOCAL_ONLY],
+        data: Data::Schema("UiAccount"),
+    },
+    Route {
+        method: "get",
+        path: "/forks/{id}/accounts/{pubkey}/exists",
+        summary: "Whether an account exists",
+        body: None,
+        query: &[LOCAL_ONLY],
+        data: Data::Type("boolean"),
+    },
+    Route {
+        method: "get",
+        path: "/forks/{id}/accounts/{pubkey}/token_balance",
+        summary: "Balance of a token account",
+        body: None,
+        query: &[],
+        data: Data::Schema("TokenBalance"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/rpc",
+        summary: "Subset of the Solana JSON-RPC API",
+        body: None,
+        query: &[],
+        data: Data::Raw("application/json"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/program_accounts",
+        summary: "Accounts of the fork owned by a program",
+        body: Some("ProgramAccountsRequest"),
+        query: &[],
+        data: Data::Type("array"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/clone_program_accounts",
+        summary: "Clone a program, its ProgramData and the accounts it owns from mainnet",
+        body: Some("CloneProgramAccountsRequest"),
+        query: &[],
+        data: Data::Schema("ClonedProgram"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/get_executed_transactions",
+        summary: "Page of the executed transactions",
+        body: Some("TransactionHistoryQuery"),
+        query: &[],
+        data: Data::Schema("TransactionPage"),
+    },
+    Route {
+        method: "post",
+        path: "/forks/{id}/get_simulated_transactions",
+        summary: "Page of the simulated transactions",
+        body: Some("TransactionHistoryQuery"),
+        query: &[],
+        data: Data::Schema("TransactionPage"),
+    },
+    Route {
+        method: "get",
+        path: "/forks/{id}/transactions/{signature}",
+        summary: "Look a transaction up by signature",
+        body: None,
+        query: &[(
+            "simulated",
+            "boolean",
+            "Also search the simulated transactions",
+        )],
+        data: Data::Schema("TransactionRecord"),
+    },
+    Route {
+        method: "get",
+        path: "/forks/{id}/accounts/{pubkey}/watch",
+        summary: "Stream the changes of an account as Server-Sent Events",
+        body: None,
+        query: &[ENCODING],
+        data: Data::Raw("text/event-stream"),
+    },
+    Route {
+        method: "get",
+        path: "/forks/{id}/events",
+        summary: "Stream executed and simulated transactions as Server-Sent Events",
+        body: None,
+        query: &[(
+            "account_changes",
+            "boolean",
+            "Include the accounts each transaction changed in its event",
+        )],
+        data: Data::Raw("text/event-stream"),
+    },
+    Route {
+        method: "get",
+        path: "/forks/{id}/ws",
+        summary: "WebSocket serving the `rpc` methods and `sendTransaction`",
+        body: None,
+        query: &[],
+        data: Data::Upgrade,
+    },
+];
+
+/// Builds the OpenAPI document of the API
+pub fn document() -> Value {
+    let mut paths = Map::new();
+    for route in ROUTES {
+        let operations = paths
+            .entry(route.path)
+            .or_insert_with(|| json!({}))
+            .as_object_mut()
+            .unwrap();
+        operations.insert(route.method.to_string(), operation(route));
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Solana Fork Simulation Engine",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": { "schemas": schemas() },
+    })
+}
+
+fn operation(route: &Route) -> Value {
+    let mut parameters: Vec<Value> = route
+        .path
+        .split('/')
+        .filter_map(|segment| segment.strip_prefix('{')?.strip_suffix('}'))
+        .map(|name| {
+            let schema = if name == "id" {
+                json!({ "type": "string", "format": "uuid" })
+            } else {
+                json!({ "type": "string" })
+            };
+            json!({ "name": name, "in": "path", "required": true, "schema": schema })
+        })
+        .collect();
+    parameters.extend(route.query.iter().map(|(name, ty, description)| {
+        json!({
+            "name": name,
+            "in": "query",
+            "required": false,
+            "description": description,
+            "schema": { "type": ty },
+        })
+    }));
+
+    let data = match route.data {
+        Data::Schema(name) => Some(reference(name)),
+        Data::Type(ty) => Some(json!({ "type": ty })),
+        Data::Array(name) => Some(json!({ "type": "array", "items": reference(name) })),
+        Data::Raw(_) | Data::Upgrade => None,
+    };
+    let responses = match (&route.data, data) {
+        (_, Some(data)) => json!({
+            "200": {
+                "description": "`success` tells whether the call succeeded, with its `data` or `error`",
+                "content": { "application/json": { "schema": {
+                    "allOf": [
+                        reference("ApiResponse"),
+                        { "type": "object", "properties": { "data": data } },
+                    ],
+                } } },
+            },
+        }),
+        (Data::Raw(content_type), None) => {
+            let mut content = Map::new();
+            content.insert(content_type.to_string(), json!({}));
+            json!({ "200": { "description": "OK", "content": content } })
+        }
+        _ => json!({ "101": { "description": "Switching to the WebSocket protocol" } }),
+    };
+
+    let mut operation = json!({
+        "summary": route.summary,
+        "parameters": parameters,
+        "responses": responses,
+    });
+    if let Some(body) = route.body {
+        operation["requestBody"] = json!({
+            "required": !OPTIONAL_BODIES.contains(&route.path),
+            "content": { "application/json": { "schema": reference(body) } },
+        });
+    }
+    operation
+}
+
+fn reference(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+/// Schemas of the request and response bodies
+fn schemas() -> Value {
+    let pubkey = json!({ "type": "string", "description": "Base58 encoded address" });
+    let tx = json!({ "type": "string", "description": "Base64 encoded, bincode serialized transaction" });
+    let unsigned = json!({ "type": "integer", "format": "int64", "minimum": 0 });
+    let optional_unsigned =
+        json!({ "type": "integer", "format": "int64", "minimum": 0, "nullable": true });
+    let rpc_url = json!({
+        "type": "string",
+        "nullable": true,
+        "description": "Mainnet RPC endpoint fetching missing accounts instead of the fork's, ignored if invalid. Rejected unless in the server's RPC_URL_ALLOWLIST",
+    });
+    let boolean = json!({ "type": "boolean" });
+    let string = json!({ "type": "string" });
+    let strings = json!({ "type": "array", "items": { "type": "string" } });
+
+    json!({
+        "ApiResponse": {
+            "type": "object",
+            "required": ["success"],
+            "properties": {
+                "success": boolean,
+                "data": {},
+                "error": { "type": "string", "nullable": true },
+                "expires_at": {
+                    "type": "string",
+                    "format": "date-time",
+                    "description": "When the fork expires, on routes mutating a fork",
+                },
+                "failure": {
+                    "type": "object",
+                    "description": "Decoded error of a failed transaction",
+                    "properties": {
+                        "error": {},
+                        "instruction_index": { "type": "integer", "nullable": true },
+                        "program_id": { "type": "string", "nullable": true },
+                        "custom_code": { "type": "integer", "nullable": true },
+                        "custom_name": { "type": "string", "nullable": true },
+                    },
+                },
+            },
+        },
+        "ForkOptions": {
+            "type": "object",
+            "properties": {
+                "offline": boolean,
+                "disabled_features": strings,
+                "preload": strings,
+                "slot": optional_unsigned,
+                "commitment": { "type": "string", "enum": ["processed", "confirmed", "finalized"] },
+                "validation": {
+                    "type": "object",
+                    "properties": {
+                        "sigverify": boolean,
+                        "fee_check": boolean,
+                        "blockhash_check": boolean,
+                    },
+                },
+                "fund": { "type": "array", "items": reference("LamportsSetup") },
+                "name": { "type": "string", "nullable": true },
+                "labels": { "type": "object", "additionalProperties": { "type": "string" } },
+                "history_capacity": optional_unsigned,
+                "artificial_delay_ms": unsigned,
+                "token_rent_source": {
+                    "description": "`minted`, `owner` or `{ \"faucet\": pubkey }`",
+                },
+                "deterministic_blockhash": boolean,
+            },
+        },
+        "LamportsSetup": {
+            "type": "object",
+            "required": ["pubkey", "lamports"],
+            "properties": {
+                "pubkey": pubkey,
+                "lamports": unsigned,
+                "allow_rent_paying": boolean,
+            },
+        },
+        "SlotHashEntry": {
+            "type": "object",
+            "properties": { "slot": unsigned, "hash": string },
+        },
+        "TokenBalance": {
+            "type": "object",
+            "properties": {
+                "mint": pubkey,
+                "owner": pubkey,
+                "amount": unsigned,
+                "frozen": boolean,
+            },
+        },
+        "TouchedAccount": {
+            "type": "object",
+            "properties": {
+                "pubkey": pubkey,
+                "signer": boolean,
+                "writable": boolean,
+                "lookup_table": {
+                    "type": "string",
+                    "nullable": true,
+                    "description": "Lookup table the address was loaded from, null for static keys",
+                },
+            },
+        },
+        "TransactionRequest": {
+            "type": "object",
+            "required": ["tx_base64"],
+            "properties": { "tx_base64": tx },
+        },
+        "ExecuteRequest": {
+            "type": "object",
+            "required": ["tx_base64"],
+            "properties": {
+                "tx_base64": tx,
+                "timeout_ms": optional_unsigned,
+                "log_max_lines": optional_unsigned,
+                "log_max_bytes": optional_unsigned,
+                "rpc_url": rpc_url,
+                "compute_units": boolean,
+            },
+        },
+        "SimulateRequest": {
+            "type": "object",
+            "required": ["tx_base64"],
+            "properties": {
+                "tx_base64": tx,
+                "trace": boolean,
+                "compute_units": boolean,
+                "refresh_accounts": boolean,
+                "allow_unsigned": boolean,
+                "log_max_lines": optional_unsigned,
+                "log_max_bytes": optional_unsigned,
+                "rpc_url": rpc_url,
+                "impersonate": strings,
+            },
+        },
+        "SimulateMatchRequest": {
+            "type": "object",
+            "required": ["tx_base64", "pattern"],
+            "properties": { "tx_base64": tx, "pattern": string, "regex": boolean },
+        },
+        "SetLamportsRequest": { "$ref": "#/components/schemas/LamportsSetup" },
+        "SetTokenBalanceRequest": {
+            "type": "object",
+            "required": ["token_account", "mint", "owner", "amount"],
+            "properties": {
+                "token_account": pubkey,
+                "mint": pubkey,
+                "owner": pubkey,
+                "amount": unsigned,
+                "decimals": { "type": "integer", "nullable": true },
+                "check_supply": boolean,
+                "lamports": optional_unsigned,
+            },
+        },
+        "TokenBalanceEntries": {
+            "type": "array",
+            "items": {
+                "type": "object",
+                "required": ["mint", "owner", "amount"],
+                "properties": {
+                    "token_account": { "type": "string", "nullable": true, "description": "The owner's associated token account when omitted" },
+                    "mint": pubkey,
+                    "owner": pubkey,
+                    "amount": unsigned,
+                    "token_program": { "type": "string", "nullable": true },
+                },
+            },
+        },
+        "ForkSetup": {
+            "type": "object",
+            "properties": {
+                "lamports": { "type": "array", "items": reference("LamportsSetup") },
+                "token_balances": { "type": "array", "items": reference("SetTokenBalanceRequest") },
+                "accounts": reference("ExportedAccounts"),
+                "programs": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "required": ["program_id", "program_base64"],
+                        "properties": { "program_id": pubkey, "program_base64": string },
+                    },
+                },
+            },
+        },
+        "ExportedAccounts": {
+            "type": "array",
+            "items": {
+                "type": "object",
+                "required": ["pubkey", "lamports", "owner", "data_base64", "executable", "rent_epoch"],
+                "properties": {
+                    "pubkey": pubkey,
+                    "lamports": unsigned,
+                    "owner": pubkey,
+                    "data_base64": string,
+                    "executable": boolean,
+                    "rent_epoch": unsigned,
+                },
+            },
+        },
+        "FreezeTokenRequest": {
+            "type": "object",
+            "required": ["token_account", "frozen"],
+            "properties": { "token_account": pubkey, "frozen": boolean },
+        },
+        "SetTokenDelegateRequest": {
+            "type": "object",
+            "required": ["token_account", "delegate", "amount"],
+            "properties": { "token_account": pubkey, "delegate": pubkey, "amount": unsigned },
+        },
+        "CreateLookupTableRequest": {
+            "type": "object",
+            "properties": {
+                "address": pubkey,
+                "authority": pubkey,
+                "addresses": { "type": "array", "items": pubkey },
+            },
+        },
+        "ExtendLookupTableRequest": {
+            "type": "object",
+            "required": ["lookup_table", "addresses"],
+            "properties": {
+                "lookup_table": pubkey,
+                "addresses": { "type": "array", "items": pubkey },
+            },
+        },
+        "CreateMintRequest": {
+            "type": "object",
+            "required": ["decimals", "mint_authority"],
+            "properties": {
+                "mint": pubkey,
+                "decimals": { "type": "integer" },
+                "mint_authority": pubkey,
+                "freeze_authority": pubkey,
+            },
+        },
+        "FindPdaRequest": {
+            "type": "object",
+            "required": ["program_id", "seeds"],
+            "properties": { "program_id": pubkey, "seeds": strings },
+        },
+        "SetOwnerRequest": {
+            "type": "object",
+            "required": ["pubkey", "owner"],
+            "properties": { "pubkey": pubkey, "owner": pubkey },
+        },
+        "SetExecutableRequest": {
+            "type": "object",
+            "required": ["pubkey", "executable"],
+            "properties": { "pubkey": pubkey, "executable": boolean },
+        },
+        "SetBlockhashRequest": {
+            "type": "object",
+            "required": ["blockhash"],
+            "properties": { "blockhash": string },
+        },
+        "SetRentRequest": {
+            "type": "object",
+            "required": ["lamports_per_byte_year", "exemption_threshold", "burn_percent"],
+            "properties": {
+                "lamports_per_byte_year": unsigned,
+                "exemption_threshold": { "type": "number" },
+                "burn_percent": { "type": "integer" },
+            },
+        },
+        "SetEpochRequest": {
+            "type": "object",
+            "required": ["epoch"],
+            "properties": { "epoch": unsigned },
+        },
+        "EpochInfo": {
+            "type": "object",
+            "properties": {
+                "epoch": unsigned,
+                "slot": unsigned,
+                "first_slot": unsigned,
+                "last_slot": unsigned,
+                "leader_schedule_epoch": unsigned,
+            },
+        },
+        "ReplayHistoryRequest": {
+            "type": "object",
+            "properties": { "transactions": strings },
+        },
+        "DiffForksRequest": {
+            "type": "object",
+            "required": ["left", "right"],
+            "properties": {
+                "left": { "type": "string", "format": "uuid" },
+                "right": { "type": "string", "format": "uuid" },
+                "offset": unsigned,
+                "limit": optional_unsigned,
+            },
+        },
+        "AssociatedTokenAddressRequest": {
+            "type": "object",
+            "required": ["owner", "mint"],
+            "properties": {
+                "owner": pubkey,
+                "mint": pubkey,
+                "token_program": { "type": "string", "nullable": true, "description": "SPL token (default) or Token-2022 program id" },
+            },
+        },
+        "GetAccountRequest": {
+            "type": "object",
+            "required": ["pubkey"],
+            "properties": {
+                "pubkey": pubkey,
+                "rpc_url": rpc_url,
+                "data_slice": reference("DataSlice"),
+            },
+        },
+        "DataSlice": {
+            "type": "object",
+            "nullable": true,
+            "required": ["offset", "length"],
+            "description": "Range of account data returned, which must lie within the data",
+            "properties": { "offset": unsigned, "length": unsigned },
+        },
+        "ProgramAccountsRequest": {
+            "type": "object",
+            "required": ["program_id"],
+            "properties": {
+                "program_id": pubkey,
+                "filters": {
+                    "type": "array",
+                    "description": "`{ \"dataSize\": n }` or `{ \"memcmp\": { offset, bytes, encoding } }`",
+                    "items": { "type": "object" },
+                },
+                "data_slice": reference("DataSlice"),
+            },
+        },
+        "CloneProgramAccountsRequest": {
+            "type": "object",
+            "required": ["program_id"],
+            "properties": {
+                "program_id": pubkey,
+                "filters": {
+                    "type": "array",
+                    "description": "`getProgramAccounts` filters, like `program_accounts`",
+                    "items": { "type": "object" },
+                },
+                "limit": {
+                    "type": "integer",
+                    "nullable": true,
+                    "description": "Maximum number of owned accounts cloned, 10000 by default",
+                },
+            },
+        },
+        "ClonedProgram": {
+            "type": "object",
+            "properties": {
+                "program_id": pubkey,
+                "program_data": { "type": "string", "nullable": true },
+                "accounts": unsigned,
+            },
+        },
+        "TransactionHistoryQuery": {
+            "type": "object",
+            "properties": {
+                "success": { "type": "boolean", "nullable": true },
+                "since": { "type": "string", "format": "date-time", "nullable": true },
+                "offset": unsigned,
+                "limit": optional_unsigned,
+                "log_max_lines": optional_unsigned,
+                "log_max_bytes": optional_unsigned,
+                "include_tx": boolean,
+            },
+        },
+        "HealthResponse": {
+            "type": "object",
+            "properties": {
+                "active_forks": unsigned,
+                "last_cleanup": { "type": "object", "nullable": true },
+            },
+        },
+        "ForkSummary": {
+            "type": "object",
+            "properties": {
+                "id": { "type": "string", "format": "uuid" },
+                "name": { "type": "string", "nullable": true },
+                "labels": { "type": "object", "additionalProperties": { "type": "string" } },
+                "offline": boolean,
+                "expires_at": { "type": "string", "format": "date-time" },
+            },
+        },
+        "ForkIds": {
+            "type": "array",
+            "items": { "type": "string", "format": "uuid" },
+        },
+        "BatchDeleteResult": {
+            "type": "object",
+            "properties": {
+                "deleted": unsigned,
+                "not_found": unsigned,
+                "results": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "id": { "type": "string", "format": "uuid" },
+                            "result": { "type": "string", "enum": ["deleted", "not_found", "expired"] },
+                        },
+                    },
+                },
+            },
+        },
+        "ForkDiff": {
+            "type": "object",
+            "properties": {
+                "total": unsigned,
+                "accounts": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "pubkey": pubkey,
+                            "left_lamports": optional_unsigned,
+                            "right_lamports": optional_unsigned,
+                            "fields": strings,
+                        },
+                    },
+                },
+            },
+        },
+        "TransactionMetadata": {
+            "type": "object",
+            "properties": {
+                "signature": string,
+                "logs": strings,
+                "inner_instructions": { "type": "array" },
+                "compute_units_consumed": unsigned,
+                "return_data": { "type": "object" },
+                "program_return": {
+                    "type": "object",
+                    "nullable": true,
+                    "description": "Decoded `return_data`, null if no program set any",
+                    "properties": {
+                        "program_id": pubkey,
+                        "data_base64": string,
+                    },
+                },
+                "fee": unsigned,
+                "trace": {
+                    "type": "array",
+                    "description": "Invoked instructions, for simulations with `trace`",
+                },
+                "impersonated": {
+                    "type": "array",
+                    "items": pubkey,
+                    "description": "Signers impersonated by a simulation, omitted when none",
+                },
+                "compute_units": {
+                    "type": "array",
+                    "description": "Units consumed by each program invocation, in the order they returned, for requests with `compute_units`",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "program_id": pubkey,
+                            "units_consumed": unsigned,
+                            "units_budget": unsigned,
+                        },
+                    },
+                },
+            },
+        },
+        "SendResult": {
+            "type": "object",
+            "properties": {
+                "simulation": reference("TransactionMetadata"),
+                "execution": reference("TransactionMetadata"),
+                "error": {},
+            },
+        },
+        "LogMatch": {
+            "type": "object",
+            "properties": {
+                "success": boolean,
+                "error": { "type": "string", "nullable": true },
+                "matched": boolean,
+                "matching_lines": strings,
+                "logs": strings,
+            },
+        },
+        "ComputeFitRequest": {
+            "type": "object",
+            "required": ["tx_base64"],
+            "properties": { "tx_base64": tx, "allow_unsigned": boolean },
+        },
+        "ComputeFit": {
+            "type": "object",
+            "properties": {
+                "compute_units_consumed": unsigned,
+                "compute_budget": unsigned,
+                "fits": boolean,
+            },
+        },
+        "FeeEstimate": {
+            "type": "object",
+            "properties": {
+                "base_fee": unsigned,
+                "prioritization_fee": unsigned,
+                "compute_unit_limit": unsigned,
+                "compute_unit_price": unsigned,
+                "total_fee": unsigned,
+            },
+        },
+        "FeeSchedule": {
+            "type": "object",
+            "properties": {
+                "lamports_per_signature": unsigned,
+                "default_instruction_compute_unit_limit": unsigned,
+                "builtin_instruction_compute_unit_limit": unsigned,
+                "max_compute_unit_limit": unsigned,
+                "micro_lamports_per_lamport": unsigned,
+                "fees_charged": boolean,
+            },
+        },
+        "ProgramAddress": {
+            "type": "object",
+            "properties": { "address": pubkey, "bump": { "type": "integer" } },
+        },
+        "BlockhashResponse": {
+            "type": "object",
+            "properties": { "blockhash": string, "slot": unsigned },
+        },
+        "ReplayResult": {
+            "type": "object",
+            "properties": {
+                "fork_id": { "type": "string", "format": "uuid" },
+                "replayed": unsigned,
+                "failed": strings,
+            },
+        },
+        "UiAccount": {
+            "type": "object",
+            "properties": {
+                "lamports": unsigned,
+                "owner": pubkey,
+                "data": { "description": "`[data, \"base64\"]`, or parsed JSON with `jsonParsed`" },
+                "executable": boolean,
+                "rent_epoch": unsigned,
+            },
+        },
+        "TransactionRecord": {
+            "type": "object",
+            "properties": {
+                "txn": reference("TransactionMetadata"),
+                "tx_base64": string,
+                "time": { "type": "string", "format": "date-time" },
+                "success": boolean,
+            },
+        },
+        "TransactionPage": {
+            "type": "object",
+            "properties": {
+                "total": unsigned,
+                "transactions": { "type": "array", "items": reference("TransactionRecord") },
+            },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Collects every `$ref` of a JSON value
+    fn references(value: &Value, refs: &mut Vec<String>) {
+        match value {
+            Value::Object(map) => {
+                if let Some(Value::String(reference)) = map.get("$ref") {
+                    refs.push(reference.clone());
+                }
+                map.values().for_each(|v| references(v, refs));
+            }
+            Value::Array(values) => values.iter().for_each(|v| references(v, refs)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_document() {
+        let document = document();
+        assert_eq!(document["openapi"], "3.0.3");
+
+        let mut refs = Vec::new();
+        references(&document, &mut refs);
+        assert!(!refs.is_empty());
+        for reference in refs {
+            let name = reference.trim_start_matches("#/components/schemas/");
+            assert!(
+                document["components"]["schemas"].get(name).is_some(),
+                "missing schema {}",
+                name
+            );
+        }
+
+        let execute = &document["paths"]["/forks/{id}/execute"]["post"];
+        assert_eq!(execute["parameters"][0]["name"], "id");
+        assert_eq!(
+            execute["requestBody"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/ExecuteRequest"
+        );
+        let forks = &document["paths"]["/forks"];
+        assert!(forks.get("get").is_some() && forks.get("post").is_some());
+    }
+}
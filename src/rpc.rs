@@ -0,0 +1,400 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use solana_account_decoder_client_types::{UiAccount, UiAccountEncoding, UiDataSliceConfig};
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind, Result as ClientResult},
+    rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::RpcFilterType,
+    rpc_request::RpcRequest,
+    rpc_response::Response,
+};
+use solana_commitment_config::{CommitmentConfig, CommitmentLevel};
+#[cfg(test)]
+use solana_rpc_client::mock_sender::{Mocks, MocksMap};
+use solana_sdk::{account::Account, epoch_schedule::EpochSchedule, hash::Hash, pubkey::Pubkey};
+use tracing::warn;
+
+/// Default timeout of a single RPC request
+const DEFAULT_RPC_TIMEOUT: Duration = Duration::from_secs(30);
+/// Default number of retries of a request failing with a transient error
+const DEFAULT_RPC_MAX_RETRIES: u32 = 3;
+/// Default delay before the first retry, doubled on every attempt
+const DEFAULT_RPC_RETRY_BACKOFF: Duration = Duration::from_millis(200);
+
+/// Timeout and retry policy of mainnet RPC requests
+#[derive(Clone, Copy, Debug)]
+pub struct RpcPolicy {
+    pub timeout: Duration,
+    pub max_retries: u32,
+    pub backoff: Duration,
+}
+
+impl Default for RpcPolicy {
+    fn default() -> Self {
+        RpcPolicy {
+            timeout: DEFAULT_RPC_TIMEOUT,
+            max_retries: DEFAULT_RPC_MAX_RETRIES,
+            backoff: DEFAULT_RPC_RETRY_BACKOFF,
+        }
+    }
+}
+
+impl RpcPolicy {
+    /// Reads the policy from the `RPC_TIMEOUT_MS`, `RPC_MAX_RETRIES` and
+    /// `RPC_RETRY_BACKOFF_MS` env vars, using defaults for missing ones
+    pub fn from_env() -> Self {
+        let env = |var: &str| std::env::var(var).ok().and_then(|v| v.parse::<u64>().ok());
+        let default = RpcPolicy::default();
+        RpcPolicy {
+            timeout: env("RPC_TIMEOUT_MS").map_or(default.timeout, Duration::from_millis),
+            max_retries: env("RPC_MAX_RETRIES").map_or(default.max_retries, |v| v as u32),
+            backoff: env("RPC_RETRY_BACKOFF_MS").map_or(default.backoff, Duration::from_millis),
+        }
+    }
+}
+
+/// Commitment level of the mainnet state a fork reads
+#[derive(Deserialize, Serialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Commitment {
+    Processed,
+    #[default]
+    Confirmed,
+    Finalized,
+}
+
+impl From<Commitment> for CommitmentConfig {
+    fn from(commitment: Commitment) -> Self {
+        let commitment = match commitment {
+            Commitment::Processed => CommitmentLevel::Processed,
+            Commitment::Confirmed => CommitmentLevel::Confirmed,
+            Commitment::Finalized => CommitmentLevel::Finalized,
+        };
+        CommitmentConfig { commitment }
+    }
+}
+
+/// Client fetching mainnet state on behalf of a fork
+pub struct MainnetRpc {
+    client: RpcClient,
+    /// Oldest slot the node may answer from, if any. It only bounds the
+    /// state fetched from mainnet, the fork's clock is independent.
+    min_context_slot: Option<u64>,
+    policy: RpcPolicy,
+    /// Number of requests sent, retries included
+    requests: AtomicU64,
+}
+
+impl MainnetRpc {
+    /// Creates a client reading state at `commitment`, which applies to every
+    /// request made through it
+    pub fn new(
+        url: &str,
+        min_context_slot: Option<u64>,
+        commitment: Commitment,
+        policy: RpcPolicy,
+    ) -> Self {
+        MainnetRpc {
+            client: RpcClient::new_with_timeout_and_commitment(
+                url.to_string(),
+                policy.timeout,
+                commitment.into(),
+            ),
+            min_context_slot,
+            policy,
+            requests: AtomicU64::new(0),
+        }
+    }
+
+    /// Creates a client answering requests from `mocks` instead of a node
+    #[cfg(test)]
+    pub fn mock(mocks: Mocks) -> Self {
+        Self::mock_map(mocks.into())
+    }
+
+    /// Client answering with `mocks`, several answers to the same request
+    /// being returned in order
+    #[cfg(test)]
+    pub fn mock_map(mocks: MocksMap) -> Self {
+        MainnetRpc {
+            client: RpcClient::new_mock_with_mocks_map("succeeds".to_string(), mocks),
+            min_context_slot: None,
+            policy: RpcPolicy::default(),
+            requests: AtomicU64::new(0),
+        }
+    }
+
+    /// Number of requests sent to the node so far, retries included
+    pub fn requests(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    /// Fetches an account, returning `None` if it doesn't exist on mainnet.
+    ///
+    /// Standard RPC nodes only serve the latest state: the fork's `slot`
+    /// option is sent as the minimum context slot, and a warning is logged
+    /// if the node answered from a different slot.
+    pub fn get_account(&self, pubkey: &Pubkey) -> anyhow::Result<Option<Account>> {
+        // Sent as is, `get_account_with_config` turns every error into
+        // `AccountNotFound` so transient failures couldn't be retried
+        let params = serde_json::json!([pubkey.to_string(), self.config()]);
+        let response: Response<Option<UiAccount>> =
+            self.with_retries(|client| client.send(RpcRequest::GetAccountInfo, params.clone()))?;
+        self.check_context_slot(response.context.slot);
+        response
+            .value
+            .map(|account| {
+                account
+                    .decode()
+                    .ok_or_else(|| anyhow::anyhow!("Can't decode account {}", pubkey))
+            })
+            .transpose()
+    }
+
+    /// Fetches several accounts in a single request
+    pub fn get_multiple_accounts(&self, keys: &[Pubkey]) -> anyhow::Result<Vec<Option<Account>>> {
+        let response = self
+            .with_retries(|client| client.get_multiple_accounts_with_config(keys, self.config()))?;
+        self.check_context_slot(response.context.slot);
+        Ok(response.value)
+    }
+
+    /// Fetches every account owned by a program which matches all the
+    /// filters. Nodes return them in a single response, so queries matching
+    /// many accounts are slow and often rejected by RPC providers.
+    pub fn get_program_accounts(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> anyhow::Result<Vec<(Pubkey, Account)>> {
+        self.get_program_accounts_sliced(program_id, filters, None)
+    }
+
+    /// Counts the accounts owned by a program which match all the filters,
+    /// fetching them without their data so the count stays cheap
+    pub fn count_program_accounts(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> anyhow::Result<usize> {
+        let empty = UiDataSliceConfig {
+            offset: 0,
+            length: 0,
+        };
+        self.get_program_accounts_sliced(program_id, filters, Some(empty))
+            .map(|accounts| accounts.len())
+    }
+
+    fn get_program_accounts_sliced(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+        data_slice: Option<UiDataSliceConfig>,
+    ) -> anyhow::Result<Vec<(Pubkey, Account)>> {
+        let config = RpcProgramAccountsConfig {
+            filters: (!filters.is_empty()).then_some(filters),
+            account_config: RpcAccountInfoConfig {
+                data_slice,
+                ..self.config()
+            },
+            ..Default::default()
+        };
+        self.with_retries(|client| {
+            client.get_program_accounts_with_config(program_id, config.clone())
+        })
+    }
+
+    pub fn get_slot(&self) -> anyhow::Result<u64> {
+        self.with_retries(|client| client.get_slot())
+    }
+
+    pub fn get_latest_blockhash(&self) -> anyhow::Result<Hash> {
+        self.with_retries(|client| client.get_latest_blockhash())
+    }
+
+    pub fn get_epoch_schedule(&self) -> anyhow::Result<EpochSchedule> {
+        self.with_retries(|client| client.get_epoch_schedule())
+    }
+
+    /// Runs a request, retrying transient failures with exponential backoff
+    /// up to the policy's number of retries
+    fn with_retries<T>(
+        &self,
+        request: impl Fn(&RpcClient) -> ClientResult<T>,
+    ) -> anyhow::Result<T> {
+        let mut attempt = 0;
+        let mut backoff = self.policy.backoff;
+        loop {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            match request(&self.client) {
+                Ok(value) => return Ok(value),
+                Err(e) if is_transient(&e) && attempt < self.policy.max_retries => {
+                    attempt += 1;
+                    warn!(error = %e, attempt, "transient RPC error, retrying");
+                    std::thread::sleep(backoff);
+                    backoff *= 2;
+                }
+                Err(e) if is_timeout(&e) => {
+                    anyhow::bail!("upstream RPC timeout after {} attempts: {}", attempt + 1, e)
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Account query config. Nodes default to base58, which they refuse
+    /// for accounts larger than 128 bytes.
+    fn config(&self) -> RpcAccountInfoConfig {
+        RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64Zstd),
+            commitment: Some(self.client.commitment()),
+            min_context_slot: self.min_context_slot,
+            ..Default::default()
+        }
+    }
+
+    fn check_context_slot(&self, context_slot: u64) {
+        if let Some(slot) = self.min_context_slot
+            && context_slot != slot
+        {
+            warn!(
+                requested_slot = slot,
+                context_slot, "RPC can't serve the requested slot, using its latest state"
+            );
+        }
+    }
+}
+
+/// Whether a request failed because of the connection rather than the
+/// request itself, so it may succeed when retried
+fn is_transient(e: &ClientError) -> bool {
+    matches!(
+        e.kind(),
+        ClientErrorKind::Io(_) | ClientErrorKind::Reqwest(_)
+    )
+}
+
+fn is_timeout(e: &ClientError) -> bool {
+    match e.kind() {
+        ClientErrorKind::Reqwest(e) => e.is_timeout(),
+        ClientErrorKind::Io(e) => e.kind() == std::io::ErrorKind::TimedOut,
+        _ => false,
+    }
+}
+
+/// Whether `url` looks like an HTTP(S) endpoint a client can be built for
+pub fn is_valid_rpc_url(url: &str) -> bool {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return false;
+    };
+    let host = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    matches!(scheme, "http" | "https") && !host.is_empty() && !url.contains(char::is_whitespace)
+}
+
+/// Whether `url` points to the scheme, host and port of one of the
+/// `allowed` URLs. Paths and queries (e.g. API keys) may differ.
+pub fn is_allowed_rpc_url(url: &str, allowed: &[String]) -> bool {
+    let origin = url_origin(url);
+    origin.is_some() && allowed.iter().any(|allowed| url_origin(allowed) == origin)
+}
+
+/// Scheme and authority of a URL, lowercased
+fn url_origin(url: &str) -> Option<String> {
+    let (scheme, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    Some(format!("{}://{}", scheme, authority).to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retries_are_bounded() {
+        // Nothing listens on this port, so every attempt fails to connect
+        let rpc = MainnetRpc::new(
+            "http://127.0.0.1:1",
+            None,
+            Commitment::default(),
+            RpcPolicy {
+                timeout: Duration::from_secs(1),
+                max_retries: 2,
+                backoff: Duration::from_millis(1),
+            },
+        );
+        assert!(rpc.get_account(&Pubkey::new_unique()).is_err());
+        assert_eq!(rpc.requests(), 3);
+    }
+
+    #[test]
+    fn test_timeout_error() {
+        // Connections are accepted by the listener's backlog but never
+        // answered
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let rpc = MainnetRpc::new(
+            &format!("http://{}", listener.local_addr().unwrap()),
+            None,
+            Commitment::default(),
+            RpcPolicy {
+                timeout: Duration::from_millis(100),
+                max_retries: 1,
+                backoff: Duration::from_millis(1),
+            },
+        );
+        let err = rpc.get_account(&Pubkey::new_unique()).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("upstream RPC timeout after 2 attempts"),
+            "{}",
+            err
+        );
+        assert_eq!(rpc.requests(), 2);
+    }
+
+    #[test]
+    fn test_requests_are_counted() {
+        let rpc = MainnetRpc::mock(Default::default());
+        assert_eq!(rpc.requests(), 0);
+        rpc.get_slot().unwrap();
+        rpc.get_latest_blockhash().unwrap();
+        assert_eq!(rpc.requests(), 2);
+    }
+
+    #[test]
+    fn test_is_valid_rpc_url() {
+        assert!(is_valid_rpc_url("https://api.mainnet-beta.solana.com"));
+        assert!(is_valid_rpc_url("http://127.0.0.1:8899/?api-key=x"));
+        assert!(!is_valid_rpc_url("api.mainnet-beta.solana.com"));
+        assert!(!is_valid_rpc_url("ftp://example.com"));
+        assert!(!is_valid_rpc_url("https:///path"));
+        assert!(!is_valid_rpc_url("https://exa mple.com"));
+    }
+
+    #[test]
+    fn test_is_allowed_rpc_url() {
+        let allowed = vec!["https://archive.example.com".to_string()];
+        assert!(is_allowed_rpc_url(
+            "https://ARCHIVE.example.com/?api-key=x",
+            &allowed
+        ));
+        assert!(!is_allowed_rpc_url("http://archive.example.com", &allowed));
+        assert!(!is_allowed_rpc_url(
+            "https://archive.example.com:8899",
+            &allowed
+        ));
+        assert!(!is_allowed_rpc_url(
+            "https://archive.example.com@169.254.169.254/",
+            &allowed
+        ));
+        assert!(!is_allowed_rpc_url(
+            "https://archive.example.com.evil.com",
+            &allowed
+        ));
+        assert!(!is_allowed_rpc_url("https://archive.example.com", &[]));
+    }
+}
@@ -0,0 +1,84 @@
+//! Lock helpers recovering from poisoning.
+//!
+//! A lock is poisoned when a thread panics while holding it. The request
+//! which panicked already failed; the others keep using the state behind
+//! the lock instead of panicking in turn, so one bad transaction can't take
+//! down every fork of the service.
+
+use std::sync::{Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use tracing::warn;
+
+pub trait RwLockExt<T> {
+    /// Like [`RwLock::read`], recovering the guard of a poisoned lock
+    fn read_or_recover(&self) -> RwLockReadGuard<'_, T>;
+    /// Like [`RwLock::write`], recovering the guard of a poisoned lock
+    fn write_or_recover(&self) -> RwLockWriteGuard<'_, T>;
+}
+
+pub trait MutexExt<T> {
+    /// Like [`Mutex::lock`], recovering the guard of a poisoned mutex
+    fn lock_or_recover(&self) -> MutexGuard<'_, T>;
+}
+
+impl<T> RwLockExt<T> for RwLock<T> {
+    fn read_or_recover(&self) -> RwLockReadGuard<'_, T> {
+        self.read().unwrap_or_else(|poisoned| {
+            self.clear_poison();
+            recover(poisoned)
+        })
+    }
+
+    fn write_or_recover(&self) -> RwLockWriteGuard<'_, T> {
+        self.write().unwrap_or_else(|poisoned| {
+            self.clear_poison();
+            recover(poisoned)
+        })
+    }
+}
+
+impl<T> MutexExt<T> for Mutex<T> {
+    fn lock_or_recover(&self) -> MutexGuard<'_, T> {
+        self.lock().unwrap_or_else(|poisoned| {
+            self.clear_poison();
+            recover(poisoned)
+        })
+    }
+}
+
+fn recover<G>(poisoned: PoisonError<G>) -> G {
+    warn!("recovering a lock poisoned by a panic");
+    poisoned.into_inner()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::Arc, thread};
+
+    #[test]
+    fn test_recover_poisoned_lock() {
+        let lock = Arc::new(RwLock::new(1));
+        let poisoner = Arc::clone(&lock);
+        let _ = thread::spawn(move || {
+            let _guard = poisoner.write().unwrap();
+            panic!("poisoning the lock");
+        })
+        .join();
+        assert!(lock.is_poisoned());
+
+        *lock.write_or_recover() += 1;
+        assert_eq!(*lock.read_or_recover(), 2);
+        assert!(!lock.is_poisoned());
+
+        let mutex = Arc::new(Mutex::new(1));
+        let poisoner = Arc::clone(&mutex);
+        let _ = thread::spawn(move || {
+            let _guard = poisoner.lock().unwrap();
+            panic!("poisoning the mutex");
+        })
+        .join();
+        assert_eq!(*mutex.lock_or_recover(), 1);
+        assert!(!mutex.is_poisoned());
+    }
+}
@@ -0,0 +1,71 @@
+use base64::{Engine, engine};
+use litesvm::types::TransactionMetadata;
+use solana_sdk::message::{VersionedMessage, compiled_instruction::CompiledInstruction};
+
+pub use simulation_engine::types::TracedInstruction;
+
+/// Builds the invocation trace of a transaction: every top-level
+/// instruction followed by the instructions it invoked through CPIs.
+///
+/// Accounts are resolved against the static keys of the message, accounts
+/// loaded from lookup tables are reported by their index (`#<index>`).
+pub fn build_trace(
+    message: &VersionedMessage,
+    meta: &TransactionMetadata,
+) -> Vec<TracedInstruction> {
+    let account_keys = message.static_account_keys();
+    let resolve = |index: u8| {
+        account_keys
+            .get(index as usize)
+            .map(|key| key.to_string())
+            .unwrap_or_else(|| format!("#{}", index))
+    };
+    let traced =
+        |instruction_index: usize, stack_height: u8, ix: &CompiledInstruction| TracedInstruction {
+            instruction_index,
+            stack_height,
+            program_id: resolve(ix.program_id_index),
+            accounts: ix.accounts.iter().map(|index| resolve(*index)).collect(),
+            data_base64: engine::general_purpose::STANDARD.encode(&ix.data),
+        };
+
+    let mut trace = Vec::new();
+    for (index, ix) in message.instructions().iter().enumerate() {
+        trace.push(traced(index, 1, ix));
+        if let Some(inner) = meta.inner_instructions.get(index) {
+            for inner_ix in inner {
+                trace.push(traced(index, inner_ix.stack_height, &inner_ix.instruction));
+            }
+        }
+    }
+    trace
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{message::Message, pubkey::Pubkey};
+    use solana_system_interface::instruction as system_instruction;
+
+    #[test]
+    fn test_top_level_trace() {
+        let payer = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let message = VersionedMessage::Legacy(Message::new(
+            &[system_instruction::transfer(&payer, &recipient, 1)],
+            Some(&payer),
+        ));
+
+        let trace = build_trace(&message, &TransactionMetadata::default());
+        assert_eq!(trace.len(), 1);
+        assert_eq!(trace[0].stack_height, 1);
+        assert_eq!(
+            trace[0].program_id,
+            solana_system_interface::program::ID.to_string()
+        );
+        assert_eq!(
+            trace[0].accounts,
+            vec![payer.to_string(), recipient.to_string()]
+        );
+    }
+}
@@ -0,0 +1,810 @@
+//! Request and response bodies of the HTTP API, shared by the server and
+//! the [`client`](crate::client)
+
+use std::collections::BTreeMap;
+
+use base64::{Engine, engine};
+use chrono::{DateTime, Utc};
+use litesvm::types::TransactionMetadata;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_sdk::{
+    account::Account,
+    pubkey::Pubkey,
+    transaction::{TransactionError, VersionedTransaction},
+};
+use uuid::Uuid;
+
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+pub struct ExecuteRequest {
+    pub tx_base64: String,
+    /// Overrides the wall-clock deadline of the execution, in milliseconds
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    /// Overrides the maximum number of log lines returned
+    #[serde(default)]
+    pub log_max_lines: Option<usize>,
+    /// Overrides the maximum size of the logs returned, in bytes
+    #[serde(default)]
+    pub log_max_bytes: Option<usize>,
+    /// Mainnet RPC endpoint fetching the transaction's missing accounts
+    /// instead of the fork's, only used by `execute` and `send`. It must be
+    /// in the server's `RPC_URL_ALLOWLIST`.
+    #[serde(default)]
+    pub rpc_url: Option<String>,
+    /// Return the compute units consumed by each program invocation, only
+    /// used by `execute`
+    #[serde(default)]
+    pub compute_units: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+pub struct SimulateRequest {
+    pub tx_base64: String,
+    /// Return the instructions invoked during the simulation
+    #[serde(default)]
+    pub trace: bool,
+    /// Return the compute units consumed by each program invocation
+    #[serde(default)]
+    pub compute_units: bool,
+    /// Re-fetch the accounts of the transaction from mainnet before
+    /// simulating, instead of using the fork's cached copies
+    #[serde(default)]
+    pub refresh_accounts: bool,
+    /// Skip signature verification, so transactions can be previewed
+    /// before being signed
+    #[serde(default)]
+    pub allow_unsigned: bool,
+    /// Overrides the maximum number of log lines returned
+    #[serde(default)]
+    pub log_max_lines: Option<usize>,
+    /// Overrides the maximum size of the logs returned, in bytes
+    #[serde(default)]
+    pub log_max_bytes: Option<usize>,
+    /// Mainnet RPC endpoint fetching the transaction's accounts instead of
+    /// the fork's, if in the server's `RPC_URL_ALLOWLIST`
+    #[serde(default)]
+    pub rpc_url: Option<String>,
+    /// Signers simulated as if they had signed, whose keys the user doesn't
+    /// hold. Other signers must still sign.
+    #[serde(default)]
+    pub impersonate: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+pub struct SetLamportsRequest {
+    pub pubkey: String,
+    pub lamports: u64,
+    /// Allow a data-bearing account to drop below its rent-exempt minimum
+    #[serde(default)]
+    pub allow_rent_paying: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+pub struct ComputeFitRequest {
+    pub tx_base64: String,
+    /// Skip signature verification, to check transactions before signing
+    /// them
+    #[serde(default)]
+    pub allow_unsigned: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SimulateMatchRequest {
+    pub tx_base64: String,
+    pub pattern: String,
+    /// Whether `pattern` is a regex rather than a substring
+    #[serde(default)]
+    pub regex: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SetTokenBalanceRequest {
+    pub token_account: String,
+    pub mint: String,
+    pub owner: String,
+    pub amount: u64,
+    /// Decimals of the mint, used when it has to be created
+    pub decimals: Option<u8>,
+    /// Reject amounts exceeding the supply of an existing mint
+    #[serde(default)]
+    pub check_supply: bool,
+    /// Lamports of the token account when it has to be created, the
+    /// rent-exempt minimum by default
+    #[serde(default)]
+    pub lamports: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct FreezeTokenRequest {
+    pub token_account: String,
+    pub frozen: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SetTokenDelegateRequest {
+    pub token_account: String,
+    pub delegate: String,
+    pub amount: u64,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SetOwnerRequest {
+    pub pubkey: String,
+    /// Program the account is reassigned to
+    pub owner: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SetExecutableRequest {
+    pub pubkey: String,
+    pub executable: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ReplayHistoryRequest {
+    /// Base64 encoded transactions to replay, the fork's executed
+    /// transactions by default
+    #[serde(default)]
+    pub transactions: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct DiffForksRequest {
+    pub left: Uuid,
+    pub right: Uuid,
+    #[serde(default)]
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct FindPdaRequest {
+    pub program_id: String,
+    /// Base64 encoded seeds
+    pub seeds: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct AssociatedTokenAddressRequest {
+    pub owner: String,
+    pub mint: String,
+    /// SPL token program by default, or the Token-2022 program
+    #[serde(default)]
+    pub token_program: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct CreateMintRequest {
+    /// Address of the mint, random if missing
+    pub mint: Option<String>,
+    pub decimals: u8,
+    pub mint_authority: String,
+    pub freeze_authority: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct CreateLookupTableRequest {
+    /// Address of the table, random if missing
+    pub address: Option<String>,
+    /// Authority recorded in the table, which is frozen without one
+    pub authority: Option<String>,
+    #[serde(default)]
+    pub addresses: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ExtendLookupTableRequest {
+    pub lookup_table: String,
+    pub addresses: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SetBlockhashRequest {
+    pub blockhash: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SetEpochRequest {
+    pub epoch: u64,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SetRentRequest {
+    pub lamports_per_byte_year: u64,
+    pub exemption_threshold: f64,
+    pub burn_percent: u8,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GetAccountRequest {
+    pub pubkey: String,
+    /// Mainnet RPC endpoint fetching the account instead of the fork's, if
+    /// in the server's `RPC_URL_ALLOWLIST`
+    #[serde(default)]
+    pub rpc_url: Option<String>,
+    /// Only return this range of the account's data
+    #[serde(default)]
+    pub data_slice: Option<DataSlice>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+pub struct AccountReadQuery {
+    #[serde(default)]
+    pub encoding: AccountEncoding,
+    /// Only read the fork's state, never fetching missing accounts from
+    /// mainnet
+    #[serde(default)]
+    pub local_only: bool,
+}
+
+/// Range of account data returned by a read, like the RPC `dataSlice`
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct DataSlice {
+    pub offset: usize,
+    pub length: usize,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct TransactionLookupQuery {
+    /// Also search the simulated transactions
+    #[serde(default)]
+    pub simulated: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ListForksQuery {
+    /// `key` or `key=value`, only listing the forks with that label
+    pub label: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct AccountExistsQuery {
+    /// Only check the fork's state, never fetching from mainnet
+    #[serde(default)]
+    pub local_only: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct MinimumBalanceQuery {
+    pub data_len: usize,
+}
+
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+pub struct TransactionEventsQuery {
+    /// Include the accounts each transaction changed in its event
+    #[serde(default)]
+    pub account_changes: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SlotHashesQuery {
+    /// Maximum number of entries returned, 32 by default
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct FundedAccount {
+    pub pubkey: String,
+    pub lamports: u64,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct BlockhashResponse {
+    pub blockhash: String,
+    pub slot: u64,
+}
+
+/// Result of `POST /forks/{id}/execute`
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ExecuteResponse {
+    #[serde(flatten)]
+    pub meta: TransactionMetadata,
+    /// Decoded `return_data`, null if no program set any
+    pub program_return: Option<ReturnData>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compute_units: Option<Vec<InstructionCompute>>,
+}
+
+impl From<TransactionMetadata> for ExecuteResponse {
+    fn from(meta: TransactionMetadata) -> Self {
+        ExecuteResponse {
+            program_return: ReturnData::from_meta(&meta),
+            meta,
+            compute_units: None,
+        }
+    }
+}
+
+/// Result of `POST /forks/{id}/simulate`
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SimulateResponse {
+    #[serde(flatten)]
+    pub meta: TransactionMetadata,
+    /// Decoded `return_data`, null if no program set any
+    pub program_return: Option<ReturnData>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace: Option<Vec<TracedInstruction>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compute_units: Option<Vec<InstructionCompute>>,
+    /// Signers whose signatures were waived, flagging results no real
+    /// transaction could produce without their keys
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub impersonated: Vec<String>,
+}
+
+/// An account owned by a program, as listed by `program_accounts`
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ProgramAccount {
+    pub pubkey: String,
+    pub account: Account,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct HealthResponse {
+    pub active_forks: usize,
+    /// Outcome of the latest cleanup of expired forks, missing until the
+    /// first one ran
+    pub last_cleanup: Option<CleanupReport>,
+}
+
+/// Summary of a fork, as listed by `GET /forks`
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ForkSummary {
+    pub id: Uuid,
+    pub name: Option<String>,
+    pub labels: BTreeMap<String, String>,
+    pub offline: bool,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Addresses set by each section of a fork setup
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+pub struct SetupResult {
+    pub lamports: Vec<String>,
+    pub token_balances: Vec<String>,
+    pub accounts: Vec<String>,
+    pub programs: Vec<String>,
+}
+
+/// A record of transaction executed/simulated on the fork
+#[derive(Deserialize, Serialize, Clone)]
+pub struct TransactionRecord {
+    pub txn: TransactionMetadata,
+    /// The transaction itself, so it can be replayed. Missing from records
+    /// which weren't recorded by this server.
+    #[serde(skip)]
+    pub tx: Option<VersionedTransaction>,
+    /// The bincode serialized transaction, encoded in base64. Only
+    /// returned when asked for, to keep history pages small.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_base64: Option<String>,
+    /// Time the transaction was recorded at, serialized as RFC3339
+    pub time: DateTime<Utc>,
+    pub success: bool,
+}
+
+impl TransactionRecord {
+    /// Fills `tx_base64` from the retained transaction
+    pub fn encode_tx(&mut self) {
+        self.tx_base64 = self
+            .tx
+            .as_ref()
+            .and_then(|tx| bincode::serialize(tx).ok())
+            .map(|bytes| engine::general_purpose::STANDARD.encode(bytes));
+    }
+}
+
+/// Summary of the state of a fork
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ForkStats {
+    /// Number of accounts materialized in the fork
+    pub accounts: usize,
+    /// Lamports held by the materialized accounts
+    pub total_lamports: u128,
+    /// Executed transactions retained in the history
+    pub executed_transactions: usize,
+    /// Simulated transactions retained in the history
+    pub simulated_transactions: usize,
+    /// Transactions ever executed, including the ones dropped from the
+    /// history
+    pub executed_transactions_total: u64,
+    /// Transactions ever simulated, including the ones dropped from the
+    /// history
+    pub simulated_transactions_total: u64,
+    /// Requests sent to the mainnet RPC by the fork (preloads, missing
+    /// accounts, sysvars), retries included
+    pub rpc_requests: u64,
+}
+
+/// Outcome of a replay of transactions on a new fork
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ReplayResult {
+    /// Fork the transactions were replayed on
+    pub fork_id: Uuid,
+    /// Number of transactions replayed
+    pub replayed: usize,
+    /// Signatures of the replayed transactions which failed
+    pub failed: Vec<String>,
+}
+
+/// A page of the accounts differing between two forks
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ForkDiff {
+    /// Number of differing accounts, across all pages
+    pub total: usize,
+    pub accounts: Vec<AccountDiff>,
+}
+
+/// An account which differs between two forks
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct AccountDiff {
+    pub pubkey: String,
+    /// Lamports in the left fork, `None` if the account is missing from it
+    pub left_lamports: Option<u64>,
+    /// Lamports in the right fork, `None` if the account is missing from it
+    pub right_lamports: Option<u64>,
+    /// Fields which differ (`lamports`, `owner`, `executable`, `data`), or
+    /// `exists` when the account is missing from one of the forks
+    pub fields: Vec<String>,
+}
+
+impl AccountDiff {
+    /// Compares the versions of an account, `None` if they don't differ
+    pub fn new(pubkey: Pubkey, left: Option<Account>, right: Option<Account>) -> Option<Self> {
+        let fields = match (&left, &right) {
+            (None, None) => Vec::new(),
+            (Some(left), Some(right)) => [
+                ("lamports", left.lamports != right.lamports),
+                ("owner", left.owner != right.owner),
+                ("executable", left.executable != right.executable),
+                ("data", left.data != right.data),
+            ]
+            .into_iter()
+            .filter(|(_, differs)| *differs)
+            .map(|(field, _)| field.to_string())
+            .collect(),
+            _ => vec!["exists".to_string()],
+        };
+        if fields.is_empty() {
+            return None;
+        }
+        Some(AccountDiff {
+            pubkey: pubkey.to_string(),
+            left_lamports: left.map(|acc| acc.lamports),
+            right_lamports: right.map(|acc| acc.lamports),
+            fields,
+        })
+    }
+}
+
+/// A program derived address
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct ProgramAddress {
+    pub address: String,
+    pub bump: u8,
+}
+
+/// A runtime feature active in a fork
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ActiveFeature {
+    pub id: String,
+    /// Description of the feature, if known to the runtime
+    pub name: Option<String>,
+}
+
+/// Outcome of a transaction simulated then executed
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SendResult {
+    /// Preview of the transaction, from its simulation
+    pub simulation: TransactionMetadata,
+    /// Result of the execution, missing when the simulation failed
+    pub execution: Option<TransactionMetadata>,
+    /// Error of the failed simulation or execution
+    pub error: Option<TransactionError>,
+}
+
+/// Accounts of a program cloned from mainnet into a fork
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct ClonedProgram {
+    pub program_id: String,
+    /// ProgramData account of an upgradeable program
+    pub program_data: Option<String>,
+    /// Number of accounts owned by the program which were cloned
+    pub accounts: usize,
+}
+
+/// Entry of the `SlotHashes` sysvar
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct SlotHashEntry {
+    pub slot: u64,
+    pub hash: String,
+}
+
+/// State of a token account relevant to its balance
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct TokenBalance {
+    pub mint: String,
+    pub owner: String,
+    pub amount: u64,
+    pub frozen: bool,
+}
+
+/// Compute units a simulated transaction consumed against its budget
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct ComputeFit {
+    pub compute_units_consumed: u64,
+    /// Compute unit limit requested by the transaction, or the default one
+    pub compute_budget: u32,
+    /// Whether the transaction ran within its budget
+    pub fits: bool,
+}
+
+/// Data a program returned with `sol_set_return_data`
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct ReturnData {
+    /// Program which last set the return data
+    pub program_id: String,
+    pub data_base64: String,
+}
+
+impl ReturnData {
+    /// Return data of a transaction, `None` if no program set any
+    pub fn from_meta(meta: &TransactionMetadata) -> Option<Self> {
+        let return_data = &meta.return_data;
+        if return_data.program_id == Pubkey::default() && return_data.data.is_empty() {
+            return None;
+        }
+        Some(ReturnData {
+            program_id: return_data.program_id.to_string(),
+            data_base64: engine::general_purpose::STANDARD.encode(&return_data.data),
+        })
+    }
+}
+
+/// Epoch of a fork's `Clock` sysvar, with the slots it spans
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct EpochInfo {
+    pub epoch: u64,
+    /// Current slot, within `first_slot..=last_slot`
+    pub slot: u64,
+    pub first_slot: u64,
+    pub last_slot: u64,
+    pub leader_schedule_epoch: u64,
+}
+
+/// A page of the transaction history of a fork
+#[derive(Deserialize, Serialize, Clone)]
+pub struct TransactionPage {
+    /// Number of transactions matching the filters, across all pages
+    pub total: usize,
+    pub transactions: Vec<TransactionRecord>,
+}
+
+/// Outcome of a cleanup of expired forks
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+pub struct CleanupReport {
+    pub at: DateTime<Utc>,
+    /// Number of expired forks removed
+    pub swept: usize,
+}
+
+/// Outcome of deleting a fork
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum DeleteForkResult {
+    Deleted,
+    NotFound,
+    Expired,
+}
+
+/// Outcome of deleting a batch of forks
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct BatchDeleteResult {
+    pub deleted: usize,
+    /// Forks which didn't exist, expired ones included
+    pub not_found: usize,
+    pub results: Vec<ForkDeletion>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ForkDeletion {
+    pub id: Uuid,
+    pub result: DeleteForkResult,
+}
+
+/// Fee a transaction costs, split into its base and priority components
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct FeeEstimate {
+    /// Fee for the signatures of the transaction
+    pub base_fee: u64,
+    /// Fee from the compute unit price set by the transaction
+    pub prioritization_fee: u64,
+    /// Compute unit limit requested or defaulted for the transaction
+    pub compute_unit_limit: u32,
+    /// Compute unit price in micro-lamports requested by the transaction
+    pub compute_unit_price: u64,
+    /// Total fee in lamports
+    pub total_fee: u64,
+}
+
+/// Fee parameters of a fork, to check fees computed client side
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct FeeSchedule {
+    pub lamports_per_signature: u64,
+    /// Compute units allotted to each instruction when the transaction
+    /// doesn't request a limit
+    pub default_instruction_compute_unit_limit: u32,
+    /// Compute units allotted to each instruction of a builtin program when
+    /// the transaction doesn't request a limit
+    pub builtin_instruction_compute_unit_limit: u32,
+    pub max_compute_unit_limit: u32,
+    /// Compute unit prices are in micro-lamports: the prioritization fee is
+    /// `ceil(compute_unit_price * compute_unit_limit / micro_lamports_per_lamport)`
+    pub micro_lamports_per_lamport: u64,
+    /// Whether fee payers are charged, see the `fee_check` validation option
+    pub fees_charged: bool,
+}
+
+/// Outcome of matching the logs of a simulated transaction
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct LogMatch {
+    /// Whether the simulation succeeded
+    pub success: bool,
+    /// Error of the simulation if it failed
+    pub error: Option<String>,
+    /// Whether any log line matched
+    pub matched: bool,
+    pub matching_lines: Vec<String>,
+    /// Every log line emitted during the simulation
+    pub logs: Vec<String>,
+}
+
+/// Compute units consumed by a program invocation, as reported by the
+/// runtime when it returns
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct InstructionCompute {
+    pub program_id: String,
+    pub units_consumed: u64,
+    /// Units left to the invocation when it started
+    pub units_budget: u64,
+}
+
+/// Structured breakdown of a transaction, built without executing it
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct TransactionBreakdown {
+    /// `"legacy"` or the version number of a versioned message
+    pub version: String,
+    pub signatures: Vec<String>,
+    pub fee_payer: String,
+    pub recent_blockhash: String,
+    /// Static account keys of the message
+    pub account_keys: Vec<AccountKey>,
+    pub address_table_lookups: Vec<AddressTableLookup>,
+    pub instructions: Vec<InstructionBreakdown>,
+}
+
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct AccountKey {
+    pub pubkey: String,
+    pub signer: bool,
+    pub writable: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct AddressTableLookup {
+    pub account_key: String,
+    pub writable_indexes: Vec<u8>,
+    pub readonly_indexes: Vec<u8>,
+}
+
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct InstructionBreakdown {
+    pub program_id: String,
+    /// Accounts passed to the instruction, accounts loaded from lookup
+    /// tables being reported by their index (`#<index>`)
+    pub accounts: Vec<String>,
+    pub data_len: usize,
+}
+
+/// Account a transaction reads or writes
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct TouchedAccount {
+    pub pubkey: String,
+    pub signer: bool,
+    pub writable: bool,
+    /// Lookup table the address was loaded from, `None` for static keys
+    pub lookup_table: Option<String>,
+}
+
+/// An instruction invoked during a transaction, either top-level
+/// (`stack_height` 1) or through a CPI
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct TracedInstruction {
+    /// Index of the top-level instruction this invocation belongs to
+    pub instruction_index: usize,
+    /// Invocation depth, 1 for top-level instructions
+    pub stack_height: u8,
+    pub program_id: String,
+    pub accounts: Vec<String>,
+    pub data_base64: String,
+}
+
+/// Encoding of account data returned by account reads, mirroring the
+/// Solana RPC `encoding` parameter
+#[derive(Deserialize, Serialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum AccountEncoding {
+    #[default]
+    Base64,
+    JsonParsed,
+}
+
+/// An account as returned over the HTTP API
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct UiAccount {
+    pub lamports: u64,
+    pub owner: String,
+    pub data: UiAccountData,
+    pub executable: bool,
+    pub rent_epoch: u64,
+}
+
+/// Account data, either raw (`["<data>", "base64"]`) or parsed into JSON
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum UiAccountData {
+    Binary(String, AccountEncoding),
+    Json(ParsedAccount),
+}
+
+/// Account data parsed from a known program layout
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ParsedAccount {
+    pub program: String,
+    pub parsed: Value,
+    pub space: usize,
+}
+
+/// Envelope of every response of the API
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ApiResponse<T> {
+    pub success: bool,
+    pub data: Option<T>,
+    pub error: Option<String>,
+}
+
+impl<T> ApiResponse<T> {
+    /// The data of a successful response, or its error
+    pub fn into_result(self) -> anyhow::Result<T> {
+        match (self.success, self.data) {
+            (true, Some(data)) => Ok(data),
+            (true, None) => anyhow::bail!("successful response without data"),
+            (false, _) => anyhow::bail!(
+                "{}",
+                self.error.unwrap_or_else(|| "unknown error".to_string())
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_into_result() {
+        let response: ApiResponse<u64> =
+            serde_json::from_str(r#"{"success":true,"data":5,"error":null,"expires_at":"x"}"#)
+                .unwrap();
+        assert_eq!(response.into_result().unwrap(), 5);
+
+        let response: ApiResponse<u64> =
+            serde_json::from_str(r#"{"success":false,"data":null,"error":"Fork not found"}"#)
+                .unwrap();
+        assert_eq!(
+            response.into_result().unwrap_err().to_string(),
+            "Fork not found"
+        );
+    }
+}
@@ -0,0 +1,293 @@
+use std::sync::{Arc, RwLock};
+
+use axum::extract::ws::{Message, WebSocket};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use solana_sdk::transaction::{TransactionError, VersionedTransaction};
+use uuid::Uuid;
+
+use crate::jsonrpc::{
+    self, INTERNAL_ERROR, INVALID_PARAMS, INVALID_REQUEST, JsonRpcResponse, PARSE_ERROR,
+};
+use crate::manager::ForkManager;
+use crate::sync::RwLockExt;
+use crate::{
+    ConcurrencyLimit, ConcurrencyPermit, artificial_delay, decode_transaction, execution_timeout,
+    run_with_timeout,
+};
+
+/// Optional config object of `sendTransaction`
+#[derive(Deserialize, Default)]
+struct SendTransactionConfig {
+    encoding: Option<String>,
+}
+
+/// What to do with a message received on the socket
+enum Incoming {
+    /// Answer right away
+    Reply(Value),
+    /// Acknowledge the transaction with its signature, then execute it and
+    /// notify its status
+    Send {
+        id: Value,
+        tx: Box<VersionedTransaction>,
+    },
+}
+
+/// Serves the JSON-RPC methods of a fork over a WebSocket, along with
+/// `sendTransaction`, which answers with the transaction's signature then
+/// sends a single `signatureNotification` once it is executed, like
+/// `sendTransaction` followed by `signatureSubscribe` on a Solana node.
+///
+/// Transactions are executed like by the HTTP `send` route: each takes a
+/// slot of the concurrency `limit`, runs within the execution timeout and
+/// is followed by the fork's artificial delay.
+pub async fn handle_socket(
+    mut socket: WebSocket,
+    manager: Arc<RwLock<ForkManager>>,
+    fork_id: Uuid,
+    limit: Option<ConcurrencyLimit>,
+) {
+    while let Some(Ok(message)) = socket.recv().await {
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+
+        // Methods may fetch accounts from mainnet, off the async runtime
+        let parsing = Arc::clone(&manager);
+        let parsed =
+            tokio::task::spawn_blocking(move || parse(&parsing, &fork_id, text.as_bytes())).await;
+        let reply = match parsed {
+            Ok(Incoming::Reply(reply)) => reply,
+            Ok(Incoming::Send { id, tx }) => {
+                let permit = limit.as_ref().and_then(ConcurrencyLimit::try_acquire);
+                if limit.is_some() && permit.is_none() {
+                    json!(JsonRpcResponse::error(
+                        id,
+                        INTERNAL_ERROR,
+                        "Server overloaded, retry later"
+                    ))
+                } else {
+                    let ack = json!(JsonRpcResponse::result(
+                        id.clone(),
+                        json!(tx.signatures[0].to_string())
+                    ));
+                    if socket
+                        .send(Message::Text(ack.to_string().into()))
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                    send(&manager, fork_id, permit, id, *tx).await
+                }
+            }
+            Err(e) => json!(JsonRpcResponse::error(
+                Value::Null,
+                INTERNAL_ERROR,
+                format!("request failed: {}", e)
+            )),
+        };
+        if socket
+            .send(Message::Text(reply.to_string().into()))
+            .await
+            .is_err()
+        {
+            return;
+        }
+    }
+}
+
+/// Executes a transaction on the blocking pool within the execution
+/// timeout, returning the `signatureNotification` of its status
+async fn send(
+    manager: &RwLock<ForkManager>,
+    fork_id: Uuid,
+    permit: Option<ConcurrencyPermit>,
+    id: Value,
+    tx: VersionedTransaction,
+) -> Value {
+    // Executed without the manager lock, only the fork's is held
+    let executing = manager.read_or_recover().scoped(&fork_id);
+    let timeout = execution_timeout(manager, None);
+    let request_id = id.clone();
+    let executed = run_with_timeout(timeout, permit, move || {
+        Ok(execute(&executing, &fork_id, id, tx))
+    })
+    .await;
+    artificial_delay(manager, &fork_id).await;
+    match executed {
+        Ok(notification) => notification,
+        // The fork may still be locked, its slot isn't read
+        Err(e) => notification(request_id, 0, json!(e.to_string())),
+    }
+}
+
+/// Handles a message, deferring `sendTransaction` requests to [`execute`]
+fn parse(manager: &RwLock<ForkManager>, fork_id: &Uuid, body: &[u8]) -> Incoming {
+    let request = match serde_json::from_slice::<Value>(body) {
+        Ok(request) => request,
+        Err(e) => {
+            return Incoming::Reply(json!(JsonRpcResponse::error(
+                Value::Null,
+                PARSE_ERROR,
+                e.to_string()
+            )));
+        }
+    };
+    if request.get("method").and_then(Value::as_str) != Some("sendTransaction") {
+        // Answered without the manager lock, only the fork's is held
+        let scoped = manager.read_or_recover().scoped(fork_id);
+        return Incoming::Reply(jsonrpc::handle(&scoped, fork_id, body));
+    }
+
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    if request.get("jsonrpc").and_then(Value::as_str) != Some("2.0") {
+        return Incoming::Reply(json!(JsonRpcResponse::error(
+            id,
+            INVALID_REQUEST,
+            "jsonrpc must be \"2.0\""
+        )));
+    }
+    let params = request.get("params").and_then(Value::as_array);
+    let tx_base64 = params
+        .and_then(|params| params.first())
+        .and_then(Value::as_str);
+    let config = params
+        .and_then(|params| params.get(1))
+        .and_then(|config| serde_json::from_value::<SendTransactionConfig>(config.clone()).ok())
+        .unwrap_or_default();
+
+    let Some(tx_base64) = tx_base64 else {
+        return Incoming::Reply(json!(JsonRpcResponse::error(
+            id,
+            INVALID_PARAMS,
+            "param 0: expected a base64 encoded transaction"
+        )));
+    };
+    if config
+        .encoding
+        .as_deref()
+        .is_some_and(|encoding| encoding != "base64")
+    {
+        return Incoming::Reply(json!(JsonRpcResponse::error(
+            id,
+            INVALID_PARAMS,
+            "only base64 encoded transactions are supported"
+        )));
+    }
+    match decode_transaction(tx_base64) {
+        Ok(tx) if manager.read_or_recover().get_fork(fork_id).is_some() => Incoming::Send {
+            id,
+            tx: Box::new(tx),
+        },
+        Ok(_) => Incoming::Reply(json!(JsonRpcResponse::error(
+            id,
+            INTERNAL_ERROR,
+            "Fork not found"
+        ))),
+        Err(e) => Incoming::Reply(json!(JsonRpcResponse::error(
+            id,
+            INVALID_PARAMS,
+            e.to_string()
+        ))),
+    }
+}
+
+/// Executes a transaction, returning the `signatureNotification` of its
+/// status
+fn execute(manager: &ForkManager, fork_id: &Uuid, id: Value, tx: VersionedTransaction) -> Value {
+    let err = match manager.execute_transaction(fork_id, tx) {
+        Ok(_) => Value::Null,
+        Err(e) => match e.downcast_ref::<TransactionError>() {
+            Some(err) => json!(err),
+            None => json!(format!("{:?}", e)),
+        },
+    };
+    let slot = manager.get_blockhash(fork_id).map_or(0, |(_, slot)| slot);
+    notification(id, slot, err)
+}
+
+/// `signatureNotification` of a transaction's status `err`. Its
+/// subscription is the id of the request.
+fn notification(id: Value, slot: u64, err: Value) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "signatureNotification",
+        "params": {
+            "result": { "context": { "slot": slot }, "value": { "err": err } },
+            "subscription": id,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::ForkOptions;
+    use base64::{Engine, engine};
+    use solana_sdk::{
+        pubkey::Pubkey,
+        signature::{Keypair, Signer},
+        transaction::Transaction,
+    };
+    use solana_system_interface::instruction as system_instruction;
+
+    #[tokio::test]
+    async fn test_send_transaction() {
+        let mut manager = ForkManager::new();
+        let fork_id = manager
+            .create_fork_with_options(ForkOptions {
+                offline: true,
+                ..Default::default()
+            })
+            .unwrap();
+        let payer = Keypair::new();
+        manager
+            .set_lamports(&fork_id, payer.pubkey(), 1_000_000_000, false)
+            .unwrap();
+        let (blockhash, _) = manager.get_blockhash(&fork_id).unwrap();
+        let manager = RwLock::new(manager);
+
+        let ix = system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1_000_000);
+        let tx =
+            Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer], blockhash);
+        let tx_base64 = engine::general_purpose::STANDARD.encode(bincode::serialize(&tx).unwrap());
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 7,
+            "method": "sendTransaction",
+            "params": [tx_base64, { "encoding": "base64" }],
+        });
+
+        let Incoming::Send { id, tx: decoded } =
+            parse(&manager, &fork_id, body.to_string().as_bytes())
+        else {
+            panic!("sendTransaction should be deferred");
+        };
+        assert_eq!(decoded.signatures, tx.signatures);
+        let notification = send(&manager, fork_id, None, id, *decoded).await;
+        assert_eq!(notification["method"], "signatureNotification");
+        assert_eq!(notification["params"]["subscription"], 7);
+        assert_eq!(
+            notification["params"]["result"]["value"]["err"],
+            Value::Null
+        );
+
+        // Other methods are answered right away
+        let body = json!({ "jsonrpc": "2.0", "id": 8, "method": "getLatestBlockhash" });
+        let Incoming::Reply(reply) = parse(&manager, &fork_id, body.to_string().as_bytes()) else {
+            panic!("getLatestBlockhash should be answered right away");
+        };
+        assert_eq!(reply["id"], 8);
+
+        let body =
+            json!({ "jsonrpc": "2.0", "id": 9, "method": "sendTransaction", "params": ["!"] });
+        let Incoming::Reply(reply) = parse(&manager, &fork_id, body.to_string().as_bytes()) else {
+            panic!("invalid transactions should be rejected");
+        };
+        assert_eq!(reply["error"]["code"], INVALID_PARAMS);
+    }
+}